@@ -0,0 +1,164 @@
+// animation.rs - Time-driven object offsets and sky blends for simple animated scenes
+use crate::cube::Cube;
+use raylib::prelude::*;
+use std::f32::consts::PI;
+
+/// Oscilación senoidal de un objeto alrededor de su posición de reposo
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "json-scene", derive(serde::Serialize, serde::Deserialize))]
+pub struct Animation {
+    /// Índice del objeto dentro de `Scene::objects`
+    pub object: usize,
+    #[cfg_attr(feature = "json-scene", serde(with = "crate::json_scene::vec3"))]
+    pub amplitude: Vector3,
+    /// Ciclos por segundo
+    pub frequency: f32,
+    /// Desfase en radianes
+    #[cfg_attr(feature = "json-scene", serde(default))]
+    pub phase: f32,
+}
+
+impl Animation {
+    pub fn offset(&self, time: f32) -> Vector3 {
+        self.amplitude * (2.0 * PI * self.frequency * time + self.phase).sin()
+    }
+}
+
+/// Segundo skybox que se mezcla con el principal, fijo o en vaivén con el tiempo
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json-scene", derive(serde::Serialize, serde::Deserialize))]
+pub struct SkyBlend {
+    pub texture: String,
+    /// Idas y vueltas por segundo entre los dos cielos; 0 deja la mezcla fija en `amount`
+    #[cfg_attr(feature = "json-scene", serde(default))]
+    pub frequency: f32,
+    /// Desfase en radianes
+    #[cfg_attr(feature = "json-scene", serde(default))]
+    pub phase: f32,
+    /// Peso del segundo cielo cuando no hay vaivén: 0 es solo el principal, 1 solo el segundo
+    #[cfg_attr(feature = "json-scene", serde(default))]
+    pub amount: f32,
+}
+
+impl SkyBlend {
+    pub fn is_animated(&self) -> bool {
+        self.frequency > 0.0
+    }
+
+    /// Peso del segundo cielo en `time`: con vaivén parte del principal y llega al segundo
+    /// a mitad de cada ciclo
+    pub fn factor(&self, time: f32) -> f32 {
+        if !self.is_animated() {
+            return self.amount.clamp(0.0, 1.0);
+        }
+        0.5 - 0.5 * (2.0 * PI * self.frequency * time + self.phase).cos()
+    }
+}
+
+struct AnimatedCube {
+    animation: Animation,
+    rest_min: Vector3,
+    rest_max: Vector3,
+}
+
+/// Mueve los cubos animados cada frame a partir de sus límites en reposo
+pub struct Animator {
+    animated: Vec<AnimatedCube>,
+}
+
+impl Animator {
+    pub fn new(animations: &[Animation], objects: &[Cube]) -> Self {
+        let animated = animations
+            .iter()
+            .filter_map(|animation| {
+                let cube = objects.get(animation.object)?;
+                Some(AnimatedCube {
+                    animation: animation.clone(),
+                    rest_min: cube.min_bounds,
+                    rest_max: cube.max_bounds,
+                })
+            })
+            .collect();
+
+        Animator { animated }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.animated.is_empty()
+    }
+
+    pub fn update(&self, time: f32, objects: &mut [Cube]) {
+        for animated in &self.animated {
+            let offset = animated.animation.offset(time);
+            let cube = &mut objects[animated.animation.object];
+            cube.min_bounds = animated.rest_min + offset;
+            cube.max_bounds = animated.rest_max + offset;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Material;
+
+    #[test]
+    fn update_offsets_from_rest_position() {
+        let mut objects = vec![
+            Cube::new(Vector3::zero(), 1.0, Material::black()),
+            Cube::new(Vector3::new(3.0, 0.0, 0.0), 1.0, Material::black()),
+        ];
+        let animation = Animation {
+            object: 1,
+            amplitude: Vector3::new(0.0, 2.0, 0.0),
+            frequency: 0.5,
+            phase: 0.0,
+        };
+        let animator = Animator::new(&[animation], &objects);
+
+        // Un cuarto de periodo: desplazamiento máximo
+        animator.update(0.5, &mut objects);
+        assert!((objects[1].min_bounds.y - 1.5).abs() < 1e-5);
+        assert!((objects[1].max_bounds.y - 2.5).abs() < 1e-5);
+        assert_eq!(objects[0].min_bounds, Vector3::new(-0.5, -0.5, -0.5));
+
+        // Un periodo completo vuelve al reposo sin acumular desplazamientos
+        animator.update(2.0, &mut objects);
+        assert!((objects[1].min_bounds.y + 0.5).abs() < 1e-5);
+        assert!((objects[1].max_bounds.y - 0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn sky_blend_swings_between_both_skies() {
+        let blend = SkyBlend {
+            texture: "night.png".to_string(),
+            frequency: 0.25,
+            phase: 0.0,
+            amount: 0.5,
+        };
+        assert_eq!(blend.factor(0.0), 0.0);
+        assert!((blend.factor(2.0) - 1.0).abs() < 1e-6);
+        assert!((blend.factor(1.0) - 0.5).abs() < 1e-6);
+        assert!(blend.factor(4.0).abs() < 1e-6);
+
+        let fixed = SkyBlend {
+            frequency: 0.0,
+            ..blend
+        };
+        assert!(!fixed.is_animated());
+        assert_eq!(fixed.factor(0.0), 0.5);
+        assert_eq!(fixed.factor(7.3), 0.5);
+    }
+
+    #[test]
+    fn animations_for_missing_objects_are_ignored() {
+        let objects = vec![Cube::new(Vector3::zero(), 1.0, Material::black())];
+        let animation = Animation {
+            object: 4,
+            amplitude: Vector3::one(),
+            frequency: 1.0,
+            phase: 0.0,
+        };
+        assert!(Animator::new(&[animation], &objects).is_empty());
+    }
+}
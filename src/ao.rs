@@ -0,0 +1,321 @@
+// ao.rs - Ambient occlusion, evaluated per frame or baked once for static scenes
+use crate::bvh::BVHNode;
+use crate::cube::Cube;
+use raylib::prelude::*;
+use std::f32::consts::PI;
+
+const AO_BIAS: f32 = 1e-3;
+// Pequeño margen para no muestrear exactamente sobre las aristas del cubo
+const FACE_INSET: f32 = 1e-3;
+
+#[derive(Clone)]
+pub struct AmbientOcclusion {
+    /// Desactivada por defecto: suma un término ambiental que cambia el aspecto de la escena
+    pub enabled: bool,
+    pub force_per_frame: bool,
+    pub radius: f32,
+    pub strength: f32,
+    pub ambient: Vector3,
+    pub bake_resolution: usize,
+    kernel: Vec<Vector3>,
+}
+
+impl AmbientOcclusion {
+    pub fn new(samples: u32, radius: f32) -> Self {
+        AmbientOcclusion {
+            enabled: false,
+            force_per_frame: false,
+            radius,
+            strength: 1.0,
+            ambient: Vector3::new(0.12, 0.05, 0.05),
+            bake_resolution: 8,
+            kernel: hemisphere_kernel(samples),
+        }
+    }
+
+    /// Visibilidad del hemisferio en `point`: 1.0 sin oclusión, 0.0 totalmente ocluido
+    pub fn visibility(
+        &self,
+        point: Vector3,
+        normal: Vector3,
+        bvh: &BVHNode,
+        objects: &[Cube],
+    ) -> f32 {
+        if self.kernel.is_empty() {
+            return 1.0;
+        }
+
+        let (tangent, bitangent) = tangent_frame(normal);
+        let origin = point + normal * AO_BIAS;
+        let mut occluded = 0;
+
+        for local in &self.kernel {
+            let dir = tangent * local.x + bitangent * local.y + normal * local.z;
+            let inv_dir = Vector3::new(1.0 / dir.x, 1.0 / dir.y, 1.0 / dir.z);
+            let hit = bvh.intersect(objects, &origin, &dir, &inv_dir);
+
+            if hit.is_intersecting && hit.distance < self.radius {
+                occluded += 1;
+            }
+        }
+
+        let occlusion = occluded as f32 / self.kernel.len() as f32;
+        (1.0 - occlusion * self.strength).clamp(0.0, 1.0)
+    }
+}
+
+/// Direcciones deterministas (espiral de Fibonacci) distribuidas por coseno sobre el hemisferio +Z.
+/// Al ser fijas, el resultado horneado coincide con el calculado por frame.
+fn hemisphere_kernel(samples: u32) -> Vec<Vector3> {
+    let golden_angle = PI * (3.0 - 5.0_f32.sqrt());
+
+    (0..samples)
+        .map(|i| {
+            let r = ((i as f32 + 0.5) / samples as f32).sqrt();
+            let phi = i as f32 * golden_angle;
+            let z = (1.0 - r * r).max(0.0).sqrt();
+            Vector3::new(r * phi.cos(), r * phi.sin(), z)
+        })
+        .collect()
+}
+
+fn tangent_frame(normal: Vector3) -> (Vector3, Vector3) {
+    let helper = if normal.x.abs() > 0.9 {
+        Vector3::new(0.0, 1.0, 0.0)
+    } else {
+        Vector3::new(1.0, 0.0, 0.0)
+    };
+    let tangent = helper.cross(normal).normalized();
+    let bitangent = normal.cross(tangent);
+    (tangent, bitangent)
+}
+
+/// Cara del cubo a partir de su normal: -X, +X, -Y, +Y, -Z, +Z
+fn face_index(normal: Vector3) -> Option<usize> {
+    if normal.x < -0.5 {
+        Some(0)
+    } else if normal.x > 0.5 {
+        Some(1)
+    } else if normal.y < -0.5 {
+        Some(2)
+    } else if normal.y > 0.5 {
+        Some(3)
+    } else if normal.z < -0.5 {
+        Some(4)
+    } else if normal.z > 0.5 {
+        Some(5)
+    } else {
+        None
+    }
+}
+
+fn face_normal(face: usize) -> Vector3 {
+    match face {
+        0 => Vector3::new(-1.0, 0.0, 0.0),
+        1 => Vector3::new(1.0, 0.0, 0.0),
+        2 => Vector3::new(0.0, -1.0, 0.0),
+        3 => Vector3::new(0.0, 1.0, 0.0),
+        4 => Vector3::new(0.0, 0.0, -1.0),
+        _ => Vector3::new(0.0, 0.0, 1.0),
+    }
+}
+
+fn component(v: Vector3, axis: usize) -> f32 {
+    match axis {
+        0 => v.x,
+        1 => v.y,
+        _ => v.z,
+    }
+}
+
+/// Ejes (normal, s, t) que parametrizan cada cara
+fn face_axes(face: usize) -> (usize, usize, usize) {
+    match face / 2 {
+        0 => (0, 2, 1),
+        1 => (1, 0, 2),
+        _ => (2, 0, 1),
+    }
+}
+
+fn face_point(cube: &Cube, face: usize, s: f32, t: f32) -> Vector3 {
+    let (axis, s_axis, t_axis) = face_axes(face);
+    let mut coords = [0.0; 3];
+
+    coords[axis] = if face.is_multiple_of(2) {
+        component(cube.min_bounds, axis)
+    } else {
+        component(cube.max_bounds, axis)
+    };
+
+    let s = s.clamp(FACE_INSET, 1.0 - FACE_INSET);
+    let t = t.clamp(FACE_INSET, 1.0 - FACE_INSET);
+    let min = cube.min_bounds;
+    let max = cube.max_bounds;
+    coords[s_axis] = component(min, s_axis) + s * (component(max, s_axis) - component(min, s_axis));
+    coords[t_axis] = component(min, t_axis) + t * (component(max, t_axis) - component(min, t_axis));
+
+    Vector3::new(coords[0], coords[1], coords[2])
+}
+
+fn face_coords(cube: &Cube, face: usize, point: Vector3) -> (f32, f32) {
+    let (_, s_axis, t_axis) = face_axes(face);
+    let min = cube.min_bounds;
+    let max = cube.max_bounds;
+    let s = (component(point, s_axis) - component(min, s_axis))
+        / (component(max, s_axis) - component(min, s_axis));
+    let t = (component(point, t_axis) - component(min, t_axis))
+        / (component(max, t_axis) - component(min, t_axis));
+    (s.clamp(0.0, 1.0), t.clamp(0.0, 1.0))
+}
+
+/// Oclusión precalculada sobre una rejilla de puntos en cada cara de cada cubo.
+/// Solo es válida mientras los cubos no se muevan.
+pub struct AOBake {
+    resolution: usize,
+    values: Vec<f32>,
+}
+
+impl AOBake {
+    pub fn bake(bvh: &BVHNode, objects: &[Cube], ao: &AmbientOcclusion) -> Self {
+        let resolution = ao.bake_resolution.max(1);
+        let side = resolution + 1;
+        let mut values = Vec::with_capacity(objects.len() * 6 * side * side);
+
+        for cube in objects {
+            // Las paredes talladas por un cubo sustractivo quedan en sus caras interiores,
+            // que la rejilla de las exteriores no cubre: esas se calculan en cada frame
+            if cube.subtractive {
+                values.resize(values.len() + 6 * side * side, 1.0);
+                continue;
+            }
+            for face in 0..6 {
+                let normal = face_normal(face);
+                for j in 0..side {
+                    for i in 0..side {
+                        let s = i as f32 / resolution as f32;
+                        let t = j as f32 / resolution as f32;
+                        let point = face_point(cube, face, s, t);
+                        values.push(ao.visibility(point, normal, bvh, objects));
+                    }
+                }
+            }
+        }
+
+        AOBake { resolution, values }
+    }
+
+    /// Interpola bilinealmente la visibilidad horneada en el punto de impacto; `None` en los
+    /// cubos sustractivos, que no se hornean
+    pub fn sample(
+        &self,
+        objects: &[Cube],
+        object_idx: usize,
+        point: Vector3,
+        normal: Vector3,
+    ) -> Option<f32> {
+        let face = face_index(normal)?;
+        let cube = objects.get(object_idx).filter(|cube| !cube.subtractive)?;
+        let (s, t) = face_coords(cube, face, point);
+
+        let side = self.resolution + 1;
+        let base = (object_idx * 6 + face) * side * side;
+
+        let fs = s * self.resolution as f32;
+        let ft = t * self.resolution as f32;
+        let i0 = (fs as usize).min(self.resolution - 1);
+        let j0 = (ft as usize).min(self.resolution - 1);
+        let ds = fs - i0 as f32;
+        let dt = ft - j0 as f32;
+
+        let at = |i: usize, j: usize| self.values.get(base + j * side + i).copied();
+        let top = at(i0, j0)? * (1.0 - ds) + at(i0 + 1, j0)? * ds;
+        let bottom = at(i0, j0 + 1)? * (1.0 - ds) + at(i0 + 1, j0 + 1)? * ds;
+
+        Some(top * (1.0 - dt) + bottom * dt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Material;
+
+    #[test]
+    fn bake_matches_per_frame_visibility() {
+        // Suelo de 5x5 con una pared al fondo y un bloque en el centro: sus caras tienen
+        // oclusión muy variada. El cubo del suelo en (x, z) es el (x + 2) * 5 + z + 2
+        let mut objects = Vec::new();
+        for x in -2..=2 {
+            for z in -2..=2 {
+                objects.push(Cube::new(
+                    Vector3::new(x as f32, 0.0, z as f32),
+                    1.0,
+                    Material::black(),
+                ));
+            }
+        }
+        for x in -2..=2 {
+            objects.push(Cube::new(
+                Vector3::new(x as f32, 1.0, -2.0),
+                1.0,
+                Material::black(),
+            ));
+        }
+        objects.push(Cube::new(
+            Vector3::new(0.0, 1.0, 0.0),
+            1.0,
+            Material::black(),
+        ));
+
+        let mut indices: Vec<usize> = (0..objects.len()).collect();
+        let bvh = BVHNode::build(&objects, &mut indices);
+        let mut ao = AmbientOcclusion::new(16, 1.0);
+        ao.enabled = true;
+        let bake = AOBake::bake(&bvh, &objects, &ao);
+
+        let up = Vector3::new(0.0, 1.0, 0.0);
+        let front = Vector3::new(0.0, 0.0, 1.0);
+        let points = [
+            // Suelo junto al bloque central, junto a la pared y lejos de ambos
+            (7, Vector3::new(-0.8, 0.5, 0.3), up),
+            (13, Vector3::new(0.3, 0.5, 0.55), up),
+            (11, Vector3::new(0.0, 0.5, -1.45), up),
+            (24, Vector3::new(2.2, 0.5, 2.3), up),
+            // Cara frontal de la pared y cara superior del bloque
+            (25, Vector3::new(-2.0, 1.1, -1.5), front),
+            (30, Vector3::new(0.1, 1.5, -0.2), up),
+        ];
+        for (idx, point, normal) in points {
+            let baked = bake.sample(&objects, idx, point, normal).unwrap();
+            let per_frame = ao.visibility(point, normal, &bvh, &objects);
+            assert!(
+                (baked - per_frame).abs() < 0.1,
+                "cube {} at {:?}: baked {} vs per frame {}",
+                idx,
+                point,
+                baked,
+                per_frame
+            );
+        }
+    }
+    #[test]
+    fn subtractive_cubes_are_not_baked() {
+        let mut hole = Cube::new(Vector3::new(0.0, 0.5, 0.0), 0.5, Material::black());
+        hole.subtractive = true;
+        let objects = vec![Cube::new(Vector3::zero(), 2.0, Material::black()), hole];
+        let mut indices: Vec<usize> = (0..objects.len()).collect();
+        let bvh = BVHNode::build(&objects, &mut indices);
+        let bake = AOBake::bake(&bvh, &objects, &AmbientOcclusion::new(4, 1.0));
+
+        let up = Vector3::new(0.0, 1.0, 0.0);
+        // El fondo del hueco es la cara inferior del cubo sustractivo, vista desde dentro
+        assert_eq!(
+            bake.sample(&objects, 1, Vector3::new(0.0, 0.25, 0.0), up),
+            None
+        );
+        assert!(
+            bake.sample(&objects, 0, Vector3::new(0.8, 1.0, 0.8), up)
+                .is_some()
+        );
+    }
+}
@@ -0,0 +1,112 @@
+// axis.rs - World up axis: Y-up (the default) or Z-up scenes from CAD and voxel tools
+use raylib::prelude::Vector3;
+
+/// Eje que apunta hacia arriba en la escena. El cielo y la órbita de la cámara se calculan
+/// en Y arriba, así que las escenas Z arriba se rotan a ese marco y de vuelta.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "json-scene",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum UpAxis {
+    #[default]
+    Y,
+    /// +Z arriba y -Y hacia delante, como Blender y la mayoría de programas de CAD
+    Z,
+}
+
+impl UpAxis {
+    pub fn name(self) -> &'static str {
+        match self {
+            UpAxis::Y => "y",
+            UpAxis::Z => "z",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "y" => Some(UpAxis::Y),
+            "z" => Some(UpAxis::Z),
+            _ => None,
+        }
+    }
+
+    pub fn up(self) -> Vector3 {
+        match self {
+            UpAxis::Y => Vector3::new(0.0, 1.0, 0.0),
+            UpAxis::Z => Vector3::new(0.0, 0.0, 1.0),
+        }
+    }
+
+    /// Lleva un vector de la escena al marco Y arriba: -90° alrededor de X para Z arriba
+    pub fn to_y_up(self, v: Vector3) -> Vector3 {
+        match self {
+            UpAxis::Y => v,
+            UpAxis::Z => Vector3::new(v.x, v.z, -v.y),
+        }
+    }
+
+    /// Inversa de `to_y_up`
+    pub fn to_scene(self, v: Vector3) -> Vector3 {
+        match self {
+            UpAxis::Y => v,
+            UpAxis::Z => Vector3::new(v.x, -v.z, v.y),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::Camera;
+
+    fn assert_close(a: Vector3, b: Vector3) {
+        assert!((a - b).length() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn conversions_are_inverse_rotations() {
+        let v = Vector3::new(0.3, -1.2, 2.5);
+        for axis in [UpAxis::Y, UpAxis::Z] {
+            assert_close(axis.to_scene(axis.to_y_up(v)), v);
+            assert_close(axis.to_y_up(axis.up()), Vector3::new(0.0, 1.0, 0.0));
+            assert!((axis.to_y_up(v).length() - v.length()).abs() < 1e-5);
+        }
+        assert_eq!(UpAxis::from_name("z"), Some(UpAxis::Z));
+        assert_eq!(UpAxis::from_name(UpAxis::Y.name()), Some(UpAxis::Y));
+        assert_eq!(UpAxis::from_name("x"), None);
+    }
+
+    #[test]
+    fn z_up_camera_sees_the_rotated_scene_the_same_way() {
+        // La misma escena descrita en Y arriba y rotada a Z arriba: cada rayo de la cámara
+        // Z arriba es el rayo Y arriba rotado, también tras orbitar y en la vista cenital
+        let (eye, center) = (Vector3::new(4.0, 3.0, 6.0), Vector3::new(0.5, 0.0, -1.0));
+        let z = UpAxis::Z;
+        let mut y_camera = Camera::new(eye, center, UpAxis::Y.up());
+        let mut z_camera = Camera::new(z.to_scene(eye), z.to_scene(center), z.up());
+        z_camera.up_axis = z;
+
+        let rays = [
+            Vector3::new(0.0, 0.0, -1.0),
+            Vector3::new(0.4, -0.2, -1.0),
+            Vector3::new(-0.7, 0.5, -1.0),
+        ];
+        let check = |y_camera: &Camera, z_camera: &Camera| {
+            assert_close(z_camera.eye, z.to_scene(y_camera.eye));
+            for ray in rays {
+                assert_close(
+                    z_camera.basis_change(&ray),
+                    z.to_scene(y_camera.basis_change(&ray)),
+                );
+            }
+        };
+
+        check(&y_camera, &z_camera);
+        y_camera.orbit(0.6, -0.3);
+        z_camera.orbit(0.6, -0.3);
+        check(&y_camera, &z_camera);
+        check(&y_camera.top_view(), &z_camera.top_view());
+    }
+}
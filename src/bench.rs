@@ -0,0 +1,236 @@
+// bench.rs - Headless timing harness for the render pipeline (`cargo run --release -- --bench`)
+use crate::ao::AOBake;
+use crate::bvh::BVHNode;
+use crate::camera::Camera;
+use crate::cube::Cube;
+use crate::framebuffer::Framebuffer;
+use crate::light::{Light, derive_emissive_lights};
+use crate::material::Material;
+use crate::textures::TextureManager;
+use crate::{RenderConfig, render};
+use raylib::prelude::*;
+use std::f32::consts::PI;
+use std::time::Instant;
+
+const WIDTH: i32 = 640;
+const HEIGHT: i32 = 360;
+const WARMUP_FRAMES: u32 = 1;
+const TIMED_FRAMES: u32 = 5;
+
+struct BenchScene {
+    name: &'static str,
+    objects: Vec<Cube>,
+    lights: Vec<Light>,
+}
+
+fn material(diffuse: Vector3, specular: f32, reflectivity: f32, transparency: f32) -> Material {
+    Material {
+        diffuse,
+        albedo: [0.9, 0.1],
+        specular,
+        reflectivity,
+        transparency,
+        refractive_index: if transparency > 0.0 { 1.3 } else { 0.0 },
+        ..Default::default()
+    }
+}
+
+fn emissive() -> Material {
+    Material {
+        emission: Vector3::new(1.0, 0.45, 0.15),
+        emission_strength: 1.2,
+        ..material(Vector3::new(0.95, 0.6, 0.3), 15.0, 0.0, 0.0)
+    }
+}
+
+fn lights_for(objects: &[Cube]) -> Vec<Light> {
+    let mut lights = vec![Light::new(
+        Vector3::new(5.0, 8.0, 5.0),
+        Vector3::new(1.0, 0.7, 0.5),
+        1.3,
+    )];
+    lights.extend(derive_emissive_lights(objects));
+    lights
+}
+
+/// Los seis cubos de la escena por defecto
+fn small_scene() -> BenchScene {
+    let objects = vec![
+        Cube::new(
+            Vector3::new(-2.5, 0.0, 0.0),
+            1.5,
+            material(Vector3::new(0.15, 0.1, 0.2), 90.0, 0.1, 0.0),
+        ),
+        Cube::new(Vector3::new(0.0, 0.0, -1.0), 1.5, emissive()),
+        Cube::new(
+            Vector3::new(2.5, 0.0, 0.0),
+            1.5,
+            material(Vector3::new(0.5, 0.1, 0.15), 5.0, 0.0, 0.0),
+        ),
+        Cube::new(
+            Vector3::new(-1.5, 0.0, 2.0),
+            1.5,
+            material(Vector3::new(0.4, 0.15, 0.35), 15.0, 0.0, 0.0),
+        ),
+        Cube::new(
+            Vector3::new(1.5, 0.0, 2.0),
+            1.5,
+            material(Vector3::new(0.5, 0.05, 0.08), 8.0, 0.0, 0.0),
+        ),
+        Cube::new(
+            Vector3::new(0.0, 0.0, 3.0),
+            1.5,
+            material(Vector3::new(0.8, 0.8, 0.8), 10.0, 0.0, 0.5),
+        ),
+    ];
+
+    BenchScene {
+        name: "small (6 cubes)",
+        lights: lights_for(&objects),
+        objects,
+    }
+}
+
+/// Terreno de 32x32 bloques con columnas, paredes reflectivas y un portal
+fn dense_scene() -> BenchScene {
+    let ground = material(Vector3::new(0.5, 0.1, 0.15), 5.0, 0.0, 0.0);
+    let stem = material(Vector3::new(0.4, 0.15, 0.35), 15.0, 0.0, 0.0);
+    let wall = material(Vector3::new(0.15, 0.1, 0.2), 90.0, 0.1, 0.0);
+    let glass = material(Vector3::new(0.8, 0.8, 0.8), 10.0, 0.0, 0.5);
+
+    let size = 0.5;
+    let mut objects = Vec::new();
+
+    for i in 0..32 {
+        for k in 0..32 {
+            let x = (i as f32 - 16.0) * size;
+            let z = (k as f32 - 16.0) * size;
+            // Relieve determinista para que los rayos recorran alturas distintas
+            let y = -2.0 - ((i * 7 + k * 13) % 3) as f32 * 0.25;
+            objects.push(Cube::new(Vector3::new(x, y, z), size, ground.clone()));
+        }
+    }
+
+    for (i, k) in [(4, 4), (4, 27), (27, 4), (27, 27), (10, 20), (20, 10)] {
+        for level in 0..6 {
+            let center = Vector3::new(
+                (i as f32 - 16.0) * size,
+                -1.5 + level as f32 * size,
+                (k as f32 - 16.0) * size,
+            );
+            let mat = if level == 5 { emissive() } else { stem.clone() };
+            objects.push(Cube::new(center, size, mat));
+        }
+    }
+
+    for i in 0..12 {
+        for level in 0..4 {
+            let center = Vector3::new(-3.0 + i as f32 * size, -1.5 + level as f32 * size, -4.0);
+            objects.push(Cube::new(center, size, wall.clone()));
+        }
+    }
+
+    for level in 0..3 {
+        objects.push(Cube::new(
+            Vector3::new(0.0, -1.5 + level as f32 * size, 1.0),
+            size,
+            glass.clone(),
+        ));
+    }
+
+    BenchScene {
+        name: "dense (32x32 terrain)",
+        lights: lights_for(&objects),
+        objects,
+    }
+}
+
+fn millis(start: Instant) -> f64 {
+    start.elapsed().as_secs_f64() * 1000.0
+}
+
+/// Tiempo mínimo y medio por frame, tras descartar los de calentamiento
+fn time_frames(
+    scene: &BenchScene,
+    bvh: &BVHNode,
+    render_config: &RenderConfig,
+    ao_bake: Option<&AOBake>,
+) -> (f64, f64) {
+    let texture_manager = TextureManager::new();
+    let mut framebuffer = Framebuffer::new(WIDTH, HEIGHT);
+    let camera = Camera::new(
+        Vector3::new(0.0, 2.0, 8.0),
+        Vector3::new(0.0, 0.0, 0.0),
+        Vector3::new(0.0, 1.0, 0.0),
+    );
+
+    let mut frame_ms = Vec::with_capacity(TIMED_FRAMES as usize);
+    for frame in 0..WARMUP_FRAMES + TIMED_FRAMES {
+        let start = Instant::now();
+        render(
+            &mut framebuffer,
+            bvh,
+            &scene.objects,
+            &camera,
+            &scene.lights,
+            &texture_manager,
+            render_config,
+            None,
+            ao_bake,
+            None,
+        );
+        if frame >= WARMUP_FRAMES {
+            frame_ms.push(millis(start));
+        }
+    }
+
+    let min = frame_ms.iter().cloned().fold(f64::INFINITY, f64::min);
+    let avg = frame_ms.iter().sum::<f64>() / frame_ms.len() as f64;
+    (min, avg)
+}
+
+fn bench_scene(scene: &BenchScene, max_leaf_size: usize, with_ao: bool) {
+    // La configuración por defecto, la misma que usa el programa sin opciones
+    let mut render_config = RenderConfig::new(WIDTH, HEIGHT, PI / 3.0);
+
+    let start = Instant::now();
+    let mut indices: Vec<usize> = (0..scene.objects.len()).collect();
+    let bvh = BVHNode::build_with_leaf_size(&scene.objects, &mut indices, max_leaf_size);
+    let build_ms = millis(start);
+
+    let (min, avg) = time_frames(scene, &bvh, &render_config, None);
+
+    println!("{}", scene.name);
+    println!(
+        "  objects: {}, lights: {}",
+        scene.objects.len(),
+        scene.lights.len()
+    );
+    println!(
+        "  BVH build ({} per leaf): {:.2} ms",
+        max_leaf_size, build_ms
+    );
+    println!(
+        "  frame {}x{}: min {:.2} ms, avg {:.2} ms over {} frames",
+        WIDTH, HEIGHT, min, avg, TIMED_FRAMES
+    );
+
+    // La oclusión ambiental es opcional: su horneado y sus frames van en una fila aparte
+    if with_ao {
+        render_config.ao.enabled = true;
+        let start = Instant::now();
+        let ao_bake = AOBake::bake(&bvh, &scene.objects, &render_config.ao);
+        let bake_ms = millis(start);
+        let (min, avg) = time_frames(scene, &bvh, &render_config, Some(&ao_bake));
+        println!(
+            "  with AO: bake {:.2} ms, frame min {:.2} ms, avg {:.2} ms",
+            bake_ms, min, avg
+        );
+    }
+}
+
+pub fn run(max_leaf_size: usize, with_ao: bool) {
+    for scene in [small_scene(), dense_scene()] {
+        bench_scene(&scene, max_leaf_size, with_ao);
+    }
+}
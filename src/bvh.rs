@@ -1,7 +1,20 @@
 // bvh.rs - Bounding Volume Hierarchy for spatial acceleration
-use crate::cube::Cube;
+use crate::cube::{Cube, HIT_EPSILON};
+use crate::instance::Instance;
 use crate::ray_intersect::{Intersect, RayIntersect};
 use raylib::prelude::*;
+use std::thread;
+
+// Por debajo de este número de cubos un subárbol se construye en el mismo hilo: lanzar otro
+// cuesta más que ordenarlo
+const PARALLEL_BUILD_THRESHOLD: usize = 1024;
+
+// Tramos máximos de un rayo que cruza huecos encadenados; corta los bucles que podría
+// provocar el redondeo en caras coincidentes
+const MAX_CARVE_STEPS: usize = 64;
+
+/// Objetos por hoja si no se pide otra cosa: uno, el árbol más profundo
+pub const DEFAULT_LEAF_SIZE: usize = 1;
 
 pub struct AABB {
     pub min: Vector3,
@@ -32,6 +45,16 @@ impl AABB {
     }
 
     pub fn intersect(&self, ray_origin: &Vector3, inv_dir: &Vector3) -> bool {
+        self.intersect_within(ray_origin, inv_dir, f32::INFINITY)
+    }
+
+    /// Como `intersect`, pero descarta la caja si empieza más lejos que `max_distance`
+    pub fn intersect_within(
+        &self,
+        ray_origin: &Vector3,
+        inv_dir: &Vector3,
+        max_distance: f32,
+    ) -> bool {
         let mut tmin = (self.min.x - ray_origin.x) * inv_dir.x;
         let mut tmax = (self.max.x - ray_origin.x) * inv_dir.x;
         if tmin > tmax {
@@ -63,8 +86,49 @@ impl AABB {
         if tmin > tzmax || tzmin > tmax {
             return false;
         }
+        if tzmin > tmin {
+            tmin = tzmin;
+        }
 
-        true
+        // Un NaN de los casos límite no debe descartar la caja
+        tmin.is_nan() || tmin <= max_distance
+    }
+
+    pub fn contains(&self, point: Vector3) -> bool {
+        point.x >= self.min.x
+            && point.x <= self.max.x
+            && point.y >= self.min.y
+            && point.y <= self.max.y
+            && point.z >= self.min.z
+            && point.z <= self.max.z
+    }
+
+    /// Distancias desde `point` al punto más cercano de la caja (0 si está dentro) y a su
+    /// esquina más lejana: todo lo que hay dentro queda entre las dos
+    pub fn distance_range(&self, point: Vector3) -> (f32, f32) {
+        let nearest = Vector3::new(
+            point.x.clamp(self.min.x, self.max.x),
+            point.y.clamp(self.min.y, self.max.y),
+            point.z.clamp(self.min.z, self.max.z),
+        );
+        let farthest = Vector3::new(
+            if point.x * 2.0 < self.min.x + self.max.x {
+                self.max.x
+            } else {
+                self.min.x
+            },
+            if point.y * 2.0 < self.min.y + self.max.y {
+                self.max.y
+            } else {
+                self.min.y
+            },
+            if point.z * 2.0 < self.min.z + self.max.z {
+                self.max.z
+            } else {
+                self.min.z
+            },
+        );
+        ((nearest - point).length(), (farthest - point).length())
     }
 
     pub fn center(&self) -> Vector3 {
@@ -76,70 +140,386 @@ impl AABB {
     }
 }
 
+/// Eje en el que la caja es más larga: 0 = X, 1 = Y, 2 = Z
+fn longest_axis(bounds: &AABB) -> usize {
+    let extent = bounds.max - bounds.min;
+    if extent.x > extent.y && extent.x > extent.z {
+        0
+    } else if extent.y > extent.z {
+        1
+    } else {
+        2
+    }
+}
+
+fn axis_component(v: Vector3, axis: usize) -> f32 {
+    match axis {
+        0 => v.x,
+        1 => v.y,
+        _ => v.z,
+    }
+}
+
+/// Caja que envuelve los cubos de `indices`
+fn cubes_bounds(cubes: &[Cube], indices: &[usize]) -> AABB {
+    let mut bounds = AABB::from_cube(&cubes[indices[0]]);
+    for &idx in indices.iter().skip(1) {
+        bounds = bounds.merge(&AABB::from_cube(&cubes[idx]));
+    }
+    bounds
+}
+
+fn objects_label(indices: &[usize]) -> &'static str {
+    if indices.len() == 1 {
+        "object"
+    } else {
+        "objects"
+    }
+}
+
+fn format_indices(indices: &[usize]) -> String {
+    indices
+        .iter()
+        .map(|idx| idx.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn format_vector(v: Vector3) -> String {
+    format!("{} {} {}", v.x, v.y, v.z)
+}
+
+fn format_bounds(bounds: &AABB) -> String {
+    format!(
+        "[{} {} {}]..[{} {} {}]",
+        bounds.min.x, bounds.min.y, bounds.min.z, bounds.max.x, bounds.max.y, bounds.max.z
+    )
+}
+
+/// Tamaño del árbol de la escena. Los BVH de los prototipos se comparten entre instancias
+/// y no cuentan como nodos; sus cubos sí cuentan como primitivas, una vez por instancia.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BVHStats {
+    pub nodes: usize,
+    pub leaves: usize,
+    /// Profundidad de la hoja más honda; la raíz está a profundidad 0
+    pub depth: usize,
+    pub primitives: usize,
+}
+
 pub enum BVHNode {
-    Leaf {
-        bounds: AABB,
-        object_idx: usize,
-    },
+    /// Hoja con hasta el tamaño de hoja pedido de objetos, que se prueban uno a uno
+    Leaf { bounds: AABB, objects: Vec<usize> },
     Internal {
         bounds: AABB,
         left: Box<BVHNode>,
         right: Box<BVHNode>,
+        /// Algún cubo de resta cuelga de este nodo
+        carves: bool,
     },
+    /// Hoja del segundo nivel: remite al BVH compartido de un prototipo
+    Instance { bounds: AABB, instance: Instance },
 }
 
 impl BVHNode {
+    /// Construye el árbol con un objeto por hoja
     pub fn build(cubes: &[Cube], indices: &mut [usize]) -> Self {
-        if indices.len() == 1 {
-            let idx = indices[0];
+        BVHNode::build_with_leaf_size(cubes, indices, DEFAULT_LEAF_SIZE)
+    }
+
+    /// Construye el árbol repartiendo los subárboles grandes de los niveles superiores entre
+    /// los núcleos disponibles. El resultado es idéntico al de la construcción en serie.
+    /// Las hojas guardan hasta `max_leaf_size` objetos: un árbol menos profundo con menos
+    /// nodos que recorrer a cambio de probar varios cubos en cada hoja.
+    pub fn build_with_leaf_size(
+        cubes: &[Cube],
+        indices: &mut [usize],
+        max_leaf_size: usize,
+    ) -> Self {
+        let cores = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1);
+        // Cada nivel paralelo duplica los hilos; basta con llegar al número de núcleos
+        let parallel_levels = usize::BITS - (cores - 1).leading_zeros();
+        BVHNode::build_node(cubes, indices, max_leaf_size.max(1), parallel_levels)
+    }
+
+    fn build_node(
+        cubes: &[Cube],
+        indices: &mut [usize],
+        max_leaf_size: usize,
+        parallel_levels: u32,
+    ) -> Self {
+        let bounds = cubes_bounds(cubes, indices);
+        if indices.len() <= max_leaf_size {
             return BVHNode::Leaf {
-                bounds: AABB::from_cube(&cubes[idx]),
-                object_idx: idx,
+                bounds,
+                objects: indices.to_vec(),
             };
         }
 
-        let mut bounds = AABB::from_cube(&cubes[indices[0]]);
-        for &idx in indices.iter().skip(1) {
-            bounds = bounds.merge(&AABB::from_cube(&cubes[idx]));
-        }
+        let axis = longest_axis(&bounds);
 
-        let extent = Vector3::new(
-            bounds.max.x - bounds.min.x,
-            bounds.max.y - bounds.min.y,
-            bounds.max.z - bounds.min.z,
-        );
+        indices.sort_by(|&a, &b| {
+            let va = axis_component(AABB::from_cube(&cubes[a]).center(), axis);
+            let vb = axis_component(AABB::from_cube(&cubes[b]).center(), axis);
+            va.partial_cmp(&vb).unwrap()
+        });
+
+        let count = indices.len();
+        let (left_indices, right_indices) = indices.split_at_mut(count / 2);
 
-        let axis = if extent.x > extent.y && extent.x > extent.z {
-            0
-        } else if extent.y > extent.z {
-            1
+        let (left, right) = if parallel_levels > 0 && count >= PARALLEL_BUILD_THRESHOLD {
+            let levels = parallel_levels - 1;
+            thread::scope(|s| {
+                let left = s
+                    .spawn(move || BVHNode::build_node(cubes, left_indices, max_leaf_size, levels));
+                let right = BVHNode::build_node(cubes, right_indices, max_leaf_size, levels);
+                (left.join().unwrap(), right)
+            })
         } else {
-            2
+            (
+                BVHNode::build_node(cubes, left_indices, max_leaf_size, 0),
+                BVHNode::build_node(cubes, right_indices, max_leaf_size, 0),
+            )
         };
 
-        indices.sort_by(|&a, &b| {
-            let ca = AABB::from_cube(&cubes[a]).center();
-            let cb = AABB::from_cube(&cubes[b]).center();
-            let va = match axis {
-                0 => ca.x,
-                1 => ca.y,
-                _ => ca.z,
-            };
-            let vb = match axis {
-                0 => cb.x,
-                1 => cb.y,
-                _ => cb.z,
-            };
+        BVHNode::Internal {
+            bounds,
+            carves: left.carves(cubes) || right.carves(cubes),
+            left: Box::new(left),
+            right: Box::new(right),
+        }
+    }
+
+    /// Árbol de dos niveles: los cubos sueltos se reparten como siempre y las instancias
+    /// forman otro subárbol cuyas hojas remiten al BVH de su prototipo, que se construye una
+    /// sola vez por muchas copias que haya. Sin instancias equivale a `build`.
+    pub fn build_with_instances(
+        cubes: &[Cube],
+        indices: &mut [usize],
+        instances: &[Instance],
+        max_leaf_size: usize,
+    ) -> Self {
+        if instances.is_empty() {
+            return BVHNode::build_with_leaf_size(cubes, indices, max_leaf_size);
+        }
+
+        let leaves = instances
+            .iter()
+            .map(|instance| BVHNode::Instance {
+                bounds: instance.bounds(),
+                instance: instance.clone(),
+            })
+            .collect();
+        let instance_tree = BVHNode::build_instance_node(leaves);
+        if indices.is_empty() {
+            return instance_tree;
+        }
+
+        let cube_tree = BVHNode::build_with_leaf_size(cubes, indices, max_leaf_size);
+        BVHNode::Internal {
+            bounds: cube_tree.bounds().merge(instance_tree.bounds()),
+            carves: cube_tree.carves(cubes),
+            left: Box::new(cube_tree),
+            right: Box::new(instance_tree),
+        }
+    }
+
+    fn build_instance_node(mut leaves: Vec<BVHNode>) -> Self {
+        if leaves.len() == 1 {
+            return leaves.pop().unwrap();
+        }
+
+        let first = leaves[0].bounds();
+        let mut bounds = AABB {
+            min: first.min,
+            max: first.max,
+        };
+        for leaf in leaves.iter().skip(1) {
+            bounds = bounds.merge(leaf.bounds());
+        }
+
+        let axis = longest_axis(&bounds);
+        leaves.sort_by(|a, b| {
+            let va = axis_component(a.bounds().center(), axis);
+            let vb = axis_component(b.bounds().center(), axis);
             va.partial_cmp(&vb).unwrap()
         });
 
-        let mid = indices.len() / 2;
-        let (left_indices, right_indices) = indices.split_at_mut(mid);
-
+        let right = leaves.split_off(leaves.len() / 2);
         BVHNode::Internal {
             bounds,
-            left: Box::new(BVHNode::build(cubes, left_indices)),
-            right: Box::new(BVHNode::build(cubes, right_indices)),
+            carves: false,
+            left: Box::new(BVHNode::build_instance_node(leaves)),
+            right: Box::new(BVHNode::build_instance_node(right)),
+        }
+    }
+
+    /// Árbol en texto indentado, un nodo por línea con su profundidad y su caja
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        self.write_text(0, &mut out);
+        out
+    }
+
+    fn write_text(&self, depth: usize, out: &mut String) {
+        let indent = "  ".repeat(depth);
+        let bounds = format_bounds(self.bounds());
+        match self {
+            BVHNode::Leaf { objects, .. } => {
+                out.push_str(&format!(
+                    "{}leaf depth={} {}={} bounds={}\n",
+                    indent,
+                    depth,
+                    objects_label(objects),
+                    format_indices(objects),
+                    bounds
+                ));
+            }
+            BVHNode::Internal { left, right, .. } => {
+                out.push_str(&format!(
+                    "{}node depth={} bounds={}\n",
+                    indent, depth, bounds
+                ));
+                left.write_text(depth + 1, out);
+                right.write_text(depth + 1, out);
+            }
+            BVHNode::Instance { instance, .. } => {
+                out.push_str(&format!(
+                    "{}instance depth={} prototype={} offset={} bounds={}\n",
+                    indent,
+                    depth,
+                    instance.prototype.name,
+                    format_vector(instance.offset),
+                    bounds
+                ));
+            }
+        }
+    }
+
+    /// Árbol en formato DOT de graphviz (`dot -Tsvg bvh.dot -o bvh.svg`)
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph bvh {\n  node [shape=box, fontname=monospace];\n");
+        let mut next_id = 0;
+        self.write_dot(0, &mut next_id, &mut out);
+        out.push_str("}\n");
+        out
+    }
+
+    /// Escribe este nodo y sus hijos; devuelve el id del nodo
+    fn write_dot(&self, depth: usize, next_id: &mut usize, out: &mut String) -> usize {
+        let id = *next_id;
+        *next_id += 1;
+        let bounds = format_bounds(self.bounds());
+
+        match self {
+            BVHNode::Leaf { objects, .. } => {
+                out.push_str(&format!(
+                    "  n{} [label=\"{} {}\\ndepth {}\\n{}\", style=filled, fillcolor=lightgray];\n",
+                    id,
+                    objects_label(objects),
+                    format_indices(objects),
+                    depth,
+                    bounds
+                ));
+            }
+            BVHNode::Internal { left, right, .. } => {
+                out.push_str(&format!(
+                    "  n{} [label=\"depth {}\\n{}\"];\n",
+                    id, depth, bounds
+                ));
+                for child in [left, right] {
+                    let child_id = child.write_dot(depth + 1, next_id, out);
+                    out.push_str(&format!("  n{} -> n{};\n", id, child_id));
+                }
+            }
+            BVHNode::Instance { instance, .. } => {
+                out.push_str(&format!(
+                    "  n{} [label=\"{} @ {}\\ndepth {}\\n{}\", style=filled, fillcolor=lightblue];\n",
+                    id,
+                    instance.prototype.name,
+                    format_vector(instance.offset),
+                    depth,
+                    bounds
+                ));
+            }
+        }
+        id
+    }
+
+    /// Guarda el árbol en `path`: en DOT si termina en `.dot`, en texto si no
+    pub fn export(&self, path: &str) -> Result<(), String> {
+        let contents = if path.ends_with(".dot") {
+            self.to_dot()
+        } else {
+            self.to_text()
+        };
+        std::fs::write(path, contents).map_err(|e| format!("Failed to write {}: {}", path, e))
+    }
+
+    pub fn stats(&self) -> BVHStats {
+        let mut stats = BVHStats::default();
+        self.collect_stats(0, &mut stats);
+        stats
+    }
+
+    fn collect_stats(&self, depth: usize, stats: &mut BVHStats) {
+        stats.nodes += 1;
+        stats.depth = stats.depth.max(depth);
+        match self {
+            BVHNode::Leaf { objects, .. } => {
+                stats.leaves += 1;
+                stats.primitives += objects.len();
+            }
+            BVHNode::Internal { left, right, .. } => {
+                left.collect_stats(depth + 1, stats);
+                right.collect_stats(depth + 1, stats);
+            }
+            BVHNode::Instance { instance, .. } => {
+                stats.leaves += 1;
+                stats.primitives += instance.prototype.cubes.len();
+            }
+        }
+    }
+
+    /// Hay algún cubo de resta en este subárbol
+    fn carves(&self, cubes: &[Cube]) -> bool {
+        match self {
+            BVHNode::Leaf { objects, .. } => objects.iter().any(|&idx| cubes[idx].subtractive),
+            BVHNode::Internal { carves, .. } => *carves,
+            BVHNode::Instance { .. } => false,
+        }
+    }
+
+    pub fn bounds(&self) -> &AABB {
+        match self {
+            BVHNode::Leaf { bounds, .. }
+            | BVHNode::Internal { bounds, .. }
+            | BVHNode::Instance { bounds, .. } => bounds,
+        }
+    }
+
+    /// Recalcula las cajas de abajo hacia arriba sin reordenar el árbol.
+    /// Sirve si los cubos se movieron poco; tras cambios grandes conviene volver a construir.
+    pub fn refit(&mut self, cubes: &[Cube]) {
+        match self {
+            BVHNode::Leaf { bounds, objects } => {
+                *bounds = cubes_bounds(cubes, objects);
+            }
+            BVHNode::Internal {
+                bounds,
+                left,
+                right,
+                carves,
+            } => {
+                left.refit(cubes);
+                right.refit(cubes);
+                *bounds = left.bounds().merge(right.bounds());
+                *carves = left.carves(cubes) || right.carves(cubes);
+            }
+            // Las instancias no se animan
+            BVHNode::Instance { .. } => {}
         }
     }
 
@@ -150,25 +530,232 @@ impl BVHNode {
         ray_direction: &Vector3,
         inv_dir: &Vector3,
     ) -> Intersect {
+        self.intersect_within(cubes, ray_origin, ray_direction, inv_dir, f32::INFINITY)
+    }
+
+    /// Impacto más cercano a no más de `max_distance`; las ramas más lejanas no se recorren.
+    /// Los cubos de resta no se ven: vacían lo que tocan y el rayo sigue por el hueco.
+    pub fn intersect_within(
+        &self,
+        cubes: &[Cube],
+        ray_origin: &Vector3,
+        ray_direction: &Vector3,
+        inv_dir: &Vector3,
+        max_distance: f32,
+    ) -> Intersect {
+        if self.carves(cubes) {
+            self.carved_hit(cubes, ray_origin, ray_direction, inv_dir, max_distance)
+        } else {
+            self.closest_hit(cubes, ray_origin, ray_direction, inv_dir, max_distance)
+        }
+    }
+
+    /// Recorre el rayo por tramos. Un impacto dentro de un hueco no cuenta y el rayo sigue
+    /// desde él; cruzar la pared de un hueco que está dentro de un sólido sí se ve, con el
+    /// material del cubo de resta.
+    fn carved_hit(
+        &self,
+        cubes: &[Cube],
+        ray_origin: &Vector3,
+        ray_direction: &Vector3,
+        inv_dir: &Vector3,
+        max_distance: f32,
+    ) -> Intersect {
+        let mut origin = *ray_origin;
+        let mut travelled = 0.0;
+
+        for _ in 0..MAX_CARVE_STEPS {
+            let remaining = max_distance - travelled;
+
+            if let Some(hole) = self.carving_at(cubes, origin + *ray_direction * HIT_EPSILON) {
+                // Dentro del hueco no hay nada visible hasta llegar a su salida
+                let mut exit = cubes[hole].ray_intersect(&origin, ray_direction);
+                if !exit.is_intersecting || exit.distance > remaining {
+                    return Intersect::empty();
+                }
+                let beyond = exit.point + *ray_direction * HIT_EPSILON;
+                if self.contains_solid(cubes, beyond) && self.carving_at(cubes, beyond).is_none() {
+                    // Del hueco se pasa a un sólido: la pared se ve desde fuera del sólido
+                    exit.inside = false;
+                    exit.distance += travelled;
+                    exit.object_idx = Some(hole);
+                    return exit;
+                }
+                origin = exit.point;
+                travelled += exit.distance;
+                continue;
+            }
+
+            let mut hit = self.closest_hit(cubes, &origin, ray_direction, inv_dir, remaining);
+            let limit = if hit.is_intersecting {
+                hit.distance
+            } else {
+                remaining
+            };
+
+            if let Some((hole, mut entry)) =
+                self.carving_entry(cubes, &origin, ray_direction, inv_dir, limit)
+            {
+                let before = entry.point - *ray_direction * HIT_EPSILON;
+                if self.contains_solid(cubes, before) && self.carving_at(cubes, before).is_none() {
+                    // De un sólido se entra al hueco: la pared se ve desde dentro del sólido
+                    entry.inside = true;
+                    entry.distance += travelled;
+                    entry.object_idx = Some(hole);
+                    return entry;
+                }
+                origin = entry.point;
+                travelled += entry.distance;
+                continue;
+            }
+
+            if !hit.is_intersecting {
+                return hit;
+            }
+            if self.carving_at(cubes, hit.point).is_some() {
+                // Cara que coincide con el borde de un hueco: también queda vaciada
+                origin = hit.point;
+                travelled += hit.distance;
+                continue;
+            }
+            hit.distance += travelled;
+            return hit;
+        }
+
+        Intersect::empty()
+    }
+
+    /// Cubo de resta que contiene `point`
+    fn carving_at(&self, cubes: &[Cube], point: Vector3) -> Option<usize> {
+        if !self.carves(cubes) || !self.bounds().contains(point) {
+            return None;
+        }
         match self {
-            BVHNode::Leaf { bounds, object_idx } => {
-                if bounds.intersect(ray_origin, inv_dir) {
-                    cubes[*object_idx].ray_intersect(ray_origin, ray_direction)
-                } else {
-                    Intersect::empty()
+            BVHNode::Leaf { objects, .. } => objects.iter().copied().find(|&idx| {
+                let cube = &cubes[idx];
+                cube.enabled && cube.subtractive && cube.contains(point)
+            }),
+            BVHNode::Internal { left, right, .. } => left
+                .carving_at(cubes, point)
+                .or_else(|| right.carving_at(cubes, point)),
+            BVHNode::Instance { .. } => None,
+        }
+    }
+
+    /// `point` está dentro de algún cubo que no es de resta
+    pub fn contains_solid(&self, cubes: &[Cube], point: Vector3) -> bool {
+        if !self.bounds().contains(point) {
+            return false;
+        }
+        match self {
+            BVHNode::Leaf { objects, .. } => objects.iter().any(|&idx| {
+                let cube = &cubes[idx];
+                cube.enabled && !cube.subtractive && cube.contains(point)
+            }),
+            BVHNode::Internal { left, right, .. } => {
+                left.contains_solid(cubes, point) || right.contains_solid(cubes, point)
+            }
+            BVHNode::Instance { instance, .. } => instance.contains_solid(point),
+        }
+    }
+
+    /// Primer cubo de resta en el que entra el rayo a no más de `max_distance`, con el
+    /// impacto sobre su cara de entrada
+    fn carving_entry(
+        &self,
+        cubes: &[Cube],
+        ray_origin: &Vector3,
+        ray_direction: &Vector3,
+        inv_dir: &Vector3,
+        max_distance: f32,
+    ) -> Option<(usize, Intersect)> {
+        if !self.carves(cubes)
+            || !self
+                .bounds()
+                .intersect_within(ray_origin, inv_dir, max_distance)
+        {
+            return None;
+        }
+        match self {
+            BVHNode::Leaf { objects, .. } => {
+                let mut nearest: Option<(usize, Intersect)> = None;
+                for &idx in objects {
+                    let cube = &cubes[idx];
+                    if !cube.enabled || !cube.subtractive {
+                        continue;
+                    }
+                    let hit = cube.ray_intersect(ray_origin, ray_direction);
+                    if hit.is_intersecting
+                        && !hit.inside
+                        && hit.distance <= max_distance
+                        && nearest
+                            .as_ref()
+                            .is_none_or(|(_, n)| hit.distance < n.distance)
+                    {
+                        nearest = Some((idx, hit));
+                    }
+                }
+                nearest
+            }
+            BVHNode::Internal { left, right, .. } => {
+                let left_entry =
+                    left.carving_entry(cubes, ray_origin, ray_direction, inv_dir, max_distance);
+                let right_entry =
+                    right.carving_entry(cubes, ray_origin, ray_direction, inv_dir, max_distance);
+                match (left_entry, right_entry) {
+                    (Some(l), Some(r)) => Some(if l.1.distance <= r.1.distance { l } else { r }),
+                    (l, r) => l.or(r),
+                }
+            }
+            BVHNode::Instance { .. } => None,
+        }
+    }
+
+    fn closest_hit(
+        &self,
+        cubes: &[Cube],
+        ray_origin: &Vector3,
+        ray_direction: &Vector3,
+        inv_dir: &Vector3,
+        max_distance: f32,
+    ) -> Intersect {
+        match self {
+            BVHNode::Leaf { bounds, objects } => {
+                let mut closest = Intersect::empty();
+                if !bounds.intersect_within(ray_origin, inv_dir, max_distance) {
+                    return closest;
                 }
+                for &idx in objects {
+                    let cube = &cubes[idx];
+                    if !cube.enabled || cube.subtractive {
+                        continue;
+                    }
+                    let mut hit = cube.ray_intersect(ray_origin, ray_direction);
+                    if hit.is_intersecting
+                        && hit.distance <= max_distance
+                        // Con empate gana el último, como entre ramas hermanas
+                        && (!closest.is_intersecting || hit.distance <= closest.distance)
+                    {
+                        hit.object_idx = Some(idx);
+                        closest = hit;
+                    }
+                }
+                closest
             }
             BVHNode::Internal {
                 bounds,
                 left,
                 right,
+                ..
             } => {
-                if !bounds.intersect(ray_origin, inv_dir) {
+                if !bounds.intersect_within(ray_origin, inv_dir, max_distance) {
                     return Intersect::empty();
                 }
 
-                let left_hit = left.intersect(cubes, ray_origin, ray_direction, inv_dir);
-                let right_hit = right.intersect(cubes, ray_origin, ray_direction, inv_dir);
+                let left_hit =
+                    left.closest_hit(cubes, ray_origin, ray_direction, inv_dir, max_distance);
+                let right_hit =
+                    right.closest_hit(cubes, ray_origin, ray_direction, inv_dir, max_distance);
 
                 if left_hit.is_intersecting && right_hit.is_intersecting {
                     if left_hit.distance < right_hit.distance {
@@ -182,6 +769,654 @@ impl BVHNode {
                     right_hit
                 }
             }
+            BVHNode::Instance { bounds, instance } => {
+                if bounds.intersect_within(ray_origin, inv_dir, max_distance) {
+                    instance.intersect_within(ray_origin, ray_direction, inv_dir, max_distance)
+                } else {
+                    Intersect::empty()
+                }
+            }
+        }
+    }
+
+    /// Índice del cubo visible más cercano a lo largo del rayo; sirve para seleccionar
+    /// objetos haciendo clic sobre la imagen
+    pub fn pick(
+        &self,
+        cubes: &[Cube],
+        ray_origin: &Vector3,
+        ray_direction: &Vector3,
+    ) -> Option<usize> {
+        let inv_dir = Vector3::new(
+            1.0 / ray_direction.x,
+            1.0 / ray_direction.y,
+            1.0 / ray_direction.z,
+        );
+        let hit = self.intersect(cubes, ray_origin, ray_direction, &inv_dir);
+        if hit.is_intersecting {
+            hit.object_idx
+        } else {
+            None
+        }
+    }
+
+    /// Consulta de sombra: basta con saber si algún cubo bloquea el rayo antes de
+    /// `max_distance`, así que termina en el primer impacto. Con `opaque_only` los cubos
+    /// transparentes no se prueban.
+    pub fn occluded(
+        &self,
+        cubes: &[Cube],
+        ray_origin: &Vector3,
+        ray_direction: &Vector3,
+        inv_dir: &Vector3,
+        max_distance: f32,
+        opaque_only: bool,
+    ) -> bool {
+        if !self.carves(cubes) {
+            return self.any_hit(
+                cubes,
+                ray_origin,
+                ray_direction,
+                inv_dir,
+                max_distance,
+                opaque_only,
+            );
+        }
+
+        // Con huecos hace falta el impacto visible; los transparentes se atraviesan
+        let mut origin = *ray_origin;
+        let mut remaining = max_distance;
+        for _ in 0..MAX_CARVE_STEPS {
+            let hit = self.intersect_within(cubes, &origin, ray_direction, inv_dir, remaining);
+            if !hit.is_intersecting || hit.distance >= remaining {
+                return false;
+            }
+            let blocks = hit
+                .object_idx
+                .is_none_or(|idx| !opaque_only || cubes[idx].is_opaque());
+            if blocks {
+                return true;
+            }
+            origin = hit.point;
+            remaining -= hit.distance;
+        }
+        false
+    }
+
+    fn any_hit(
+        &self,
+        cubes: &[Cube],
+        ray_origin: &Vector3,
+        ray_direction: &Vector3,
+        inv_dir: &Vector3,
+        max_distance: f32,
+        opaque_only: bool,
+    ) -> bool {
+        if !self
+            .bounds()
+            .intersect_within(ray_origin, inv_dir, max_distance)
+        {
+            return false;
+        }
+
+        match self {
+            BVHNode::Leaf { objects, .. } => objects.iter().any(|&idx| {
+                let cube = &cubes[idx];
+                if !cube.enabled || cube.subtractive || (opaque_only && !cube.is_opaque()) {
+                    return false;
+                }
+                let hit = cube.ray_intersect(ray_origin, ray_direction);
+                hit.is_intersecting && hit.distance < max_distance
+            }),
+            BVHNode::Internal { left, right, .. } => {
+                left.any_hit(
+                    cubes,
+                    ray_origin,
+                    ray_direction,
+                    inv_dir,
+                    max_distance,
+                    opaque_only,
+                ) || right.any_hit(
+                    cubes,
+                    ray_origin,
+                    ray_direction,
+                    inv_dir,
+                    max_distance,
+                    opaque_only,
+                )
+            }
+            BVHNode::Instance { instance, .. } => instance.occluded(
+                ray_origin,
+                ray_direction,
+                inv_dir,
+                max_distance,
+                opaque_only,
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Material;
+
+    fn unit_box() -> AABB {
+        AABB {
+            min: Vector3::new(-1.0, -1.0, -1.0),
+            max: Vector3::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    fn inv(dir: Vector3) -> Vector3 {
+        Vector3::new(1.0 / dir.x, 1.0 / dir.y, 1.0 / dir.z)
+    }
+
+    #[test]
+    fn head_on_ray_hits() {
+        let dir = Vector3::new(0.0, 0.0, -1.0);
+        assert!(unit_box().intersect(&Vector3::new(0.0, 0.0, 5.0), &inv(dir)));
+    }
+
+    #[test]
+    fn diagonal_ray_hits() {
+        let origin = Vector3::new(5.0, 5.0, 5.0);
+        let dir = (Vector3::zero() - origin).normalized();
+        assert!(unit_box().intersect(&origin, &inv(dir)));
+    }
+
+    #[test]
+    fn ray_passing_beside_box_misses() {
+        let dir = Vector3::new(0.0, 0.0, -1.0);
+        assert!(!unit_box().intersect(&Vector3::new(3.0, 0.0, 5.0), &inv(dir)));
+
+        let dir = Vector3::new(0.3, 0.0, -1.0).normalized();
+        assert!(!unit_box().intersect(&Vector3::new(2.0, 0.0, 5.0), &inv(dir)));
+    }
+
+    #[test]
+    fn ray_starting_inside_hits() {
+        let dir = Vector3::new(0.3, -0.5, 0.8).normalized();
+        assert!(unit_box().intersect(&Vector3::new(0.2, 0.1, -0.3), &inv(dir)));
+    }
+
+    #[test]
+    fn ray_parallel_to_face_inside_slab_hits() {
+        // Dirección paralela a las caras ±Y: inv_dir.y es infinito
+        let dir = Vector3::new(1.0, 0.0, 0.0);
+        assert!(unit_box().intersect(&Vector3::new(-5.0, 0.5, 0.0), &inv(dir)));
+    }
+
+    #[test]
+    fn ray_parallel_to_face_outside_slab_misses() {
+        let dir = Vector3::new(1.0, 0.0, 0.0);
+        assert!(!unit_box().intersect(&Vector3::new(-5.0, 1.5, 0.0), &inv(dir)));
+        assert!(!unit_box().intersect(&Vector3::new(-5.0, 0.0, -1.5), &inv(dir)));
+    }
+
+    #[test]
+    fn distance_range_spans_the_whole_box() {
+        let (near, far) = unit_box().distance_range(Vector3::new(0.0, 0.0, 5.0));
+        assert!((near - 4.0).abs() < 1e-6);
+        assert!((far - (1.0f32 + 1.0 + 36.0).sqrt()).abs() < 1e-5);
+
+        // Desde dentro lo más cercano está a distancia 0
+        let (near, far) = unit_box().distance_range(Vector3::new(0.5, 0.0, 0.0));
+        assert_eq!(near, 0.0);
+        assert!((far - (2.25f32 + 1.0 + 1.0).sqrt()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn axis_aligned_rays_on_every_axis() {
+        let cases = [
+            (Vector3::new(5.0, 0.0, 0.0), Vector3::new(-1.0, 0.0, 0.0)),
+            (Vector3::new(-5.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0)),
+            (Vector3::new(0.0, 5.0, 0.0), Vector3::new(0.0, -1.0, 0.0)),
+            (Vector3::new(0.0, -5.0, 0.0), Vector3::new(0.0, 1.0, 0.0)),
+            (Vector3::new(0.0, 0.0, 5.0), Vector3::new(0.0, 0.0, -1.0)),
+            (Vector3::new(0.0, 0.0, -5.0), Vector3::new(0.0, 0.0, 1.0)),
+        ];
+
+        for (origin, dir) in cases {
+            let inv_dir = inv(dir);
+            assert!(inv_dir.x.is_infinite() || inv_dir.y.is_infinite());
+            assert!(unit_box().intersect(&origin, &inv_dir), "{:?}", dir);
+        }
+    }
+
+    #[test]
+    fn axis_aligned_ray_on_slab_boundary() {
+        // El origen está exactamente en el plano y = max: (max - o) * inf = 0 * inf = NaN
+        let dir = Vector3::new(0.0, 0.0, -1.0);
+        let origin = Vector3::new(0.0, 1.0, 5.0);
+        assert!(unit_box().intersect(&origin, &inv(dir)));
+
+        let origin = Vector3::new(1.0, 1.0, 5.0);
+        assert!(unit_box().intersect(&origin, &inv(dir)));
+    }
+
+    /// Generador xorshift para que la prueba sea determinista sin dependencias externas
+    struct Rng(u32);
+
+    impl Rng {
+        fn next(&mut self) -> f32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 17;
+            self.0 ^= self.0 << 5;
+            self.0 as f32 / u32::MAX as f32
+        }
+
+        fn range(&mut self, min: f32, max: f32) -> f32 {
+            min + (max - min) * self.next()
+        }
+
+        fn vector(&mut self, min: f32, max: f32) -> Vector3 {
+            Vector3::new(
+                self.range(min, max),
+                self.range(min, max),
+                self.range(min, max),
+            )
+        }
+    }
+
+    fn brute_force(cubes: &[Cube], origin: &Vector3, dir: &Vector3) -> (Intersect, Option<usize>) {
+        let mut closest = Intersect::empty();
+        let mut closest_idx = None;
+
+        for (idx, cube) in cubes.iter().enumerate() {
+            let hit = cube.ray_intersect(origin, dir);
+            if hit.is_intersecting && (!closest.is_intersecting || hit.distance < closest.distance)
+            {
+                closest = hit;
+                closest_idx = Some(idx);
+            }
+        }
+
+        (closest, closest_idx)
+    }
+
+    #[test]
+    fn matches_brute_force_closest_hit() {
+        let mut rng = Rng(0x9e37_79b9);
+
+        let cubes: Vec<Cube> = (0..200)
+            .map(|i| {
+                let center = rng.vector(-10.0, 10.0);
+                if i % 3 == 0 {
+                    let size = rng.vector(0.2, 3.0);
+                    Cube::new_rect(center, size.x, size.y, size.z, Material::black())
+                } else {
+                    Cube::new(center, rng.range(0.2, 2.0), Material::black())
+                }
+            })
+            .collect();
+
+        let mut indices: Vec<usize> = (0..cubes.len()).collect();
+        let bvh = BVHNode::build(&cubes, &mut indices);
+
+        let mut hits = 0;
+        for _ in 0..2000 {
+            let origin = rng.vector(-25.0, 25.0);
+            let target = rng.vector(-10.0, 10.0);
+            let dir = (target - origin).normalized();
+
+            let hit = bvh.intersect(&cubes, &origin, &dir, &inv(dir));
+            let (expected, expected_idx) = brute_force(&cubes, &origin, &dir);
+
+            assert_eq!(hit.is_intersecting, expected.is_intersecting);
+            if !expected.is_intersecting {
+                continue;
+            }
+
+            hits += 1;
+            assert_eq!(hit.distance, expected.distance);
+            assert_eq!(hit.object_idx, expected_idx);
+            assert_eq!(hit.normal, expected.normal);
+        }
+
+        // Que la prueba no pase trivialmente porque ningún rayo impacta
+        assert!(hits > 1000, "only {} rays hit", hits);
+    }
+
+    #[test]
+    fn larger_leaves_find_the_same_hits() {
+        let cubes = sample_cubes();
+        let mut indices: Vec<usize> = (0..cubes.len()).collect();
+        let single = BVHNode::build(&cubes, &mut indices);
+        let mut indices: Vec<usize> = (0..cubes.len()).collect();
+        let grouped = BVHNode::build_with_leaf_size(&cubes, &mut indices, 4);
+
+        // Cada objeto sigue en exactamente una hoja, ahora de hasta 4
+        let mut order = Vec::new();
+        leaf_order(&grouped, &mut order);
+        order.sort();
+        assert_eq!(order, (0..cubes.len()).collect::<Vec<_>>());
+        let leaves = grouped.to_text().matches("leaf depth").count();
+        assert!((13..50).contains(&leaves), "{} leaves", leaves);
+
+        let mut rng = Rng(0x5eed_1234);
+        for _ in 0..500 {
+            let origin = rng.vector(-25.0, 25.0);
+            let dir = (rng.vector(-10.0, 10.0) - origin).normalized();
+            let expected = single.intersect(&cubes, &origin, &dir, &inv(dir));
+            let hit = grouped.intersect(&cubes, &origin, &dir, &inv(dir));
+            assert_eq!(hit.is_intersecting, expected.is_intersecting);
+            assert_eq!(hit.distance, expected.distance);
+            assert_eq!(hit.object_idx, expected.object_idx);
+            assert_eq!(
+                grouped.occluded(&cubes, &origin, &dir, &inv(dir), 15.0, false),
+                single.occluded(&cubes, &origin, &dir, &inv(dir), 15.0, false)
+            );
+        }
+    }
+
+    #[test]
+    fn far_clip_drops_distant_hits() {
+        let cubes = vec![
+            Cube::new(Vector3::new(0.0, 0.0, 0.0), 1.0, Material::black()),
+            Cube::new(Vector3::new(0.0, 0.0, -20.0), 1.0, Material::black()),
+        ];
+        let mut indices: Vec<usize> = (0..cubes.len()).collect();
+        let bvh = BVHNode::build(&cubes, &mut indices);
+
+        let origin = Vector3::new(0.0, 0.0, 5.0);
+        let dir = Vector3::new(0.0, 0.0, -1.0);
+        let hit = bvh.intersect_within(&cubes, &origin, &dir, &inv(dir), 10.0);
+        assert!(hit.is_intersecting);
+        assert_eq!(hit.object_idx, Some(0));
+
+        // Rayo que solo alcanza el cubo lejano
+        let origin = Vector3::new(0.0, 0.0, -5.0);
+        assert!(
+            bvh.intersect(&cubes, &origin, &dir, &inv(dir))
+                .is_intersecting
+        );
+        assert!(
+            !bvh.intersect_within(&cubes, &origin, &dir, &inv(dir), 10.0)
+                .is_intersecting
+        );
+        assert!(!unit_box().intersect_within(&Vector3::new(0.0, 0.0, 20.0), &inv(dir), 10.0));
+    }
+
+    fn assert_same_bounds(a: &BVHNode, b: &BVHNode) {
+        assert_eq!(a.bounds().min, b.bounds().min);
+        assert_eq!(a.bounds().max, b.bounds().max);
+
+        match (a, b) {
+            (
+                BVHNode::Internal {
+                    left: la,
+                    right: ra,
+                    ..
+                },
+                BVHNode::Internal {
+                    left: lb,
+                    right: rb,
+                    ..
+                },
+            ) => {
+                assert_same_bounds(la, lb);
+                assert_same_bounds(ra, rb);
+            }
+            (BVHNode::Leaf { .. }, BVHNode::Leaf { .. }) => {}
+            _ => panic!("trees have different shapes"),
+        }
+    }
+
+    fn sample_cubes() -> Vec<Cube> {
+        let mut rng = Rng(0x1234_5678);
+        (0..50)
+            .map(|_| {
+                Cube::new(
+                    rng.vector(-10.0, 10.0),
+                    rng.range(0.2, 2.0),
+                    Material::black(),
+                )
+            })
+            .collect()
+    }
+
+    fn leaf_order(node: &BVHNode, out: &mut Vec<usize>) {
+        match node {
+            BVHNode::Leaf { objects, .. } => out.extend(objects),
+            BVHNode::Internal { left, right, .. } => {
+                leaf_order(left, out);
+                leaf_order(right, out);
+            }
+            BVHNode::Instance { .. } => {}
+        }
+    }
+
+    #[test]
+    fn parallel_build_matches_serial_build() {
+        let mut rng = Rng(0x0bad_cafe);
+        let cubes: Vec<Cube> = (0..PARALLEL_BUILD_THRESHOLD * 5)
+            .map(|_| {
+                Cube::new(
+                    rng.vector(-100.0, 100.0),
+                    rng.range(0.2, 2.0),
+                    Material::black(),
+                )
+            })
+            .collect();
+
+        let mut indices: Vec<usize> = (0..cubes.len()).collect();
+        let serial = BVHNode::build_node(&cubes, &mut indices, 1, 0);
+        let mut indices: Vec<usize> = (0..cubes.len()).collect();
+        let parallel = BVHNode::build_node(&cubes, &mut indices, 1, 3);
+
+        assert_same_bounds(&parallel, &serial);
+        let (mut a, mut b) = (Vec::new(), Vec::new());
+        leaf_order(&parallel, &mut a);
+        leaf_order(&serial, &mut b);
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn export_lists_every_node() {
+        let cubes = sample_cubes();
+        let mut indices: Vec<usize> = (0..cubes.len()).collect();
+        let bvh = BVHNode::build(&cubes, &mut indices);
+
+        // Un árbol binario con n hojas tiene n - 1 nodos internos
+        let text = bvh.to_text();
+        assert_eq!(
+            text.lines()
+                .filter(|l| l.trim_start().starts_with("leaf"))
+                .count(),
+            50
+        );
+        assert_eq!(
+            text.lines()
+                .filter(|l| l.trim_start().starts_with("node"))
+                .count(),
+            49
+        );
+        assert!(text.starts_with("node depth=0 bounds="));
+
+        let dot = bvh.to_dot();
+        assert!(dot.starts_with("digraph bvh {") && dot.trim_end().ends_with('}'));
+        assert_eq!(dot.matches(" -> ").count(), 98);
+        for object in 0..cubes.len() {
+            assert!(dot.contains(&format!("object {}\\n", object)));
+        }
+    }
+
+    #[test]
+    fn refit_without_motion_matches_fresh_build() {
+        let cubes = sample_cubes();
+        let mut indices: Vec<usize> = (0..cubes.len()).collect();
+        let fresh = BVHNode::build(&cubes, &mut indices);
+
+        let mut indices: Vec<usize> = (0..cubes.len()).collect();
+        let mut refitted = BVHNode::build(&cubes, &mut indices);
+        refitted.refit(&cubes);
+
+        assert_same_bounds(&refitted, &fresh);
+    }
+
+    #[test]
+    fn refit_follows_moved_cube() {
+        let mut cubes = sample_cubes();
+        let mut indices: Vec<usize> = (0..cubes.len()).collect();
+        let mut bvh = BVHNode::build(&cubes, &mut indices);
+
+        let offset = Vector3::new(0.0, 30.0, 0.0);
+        cubes[7].min_bounds += offset;
+        cubes[7].max_bounds += offset;
+        bvh.refit(&cubes);
+
+        assert!(bvh.bounds().max.y >= cubes[7].max_bounds.y);
+
+        let target = (cubes[7].min_bounds + cubes[7].max_bounds) * 0.5;
+        let origin = target + Vector3::new(0.0, 20.0, 0.0);
+        let dir = Vector3::new(0.0, -1.0, 0.0);
+        let hit = bvh.intersect(&cubes, &origin, &dir, &inv(dir));
+        assert_eq!(hit.object_idx, Some(7));
+    }
+
+    #[test]
+    fn occluded_matches_closest_hit() {
+        let cubes = sample_cubes();
+        let mut indices: Vec<usize> = (0..cubes.len()).collect();
+        let bvh = BVHNode::build(&cubes, &mut indices);
+        let mut rng = Rng(0xdead_beef);
+
+        for _ in 0..1000 {
+            let origin = rng.vector(-15.0, 15.0);
+            let target = rng.vector(-15.0, 15.0);
+            let max_distance = (target - origin).length();
+            let dir = (target - origin).normalized();
+
+            let hit = bvh.intersect(&cubes, &origin, &dir, &inv(dir));
+            let expected = hit.is_intersecting && hit.distance < max_distance;
+            assert_eq!(
+                bvh.occluded(&cubes, &origin, &dir, &inv(dir), max_distance, true),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn transparent_cubes_can_be_skipped_by_shadows() {
+        let glass = Material {
+            transparency: 0.5,
+            ..Material::black()
+        };
+        let cubes = vec![Cube::new(Vector3::zero(), 1.0, glass)];
+        let mut indices = vec![0];
+        let bvh = BVHNode::build(&cubes, &mut indices);
+
+        let origin = Vector3::new(0.0, 0.0, 5.0);
+        let dir = Vector3::new(0.0, 0.0, -1.0);
+        assert!(bvh.occluded(&cubes, &origin, &dir, &inv(dir), 10.0, false));
+        assert!(!bvh.occluded(&cubes, &origin, &dir, &inv(dir), 10.0, true));
+        // El cubo queda más allá de la luz
+        assert!(!bvh.occluded(&cubes, &origin, &dir, &inv(dir), 3.0, false));
+    }
+
+    #[test]
+    fn grazing_light_leaves_a_floor_unshadowed() {
+        // Suelo de cubos contiguos y una luz casi tangente a sus caras superiores
+        let mut cubes = Vec::new();
+        for x in -10..10 {
+            for z in -10..10 {
+                let center = Vector3::new(x as f32 * 1.5, 0.0, z as f32 * 1.5);
+                cubes.push(Cube::new(center, 1.5, Material::black()));
+            }
         }
+        let mut indices: Vec<usize> = (0..cubes.len()).collect();
+        let bvh = BVHNode::build(&cubes, &mut indices);
+        let light = Vector3::new(1.0, 0.002, 0.4).normalized();
+        let eye = Vector3::new(0.3, 20.0, 40.0);
+
+        for i in 0..50 {
+            for j in 0..50 {
+                let target = Vector3::new(i as f32 * 0.57 - 14.0, 0.75, j as f32 * 0.55 - 14.0);
+                let dir = (target - eye).normalized();
+                let hit = bvh.intersect(&cubes, &eye, &dir, &inv(dir));
+                assert!(hit.is_intersecting && hit.normal.y > 0.5);
+
+                let origin = hit.shadow_origin(light);
+                assert!(
+                    !bvh.occluded(&cubes, &origin, &light, &inv(light), 50.0, false),
+                    "{:?}",
+                    hit.point
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn pick_returns_nearest_visible_object() {
+        let mut cubes = vec![
+            Cube::new(Vector3::new(0.0, 0.0, -5.0), 1.0, Material::black()),
+            Cube::new(Vector3::new(0.0, 0.0, 0.0), 1.0, Material::black()),
+            Cube::new(Vector3::new(4.0, 0.0, 0.0), 1.0, Material::black()),
+        ];
+        let mut indices: Vec<usize> = (0..cubes.len()).collect();
+        let bvh = BVHNode::build(&cubes, &mut indices);
+
+        let origin = Vector3::new(0.0, 0.0, 5.0);
+        let dir = Vector3::new(0.0, 0.0, -1.0);
+        assert_eq!(bvh.pick(&cubes, &origin, &dir), Some(1));
+        assert_eq!(bvh.pick(&cubes, &origin, &-dir), None);
+
+        // Un objeto oculto no se puede seleccionar; el clic llega al que está detrás
+        cubes[1].enabled = false;
+        assert_eq!(bvh.pick(&cubes, &origin, &dir), Some(0));
+    }
+
+    #[test]
+    fn subtracted_cube_leaves_a_hole() {
+        // Cubo de 4 con un hueco de 1 x 1 y 0.5 de hondo en su cara +Z
+        let mut hole = Cube::new(Vector3::new(0.0, 0.0, 2.0), 1.0, Material::black());
+        hole.subtractive = true;
+        let cubes = vec![Cube::new(Vector3::zero(), 4.0, Material::black()), hole];
+        let mut indices: Vec<usize> = (0..cubes.len()).collect();
+        let bvh = BVHNode::build(&cubes, &mut indices);
+        let dir = Vector3::new(0.0, 0.0, -1.0);
+
+        // Por el hueco se llega al fondo, que se ve desde fuera y pertenece al cubo de resta
+        let origin = Vector3::new(0.0, 0.0, 10.0);
+        let hit = bvh.intersect(&cubes, &origin, &dir, &inv(dir));
+        assert!(hit.is_intersecting);
+        assert!((hit.distance - 8.5).abs() < 1e-4, "{}", hit.distance);
+        assert_eq!(hit.normal, Vector3::new(0.0, 0.0, 1.0));
+        assert!(!hit.inside);
+        assert_eq!(hit.object_idx, Some(1));
+
+        // Fuera del hueco la cara sigue entera
+        let beside = Vector3::new(1.5, 0.0, 10.0);
+        let hit = bvh.intersect(&cubes, &beside, &dir, &inv(dir));
+        assert!((hit.distance - 8.0).abs() < 1e-4);
+        assert_eq!(hit.object_idx, Some(0));
+    }
+
+    #[test]
+    fn subtracted_tunnel_lets_rays_through() {
+        let mut tunnel = Cube::new_rect(Vector3::zero(), 1.0, 1.0, 10.0, Material::black());
+        tunnel.subtractive = true;
+        let cubes = vec![Cube::new(Vector3::zero(), 4.0, Material::black()), tunnel];
+        let mut indices: Vec<usize> = (0..cubes.len()).collect();
+        let bvh = BVHNode::build(&cubes, &mut indices);
+
+        let origin = Vector3::new(0.0, 0.0, 10.0);
+        let dir = Vector3::new(0.0, 0.0, -1.0);
+        assert!(
+            !bvh.intersect(&cubes, &origin, &dir, &inv(dir))
+                .is_intersecting
+        );
+        assert!(!bvh.occluded(&cubes, &origin, &dir, &inv(dir), 20.0, false));
+        let beside = Vector3::new(1.5, 0.0, 10.0);
+        assert!(bvh.occluded(&cubes, &beside, &dir, &inv(dir), 20.0, false));
+
+        // Desde dentro del sólido se ve la pared del túnel, con la normal hacia el sólido
+        let inside = Vector3::new(1.5, 0.0, 0.0);
+        let left = Vector3::new(-1.0, 0.0, 0.0);
+        let hit = bvh.intersect(&cubes, &inside, &left, &inv(left));
+        assert!((hit.distance - 1.0).abs() < 1e-4);
+        assert!(hit.inside);
+        assert_eq!(hit.outward_normal(), left);
     }
 }
@@ -1,3 +1,7 @@
+use crate::axis::UpAxis;
+use crate::bvh::AABB;
+use crate::cube::Cube;
+use crate::instance::Instance;
 use raylib::prelude::*;
 
 pub struct Camera {
@@ -6,6 +10,8 @@ pub struct Camera {
     pub up: Vector3,     // donde esta arriba
     pub forward: Vector3,
     pub right: Vector3,
+    /// Eje vertical del mundo: la órbita gira alrededor de él y la vista cenital lo sigue
+    pub up_axis: UpAxis,
     changed: bool, // para optimizar actualizaciones
 }
 
@@ -17,6 +23,7 @@ impl Camera {
             up,
             forward: Vector3::zero(),
             right: Vector3::zero(),
+            up_axis: UpAxis::Y,
             changed: true,
         };
 
@@ -26,38 +33,135 @@ impl Camera {
 
     pub fn update_basis(&mut self) {
         self.forward = (self.center - self.eye).normalized();
-        self.right = self.forward.cross(self.up).normalized();
+        // Un up paralelo a la vista no define la derecha; se usa el eje vertical del mundo
+        let mut up = self.up;
+        if self.forward.cross(up).length() < 1e-6 {
+            up = self.up_axis.up();
+        }
+        if self.forward.cross(up).length() < 1e-6 {
+            up = self.up_axis.to_scene(Vector3::new(0.0, 0.0, -1.0));
+        }
+        self.right = self.forward.cross(up).normalized();
         self.up = self.right.cross(self.forward);
         self.changed = true;
     }
 
+    /// Vista cenital del mismo punto central, a la misma distancia que esta cámara
+    pub fn top_view(&self) -> Camera {
+        let distance = (self.eye - self.center).length();
+        let axis = self.up_axis;
+        let mut camera = Camera::new(
+            self.center + axis.up() * distance,
+            self.center,
+            axis.to_scene(Vector3::new(0.0, 0.0, -1.0)),
+        );
+        camera.up_axis = axis;
+        camera
+    }
+
+    /// Cámara desplazada `offset` unidades a lo largo de `right`, mirando en la misma
+    /// dirección (estéreo paralelo); negativo para el ojo izquierdo
+    pub fn stereo_eye(&self, offset: f32) -> Camera {
+        let shift = self.right * offset;
+        let mut camera = Camera::new(self.eye + shift, self.center + shift, self.up);
+        camera.up_axis = self.up_axis;
+        camera
+    }
+
     /// Rota la cámara alrededor del punto center (movimiento orbital)
     pub fn orbit(&mut self, yaw: f32, pitch: f32) {
-        let relative_pos = self.eye - self.center;
+        let (current_yaw, current_pitch) = self.orbit_angles();
+        self.set_orbit_angles(current_yaw + yaw, current_pitch + pitch);
+    }
 
+    /// Como `orbit`, pero en saltos de `step` radianes: primero se alinea con el múltiplo
+    /// de `step` más cercano y luego avanza `yaw_steps` y `pitch_steps` saltos, de modo
+    /// que los mismos pasos dan siempre los mismos ángulos
+    pub fn orbit_snapped(&mut self, yaw_steps: i32, pitch_steps: i32, step: f32) {
+        let (current_yaw, current_pitch) = self.orbit_angles();
+        let snap = |angle: f32, steps: i32| ((angle / step).round() + steps as f32) * step;
+        self.set_orbit_angles(
+            snap(current_yaw, yaw_steps),
+            snap(current_pitch, pitch_steps),
+        );
+    }
+
+    /// Coloca el ojo a `radius` del centro y con inclinación `pitch` (radianes) sin cambiar
+    /// el ángulo horizontal; con `None` se conserva el valor actual
+    pub fn place_on_orbit(&mut self, radius: Option<f32>, pitch: Option<f32>) {
+        let (yaw, current_pitch) = self.orbit_angles();
+        if let Some(radius) = radius {
+            let direction = (self.eye - self.center).normalized();
+            self.eye = self.center + direction * radius;
+        }
+        self.set_orbit_angles(yaw, pitch.unwrap_or(current_pitch));
+    }
+
+    /// Ángulos horizontal y vertical del ojo alrededor de center, medidos con el eje
+    /// vertical del mundo llevado a Y
+    fn orbit_angles(&self) -> (f32, f32) {
+        let relative_pos = self.up_axis.to_y_up(self.eye - self.center);
         let radius = relative_pos.length();
+        (
+            relative_pos.z.atan2(relative_pos.x),
+            (relative_pos.y / radius).asin(),
+        )
+    }
 
-        let current_yaw = relative_pos.z.atan2(relative_pos.x);
-        let current_pitch = (relative_pos.y / radius).asin();
+    fn set_orbit_angles(&mut self, new_yaw: f32, new_pitch: f32) {
+        let radius = (self.eye - self.center).length();
 
         // Limitar pitch para evitar gimbal lock (no permitir mirar completamente arriba/abajo)
-        let new_yaw = current_yaw + yaw;
-        let new_pitch = (current_pitch + pitch).clamp(-1.4, 1.4); // ~80 grados máximo
+        let new_pitch = new_pitch.clamp(-1.4, 1.4); // ~80 grados máximo
 
         let pitch_cos = new_pitch.cos();
         let pitch_sin = new_pitch.sin();
 
-        let new_relative_pos = Vector3::new(
+        let new_relative_pos = self.up_axis.to_scene(Vector3::new(
             radius * pitch_cos * new_yaw.cos(),
             radius * pitch_sin,
             radius * pitch_cos * new_yaw.sin(),
-        );
+        ));
 
         self.eye = self.center + new_relative_pos;
 
         self.update_basis();
     }
 
+    /// Apunta al centro de la caja que envuelve los objetos visibles y las instancias (como
+    /// la raíz del BVH) y se aleja lo justo para que entre entera con el campo de visión
+    /// vertical `fov` (radianes) y la proporción `aspect_ratio`, sin cambiar la dirección desde
+    /// la que se mira. Sin nada visible la cámara no se mueve y devuelve `false`.
+    pub fn frame_objects(
+        &mut self,
+        objects: &[Cube],
+        instances: &[Instance],
+        fov: f32,
+        aspect_ratio: f32,
+    ) -> bool {
+        let mut boxes = objects
+            .iter()
+            .filter(|c| c.enabled && !c.subtractive)
+            .map(AABB::from_cube)
+            .chain(instances.iter().map(Instance::bounds));
+        let Some(first) = boxes.next() else {
+            return false;
+        };
+        let bounds = boxes.fold(first, |bounds, other| bounds.merge(&other));
+
+        // La esfera que envuelve la caja debe caber en el más estrecho de los dos ángulos
+        let radius = (bounds.max - bounds.min).length() * 0.5;
+        let horizontal_fov = 2.0 * ((fov * 0.5).tan() * aspect_ratio).atan();
+        let half_angle = fov.min(horizontal_fov) * 0.5;
+        let distance = radius / half_angle.sin();
+
+        let direction = (self.eye - self.center).normalized();
+        self.center = bounds.center();
+        self.eye = self.center + direction * distance;
+        self.update_basis();
+        true
+    }
+
     /// Acerca o aleja la cámara hacia/desde el centro
     pub fn zoom(&mut self, amount: f32) {
         let forward = (self.center - self.eye).normalized();
@@ -85,4 +189,43 @@ impl Camera {
             p.x * self.right.z + p.y * self.up.z - p.z * self.forward.z,
         )
     }
+
+    /// Inversa de `basis_change` para un punto: posición relativa al ojo en espacio de cámara
+    pub fn to_camera_space(&self, point: &Vector3) -> Vector3 {
+        let d = *point - self.eye;
+        Vector3::new(d.dot(self.right), d.dot(self.up), -d.dot(self.forward))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instance::Prototype;
+    use crate::material::Material;
+    use std::sync::Arc;
+
+    #[test]
+    fn framing_fits_instances_inside_the_field_of_view() {
+        let cubes = vec![Cube::new(Vector3::zero(), 2.0, Material::black())];
+        let instance = Instance {
+            prototype: Arc::new(Prototype::new("block", cubes, 1)),
+            offset: Vector3::new(10.0, 0.0, 0.0),
+        };
+        let mut camera = Camera::new(
+            Vector3::new(10.0, 0.0, 5.0),
+            Vector3::new(10.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+        );
+
+        let fov = 60.0_f32.to_radians();
+        // Una escena solo con instancias también se encuadra
+        assert!(camera.frame_objects(&[], &[instance], fov, 16.0 / 9.0));
+        assert_eq!(camera.center, Vector3::new(10.0, 0.0, 0.0));
+        assert!((camera.forward - Vector3::new(0.0, 0.0, -1.0)).length() < 1e-4);
+
+        // La esfera que envuelve el cubo toca justo el campo de visión vertical, el más estrecho
+        let radius = 3.0_f32.sqrt();
+        let distance = (camera.eye - camera.center).length();
+        assert!((radius / distance - (fov * 0.5).sin()).abs() < 1e-4);
+    }
 }
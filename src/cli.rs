@@ -0,0 +1,666 @@
+// cli.rs - Command line options for interactive and batch rendering
+use crate::axis::UpAxis;
+use crate::bvh::DEFAULT_LEAF_SIZE;
+use crate::denoise::Denoiser;
+use crate::sampling::SamplePattern;
+use crate::{DebugMode, Region, ToneMap};
+use raylib::prelude::Vector3;
+
+// Tamaño de render cuando ni la línea de comandos ni la escena lo indican
+pub const DEFAULT_WIDTH: i32 = 1300;
+pub const DEFAULT_HEIGHT: i32 = 900;
+
+pub const USAGE: &str = "Usage: raytracer [render] [options]
+
+Without arguments the interactive window opens with the default scene.
+
+Options:
+  --scene <path>            Scene to load (.scn, .json or a plain cube list .txt)
+  --output <path>           Image written by the batch render, .hdr keeps the full range (default: render.png)
+  --width <px>              Render width (default: the scene's or 1300)
+  --height <px>             Render height (default: the scene's or 900)
+  --up-axis <y|z>           World up axis for the camera orbit and the sky (default: the scene's or y)
+  --samples <n>             Samples per pixel for anti-aliasing (default: 1)
+  --final-samples <n>       Samples for the still frame once the view stops moving; --samples is the preview (default: off)
+  --sampling <pattern>      Sample placement: lattice or stratified (jittered grid) (default: lattice)
+  --seed <n>                Seed for the stratified jitter (default: 0)
+  --ibl <strength>          Skybox contribution to ambient light, 0 disables it (default: 0)
+  --irradiance <strength>   Soft sky lighting from a probe precomputed at startup, 0 disables it (default: 0)
+  --orbit-step <degrees>    Angle per key press when orbit snapping is toggled with O (default: 15)
+  --far <distance>          Rays farther than this show the sky (default: unlimited)
+  --near <distance>         Camera rays start this far ahead of the eye (default: 0)
+  --background <r,g,b>      Flat color for rays that miss, components in 0..1 (default: sky)
+  --crop <x,y,w,h>          Only trace this pixel rectangle, the rest of the image stays black
+  --split                   Show the camera and a top-down view of the same point side by side
+  --stereo <distance>       Red/cyan anaglyph with this eye separation, 0 renders mono (default: 0)
+  --exposure <value>        Multiply the radiance before tone mapping the 8-bit image (default: 1 or saved)
+  --auto-exposure           Adapt the exposure to the average brightness of the last frame
+  --tonemap <curve>         Tone mapping for the 8-bit image: none, reinhard or aces (default: none or saved)
+  --denoise <radius>        Edge-aware filter over the image guided by normals and depth, 0 disables it (default: 0)
+  --denoise-sigma <s,n,d>   Filter tolerance for screen distance, normal and relative depth (default: 2,0.1,0.05)
+  --vignette <strength>     Darken the image corners, 0 disables it (default: 0)
+  --debug <mode>            Show only one term: diffuse, specular, reflection, refraction, emission or depth
+  --depth-range <near,far>  Distances shown white and black by --debug depth (default: the scene's bounds)
+  --threads <n>             Render threads, 0 uses every core (default: 0)
+  --texture-budget <MB>     Keep at most this much decoded texture data, reloading evicted textures on demand (default: unlimited)
+  --ray-differentials       Filter textures over each pixel's footprint instead of sampling a single texel
+  --placeholder-textures    Show missing textures as a magenta checkerboard
+  --ao                      Ambient occlusion: a dim ambient term darkened in corners, baked once for static scenes
+  --clamp-bounces           Scale reflection and refraction down when reflectivity + transparency passes 1
+  --energy-conserving       Normalize diffuse by pi and keep material weights from adding up past 1
+  --shadow-jitter <amount>  Soften point light shadow edges by jittering shadow rays, 0 keeps them hard (default: 0)
+  --shadow-rays <n>         Shadow rays averaged per point light with --shadow-jitter, 1 keeps hard shadows (default: 4)
+  --transparent-shadows     Let transparent objects cast shadows like opaque ones
+  --no-emissive-lights      Emissive objects glow but do not light the rest of the scene
+  --aovs                    Also write normal, depth and albedo .hdr images next to --output
+  --no-window               Render a single frame to --output without opening a window
+  --turntable <frames>      Render a full orbit of the camera as numbered PNGs, without a window
+  --turntable-dir <dir>     Directory for the turntable frames (default: turntable)
+  --turntable-radius <r>    Camera distance to the center during the turntable (default: the scene's)
+  --turntable-pitch <deg>   Camera elevation during the turntable, -80..80 (default: the scene's)
+  --export-bvh <path>       Write the BVH tree for inspection, as graphviz DOT if the path ends in .dot
+  --bvh-leaf-size <n>       Objects per BVH leaf; larger leaves give a shallower tree (default: 1)
+  --bench                   Run the render benchmark and exit (with --ao, also time AO)
+  -h, --help                Show this message
+
+The `render` subcommand is shorthand for --no-window.";
+
+pub struct CliOptions {
+    pub scene: Option<String>,
+    pub output: String,
+    /// Tamaño pedido en la línea de comandos; `None` usa el de la escena o el por defecto
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    /// Eje vertical pedido en la línea de comandos; `None` usa el de la escena o Y
+    pub up_axis: Option<UpAxis>,
+    pub samples: u32,
+    /// Muestras por píxel del frame final cuando la vista se queda quieta; mientras se mueve
+    /// se usan `samples`. En los renders sin ventana no hay vista previa y se usan siempre.
+    pub final_samples: Option<u32>,
+    pub sample_pattern: SamplePattern,
+    pub seed: u32,
+    pub ibl_strength: f32,
+    pub irradiance_strength: f32,
+    /// Salto en grados de la órbita por pasos
+    pub orbit_step: f32,
+    pub max_distance: f32,
+    pub near: f32,
+    pub background: Option<Vector3>,
+    pub vignette: f32,
+    /// `None` deja la curva y la exposición guardadas en `settings.txt` (o las de siempre)
+    pub tone_map: Option<ToneMap>,
+    pub exposure: Option<f32>,
+    pub auto_exposure: bool,
+    pub denoiser: Denoiser,
+    pub crop: Option<Region>,
+    pub split: bool,
+    pub stereo: f32,
+    pub debug_mode: DebugMode,
+    /// Distancias que la vista de profundidad pinta de blanco y de negro; `None` las saca de
+    /// la caja de la escena
+    pub depth_range: Option<(f32, f32)>,
+    pub threads: Option<usize>,
+    pub placeholder_textures: bool,
+    pub ray_differentials: bool,
+    /// Memoria máxima de texturas en CPU en MB; `None` no pone límite
+    pub texture_budget_mb: Option<usize>,
+    pub emissive_lights: bool,
+    pub energy_conserving: bool,
+    pub clamp_bounces: bool,
+    pub transparent_shadows: bool,
+    pub shadow_jitter: f32,
+    pub shadow_jitter_samples: u32,
+    pub aovs: bool,
+    /// Activa la oclusión ambiental; apagada la escena se ve como sin ella
+    pub ao: bool,
+    pub headless: bool,
+    /// Fotogramas de la vuelta completa de cámara; `None` renderiza una sola imagen
+    pub turntable: Option<u32>,
+    pub turntable_dir: String,
+    pub turntable_radius: Option<f32>,
+    /// Elevación en grados; `None` conserva la de la escena
+    pub turntable_pitch: Option<f32>,
+    /// Archivo en el que volcar el BVH construido (DOT si termina en `.dot`)
+    pub export_bvh: Option<String>,
+    /// Máximo de objetos en cada hoja del BVH
+    pub bvh_leaf_size: usize,
+    pub bench: bool,
+    pub help: bool,
+}
+
+impl Default for CliOptions {
+    fn default() -> Self {
+        CliOptions {
+            scene: None,
+            output: "render.png".to_string(),
+            width: None,
+            height: None,
+            up_axis: None,
+            samples: 1,
+            final_samples: None,
+            sample_pattern: SamplePattern::Lattice,
+            seed: 0,
+            ibl_strength: 0.0,
+            irradiance_strength: 0.0,
+            orbit_step: 15.0,
+            max_distance: f32::INFINITY,
+            near: 0.0,
+            background: None,
+            vignette: 0.0,
+            tone_map: None,
+            exposure: None,
+            auto_exposure: false,
+            denoiser: Denoiser::default(),
+            crop: None,
+            split: false,
+            stereo: 0.0,
+            debug_mode: DebugMode::Off,
+            depth_range: None,
+            threads: None,
+            placeholder_textures: false,
+            ray_differentials: false,
+            texture_budget_mb: None,
+            emissive_lights: true,
+            energy_conserving: false,
+            clamp_bounces: false,
+            transparent_shadows: false,
+            shadow_jitter: 0.0,
+            shadow_jitter_samples: 4,
+            aovs: false,
+            ao: false,
+            headless: false,
+            turntable: None,
+            turntable_dir: "turntable".to_string(),
+            turntable_radius: None,
+            turntable_pitch: None,
+            export_bvh: None,
+            bvh_leaf_size: DEFAULT_LEAF_SIZE,
+            bench: false,
+            help: false,
+        }
+    }
+}
+
+fn value_for(flag: &str, args: &mut impl Iterator<Item = String>) -> Result<String, String> {
+    args.next()
+        .ok_or_else(|| format!("Missing value for '{}'", flag))
+}
+
+fn number_for<T: std::str::FromStr>(
+    flag: &str,
+    args: &mut impl Iterator<Item = String>,
+) -> Result<T, String> {
+    let value = value_for(flag, args)?;
+    value
+        .parse::<T>()
+        .map_err(|_| format!("Invalid value for '{}': '{}'", flag, value))
+}
+
+/// Tres números separados por comas; `expected` describe el formato en el error
+fn triple_for(
+    flag: &str,
+    args: &mut impl Iterator<Item = String>,
+    expected: &str,
+) -> Result<[f32; 3], String> {
+    let value = value_for(flag, args)?;
+    let parts: Vec<f32> = value
+        .split(',')
+        .map(|part| part.trim().parse::<f32>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("Invalid value for '{}': '{}'", flag, value))?;
+
+    match parts[..] {
+        [a, b, c] => Ok([a, b, c]),
+        _ => Err(format!(
+            "Invalid value for '{}': '{}', expected {}",
+            flag, value, expected
+        )),
+    }
+}
+
+fn color_for(flag: &str, args: &mut impl Iterator<Item = String>) -> Result<Vector3, String> {
+    let [r, g, b] = triple_for(flag, args, "r,g,b")?;
+    Ok(Vector3::new(r, g, b))
+}
+
+fn region_for(flag: &str, args: &mut impl Iterator<Item = String>) -> Result<Region, String> {
+    let value = value_for(flag, args)?;
+    let parts: Vec<i32> = value
+        .split(',')
+        .map(|part| part.trim().parse::<i32>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("Invalid value for '{}': '{}'", flag, value))?;
+
+    match parts[..] {
+        [x, y, width, height] if width > 0 && height > 0 => Ok(Region::new(x, y, width, height)),
+        _ => Err(format!(
+            "Invalid value for '{}': '{}', expected x,y,width,height",
+            flag, value
+        )),
+    }
+}
+
+/// Interpreta los argumentos (sin incluir el nombre del programa)
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<CliOptions, String> {
+    let mut options = CliOptions::default();
+    let mut args = args.into_iter();
+    let mut first = true;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "render" if first => options.headless = true,
+            "--scene" => options.scene = Some(value_for(&arg, &mut args)?),
+            "--output" | "-o" => options.output = value_for(&arg, &mut args)?,
+            "--width" => options.width = Some(number_for(&arg, &mut args)?),
+            "--height" => options.height = Some(number_for(&arg, &mut args)?),
+            "--samples" => options.samples = number_for(&arg, &mut args)?,
+            "--final-samples" => options.final_samples = Some(number_for(&arg, &mut args)?),
+            "--ibl" => options.ibl_strength = number_for(&arg, &mut args)?,
+            "--orbit-step" => options.orbit_step = number_for(&arg, &mut args)?,
+            "--irradiance" => options.irradiance_strength = number_for(&arg, &mut args)?,
+            "--far" => options.max_distance = number_for(&arg, &mut args)?,
+            "--near" => options.near = number_for(&arg, &mut args)?,
+            "--background" => options.background = Some(color_for(&arg, &mut args)?),
+            "--vignette" => options.vignette = number_for(&arg, &mut args)?,
+            "--crop" => options.crop = Some(region_for(&arg, &mut args)?),
+            "--split" => options.split = true,
+            "--stereo" => options.stereo = number_for(&arg, &mut args)?,
+            "--debug" => {
+                let value = value_for(&arg, &mut args)?;
+                options.debug_mode = DebugMode::from_name(&value)
+                    .ok_or_else(|| format!("Unknown debug mode '{}'", value))?;
+            }
+            "--depth-range" => {
+                let value = value_for(&arg, &mut args)?;
+                let range = value
+                    .split_once(',')
+                    .and_then(|(near, far)| {
+                        Some((
+                            near.trim().parse::<f32>().ok()?,
+                            far.trim().parse::<f32>().ok()?,
+                        ))
+                    })
+                    .filter(|&(near, far)| near >= 0.0 && near < far && far.is_finite());
+                options.depth_range = Some(range.ok_or_else(|| {
+                    format!(
+                        "Invalid value for '{}': '{}', expected near,far with 0 <= near < far",
+                        arg, value
+                    )
+                })?);
+            }
+            "--tonemap" => {
+                let value = value_for(&arg, &mut args)?;
+                options.tone_map = Some(
+                    ToneMap::from_name(&value)
+                        .ok_or_else(|| format!("Unknown tone mapping '{}'", value))?,
+                );
+            }
+            "--sampling" => {
+                let value = value_for(&arg, &mut args)?;
+                options.sample_pattern = SamplePattern::from_name(&value)
+                    .ok_or_else(|| format!("Unknown sample pattern '{}'", value))?;
+            }
+            "--up-axis" => {
+                let value = value_for(&arg, &mut args)?;
+                options.up_axis = Some(
+                    UpAxis::from_name(&value)
+                        .ok_or_else(|| format!("Unknown up axis '{}'", value))?,
+                );
+            }
+            "--seed" => options.seed = number_for(&arg, &mut args)?,
+            "--exposure" => options.exposure = Some(number_for(&arg, &mut args)?),
+            "--auto-exposure" => options.auto_exposure = true,
+            "--denoise" => options.denoiser.radius = number_for(&arg, &mut args)?,
+            "--denoise-sigma" => {
+                let [spatial, normal, depth] = triple_for(&arg, &mut args, "spatial,normal,depth")?;
+                options.denoiser.sigma_spatial = spatial;
+                options.denoiser.sigma_normal = normal;
+                options.denoiser.sigma_depth = depth;
+            }
+            "--threads" => options.threads = Some(number_for(&arg, &mut args)?),
+            "--ray-differentials" => options.ray_differentials = true,
+            "--placeholder-textures" => options.placeholder_textures = true,
+            "--texture-budget" => options.texture_budget_mb = Some(number_for(&arg, &mut args)?),
+            "--no-emissive-lights" => options.emissive_lights = false,
+            "--energy-conserving" => options.energy_conserving = true,
+            "--clamp-bounces" => options.clamp_bounces = true,
+            "--transparent-shadows" => options.transparent_shadows = true,
+            "--shadow-jitter" => options.shadow_jitter = number_for(&arg, &mut args)?,
+            "--shadow-rays" => options.shadow_jitter_samples = number_for(&arg, &mut args)?,
+            "--aovs" => options.aovs = true,
+            "--ao" => options.ao = true,
+            "--no-window" | "--headless" => options.headless = true,
+            "--turntable" => {
+                options.turntable = Some(number_for(&arg, &mut args)?);
+                options.headless = true;
+            }
+            "--turntable-dir" => options.turntable_dir = value_for(&arg, &mut args)?,
+            "--turntable-radius" => options.turntable_radius = Some(number_for(&arg, &mut args)?),
+            "--turntable-pitch" => options.turntable_pitch = Some(number_for(&arg, &mut args)?),
+            "--export-bvh" => options.export_bvh = Some(value_for(&arg, &mut args)?),
+            "--bvh-leaf-size" => options.bvh_leaf_size = number_for(&arg, &mut args)?,
+            "--bench" => options.bench = true,
+            "-h" | "--help" => options.help = true,
+            _ => return Err(format!("Unknown argument '{}'", arg)),
+        }
+        first = false;
+    }
+
+    for size in [options.width, options.height].into_iter().flatten() {
+        if size <= 0 {
+            return Err(format!("Render size must be positive, got {}", size));
+        }
+    }
+    if options.turntable == Some(0) {
+        return Err("Turntable needs at least 1 frame".to_string());
+    }
+    if let Some(radius) = options.turntable_radius
+        && !(radius.is_finite() && radius > 0.0)
+    {
+        return Err(format!("Turntable radius must be positive, got {}", radius));
+    }
+    if let Some(pitch) = options.turntable_pitch
+        && !(-80.0..=80.0).contains(&pitch)
+    {
+        return Err(format!(
+            "Turntable pitch must be between -80 and 80 degrees, got {}",
+            pitch
+        ));
+    }
+    if options.samples == 0 {
+        return Err("Samples per pixel must be at least 1".to_string());
+    }
+    if options.final_samples == Some(0) {
+        return Err("Final samples per pixel must be at least 1".to_string());
+    }
+    if options.bvh_leaf_size == 0 {
+        return Err("BVH leaf size must be at least 1".to_string());
+    }
+    if !options.ibl_strength.is_finite() || options.ibl_strength < 0.0 {
+        return Err(format!(
+            "IBL strength must be zero or positive, got {}",
+            options.ibl_strength
+        ));
+    }
+    if !options.irradiance_strength.is_finite() || options.irradiance_strength < 0.0 {
+        return Err(format!(
+            "Irradiance strength must be zero or positive, got {}",
+            options.irradiance_strength
+        ));
+    }
+    if !(options.orbit_step > 0.0 && options.orbit_step <= 90.0) {
+        return Err(format!(
+            "Orbit step must be between 0 and 90 degrees, got {}",
+            options.orbit_step
+        ));
+    }
+    if let Some(exposure) = options.exposure
+        && (!exposure.is_finite() || exposure <= 0.0)
+    {
+        return Err(format!("Exposure must be positive, got {}", exposure));
+    }
+    if options.denoiser.radius < 0 {
+        return Err(format!(
+            "Denoise radius must be zero or positive, got {}",
+            options.denoiser.radius
+        ));
+    }
+    let sigmas = [
+        options.denoiser.sigma_spatial,
+        options.denoiser.sigma_normal,
+        options.denoiser.sigma_depth,
+    ];
+    if sigmas.iter().any(|s| !s.is_finite() || *s <= 0.0) {
+        return Err(format!("Denoise sigmas must be positive, got {:?}", sigmas));
+    }
+    if !options.shadow_jitter.is_finite() || options.shadow_jitter < 0.0 {
+        return Err(format!(
+            "Shadow jitter must be zero or positive, got {}",
+            options.shadow_jitter
+        ));
+    }
+    if options.shadow_jitter_samples == 0 {
+        return Err("Shadow rays must be at least 1".to_string());
+    }
+    if !(0.0..=1.0).contains(&options.vignette) {
+        return Err(format!(
+            "Vignette strength must be between 0 and 1, got {}",
+            options.vignette
+        ));
+    }
+    if options.max_distance.is_nan() || options.max_distance <= 0.0 {
+        return Err(format!(
+            "Far distance must be positive, got {}",
+            options.max_distance
+        ));
+    }
+    if !options.near.is_finite() || options.near < 0.0 {
+        return Err(format!(
+            "Near distance must be zero or positive, got {}",
+            options.near
+        ));
+    }
+    if options.near >= options.max_distance {
+        return Err(format!(
+            "Near distance {} must be smaller than the far distance {}",
+            options.near, options.max_distance
+        ));
+    }
+    if !options.stereo.is_finite() || options.stereo < 0.0 {
+        return Err(format!(
+            "Stereo eye distance must be zero or positive, got {}",
+            options.stereo
+        ));
+    }
+    if options.split && options.stereo > 0.0 {
+        return Err("--stereo cannot be combined with --split".to_string());
+    }
+    if options.split && options.crop.is_some() {
+        return Err("--crop cannot be combined with --split".to_string());
+    }
+    if options.output.to_lowercase().ends_with(".exr") {
+        return Err("OpenEXR output is not supported, use .hdr instead".to_string());
+    }
+
+    Ok(options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<CliOptions, String> {
+        parse_args(args.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn no_arguments_keeps_interactive_defaults() {
+        let options = parse(&[]).unwrap();
+        assert!(!options.headless);
+        assert_eq!(options.scene, None);
+        assert_eq!((options.width, options.height), (None, None));
+        assert_eq!(options.samples, 1);
+        assert_eq!(options.debug_mode, DebugMode::Off);
+        assert!(options.emissive_lights);
+    }
+
+    #[test]
+    fn ambient_occlusion_is_opt_in() {
+        assert!(!parse(&[]).unwrap().ao);
+        assert!(parse(&["--ao"]).unwrap().ao);
+    }
+
+    #[test]
+    fn emissive_lights_can_be_disabled() {
+        let options = parse(&["--no-emissive-lights"]).unwrap();
+        assert!(!options.emissive_lights);
+    }
+
+    #[test]
+    fn final_samples_are_optional_and_positive() {
+        assert_eq!(parse(&[]).unwrap().final_samples, None);
+        let options = parse(&["--samples", "2", "--final-samples", "16"]).unwrap();
+        assert_eq!((options.samples, options.final_samples), (2, Some(16)));
+        assert!(parse(&["--final-samples", "0"]).is_err());
+    }
+
+    #[test]
+    fn render_subcommand_reads_all_options() {
+        let options = parse(&[
+            "render",
+            "--scene",
+            "castle.scn",
+            "-o",
+            "out.png",
+            "--width",
+            "320",
+            "--height",
+            "200",
+            "--samples",
+            "4",
+            "--threads",
+            "2",
+            "--texture-budget",
+            "64",
+        ])
+        .unwrap();
+        assert!(options.headless);
+        assert_eq!(options.scene.as_deref(), Some("castle.scn"));
+        assert_eq!(options.output, "out.png");
+        assert_eq!((options.width, options.height), (Some(320), Some(200)));
+        assert_eq!(options.samples, 4);
+        assert_eq!(options.threads, Some(2));
+        assert_eq!(options.texture_budget_mb, Some(64));
+    }
+
+    #[test]
+    fn bvh_leaf_size_must_be_positive() {
+        assert_eq!(parse(&[]).unwrap().bvh_leaf_size, DEFAULT_LEAF_SIZE);
+        assert_eq!(parse(&["--bvh-leaf-size", "4"]).unwrap().bvh_leaf_size, 4);
+        assert!(parse(&["--bvh-leaf-size", "0"]).is_err());
+        assert!(parse(&["--bvh-leaf-size", "-2"]).is_err());
+    }
+
+    #[test]
+    fn background_is_parsed_as_rgb() {
+        let options = parse(&["--background", "0.5, 0.25,1"]).unwrap();
+        assert_eq!(options.background, Some(Vector3::new(0.5, 0.25, 1.0)));
+        assert!(parse(&["--background", "0.5,0.25"]).is_err());
+        assert!(parse(&["--background", "red"]).is_err());
+    }
+
+    #[test]
+    fn tone_map_is_parsed_by_name() {
+        assert_eq!(parse(&[]).unwrap().tone_map, None);
+        let tone_map = parse(&["--tonemap", "aces"]).unwrap().tone_map.unwrap();
+        assert_eq!(tone_map, ToneMap::Aces);
+        assert_eq!(tone_map.apply(Vector3::zero()), Vector3::zero());
+        assert!(tone_map.apply(Vector3::new(50.0, 50.0, 50.0)).x <= 1.0);
+    }
+
+    #[test]
+    fn denoiser_reads_radius_and_sigmas() {
+        assert!(!parse(&[]).unwrap().denoiser.is_enabled());
+        let options = parse(&["--denoise", "3", "--denoise-sigma", "1.5,0.2,0.1"]).unwrap();
+        assert_eq!(options.denoiser.radius, 3);
+        assert_eq!(options.denoiser.sigma_spatial, 1.5);
+        assert_eq!(options.denoiser.sigma_normal, 0.2);
+        assert_eq!(options.denoiser.sigma_depth, 0.1);
+        assert!(parse(&["--denoise", "-1"]).is_err());
+        assert!(parse(&["--denoise-sigma", "1,0,1"]).is_err());
+    }
+
+    #[test]
+    fn depth_view_takes_an_optional_range() {
+        let options = parse(&["--debug", "depth"]).unwrap();
+        assert_eq!(options.debug_mode, DebugMode::Depth);
+        assert_eq!(options.depth_range, None);
+        let options = parse(&["--debug", "depth", "--depth-range", "0.5, 20"]).unwrap();
+        assert_eq!(options.depth_range, Some((0.5, 20.0)));
+    }
+
+    #[test]
+    fn up_axis_is_parsed_by_name() {
+        assert_eq!(parse(&[]).unwrap().up_axis, None);
+        assert_eq!(parse(&["--up-axis", "z"]).unwrap().up_axis, Some(UpAxis::Z));
+        assert!(parse(&["--up-axis", "x"]).is_err());
+    }
+
+    #[test]
+    fn sample_pattern_is_parsed_by_name() {
+        assert_eq!(parse(&[]).unwrap().sample_pattern, SamplePattern::Lattice);
+        let options = parse(&["--sampling", "stratified", "--seed", "7"]).unwrap();
+        assert_eq!(options.sample_pattern, SamplePattern::Stratified);
+        assert_eq!(options.seed, 7);
+        assert!(parse(&["--sampling", "random"]).is_err());
+    }
+
+    #[test]
+    fn shadow_jitter_reads_amount_and_rays() {
+        let options = parse(&[]).unwrap();
+        assert_eq!(options.shadow_jitter, 0.0);
+        assert_eq!(options.shadow_jitter_samples, 4);
+
+        let options = parse(&["--shadow-jitter", "0.05", "--shadow-rays", "6"]).unwrap();
+        assert_eq!(options.shadow_jitter, 0.05);
+        assert_eq!(options.shadow_jitter_samples, 6);
+        assert!(parse(&["--shadow-jitter", "-0.1"]).is_err());
+        assert!(parse(&["--shadow-rays", "0"]).is_err());
+    }
+
+    #[test]
+    fn crop_is_parsed_as_rectangle() {
+        let options = parse(&["--crop", "10,20,64,32"]).unwrap();
+        assert_eq!(options.crop, Some(Region::new(10, 20, 64, 32)));
+        assert!(parse(&["--crop", "10,20,0,32"]).is_err());
+        assert!(parse(&["--crop", "10,20,64"]).is_err());
+    }
+
+    #[test]
+    fn turntable_renders_without_window() {
+        let options = parse(&[
+            "--turntable",
+            "24",
+            "--turntable-dir",
+            "frames",
+            "--turntable-pitch",
+            "30",
+        ])
+        .unwrap();
+        assert!(options.headless);
+        assert_eq!(options.turntable, Some(24));
+        assert_eq!(options.turntable_dir, "frames");
+        assert_eq!(options.turntable_radius, None);
+        assert_eq!(options.turntable_pitch, Some(30.0));
+
+        assert!(parse(&["--turntable", "0"]).is_err());
+        assert!(parse(&["--turntable", "8", "--turntable-radius", "0"]).is_err());
+        assert!(parse(&["--turntable", "8", "--turntable-pitch", "95"]).is_err());
+    }
+
+    #[test]
+    fn invalid_arguments_are_reported() {
+        assert!(parse(&["--width"]).is_err());
+        assert!(parse(&["--width", "wide"]).is_err());
+        assert!(parse(&["--samples", "0"]).is_err());
+        assert!(parse(&["--ibl", "-0.5"]).is_err());
+        assert!(parse(&["--irradiance", "-1"]).is_err());
+        assert!(parse(&["--orbit-step", "0"]).is_err());
+        assert!(parse(&["--orbit-step", "120"]).is_err());
+        assert!(parse(&["--far", "0"]).is_err());
+        assert!(parse(&["--near", "-1"]).is_err());
+        assert!(parse(&["--near", "5", "--far", "5"]).is_err());
+        assert!(parse(&["--debug", "wireframe"]).is_err());
+        assert!(parse(&["--depth-range", "5,2"]).is_err());
+        assert!(parse(&["--depth-range", "-1,2"]).is_err());
+        assert!(parse(&["--depth-range", "3"]).is_err());
+        assert!(parse(&["--tonemap", "filmic"]).is_err());
+        assert!(parse(&["--exposure", "0"]).is_err());
+        assert!(parse(&["--frobnicate"]).is_err());
+        assert!(parse(&["--no-window", "render"]).is_err());
+        assert!(parse(&["render", "-o", "out.exr"]).is_err());
+        assert!(parse(&["--split", "--crop", "0,0,10,10"]).is_err());
+        assert!(parse(&["--stereo", "-0.1"]).is_err());
+        assert!(parse(&["--split", "--stereo", "0.1"]).is_err());
+    }
+}
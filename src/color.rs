@@ -0,0 +1,167 @@
+// color.rs - Shared color helpers: luminance, sRGB transfer curves, clamping and 8-bit conversion
+use raylib::prelude::*;
+
+/// Luminancia relativa con los pesos de Rec. 709
+pub fn luminance(v: Vector3) -> f32 {
+    0.2126 * v.x + 0.7152 * v.y + 0.0722 * v.z
+}
+
+/// Cada canal recortado a [0, 1]
+pub fn saturate(v: Vector3) -> Vector3 {
+    Vector3::new(
+        v.x.clamp(0.0, 1.0),
+        v.y.clamp(0.0, 1.0),
+        v.z.clamp(0.0, 1.0),
+    )
+}
+
+/// Cada canal negativo llevado a 0, sin límite por arriba
+pub fn non_negative(v: Vector3) -> Vector3 {
+    Vector3::new(v.x.max(0.0), v.y.max(0.0), v.z.max(0.0))
+}
+
+/// Limita la luminancia de una muestra conservando su tono, para evitar "fireflies".
+/// Un límite de 0 o infinito la deja intacta.
+pub fn clamp_luminance(color: Vector3, max_luminance: f32) -> Vector3 {
+    if max_luminance <= 0.0 || !max_luminance.is_finite() {
+        return color;
+    }
+
+    let lum = luminance(color);
+    if lum > max_luminance {
+        color * (max_luminance / lum)
+    } else {
+        color
+    }
+}
+
+/// Color de los píxeles con algún componente NaN o infinito, para que destaquen
+pub const INVALID_COLOR: Color = Color::new(255, 0, 255, 255);
+
+fn is_finite_color(v: Vector3) -> bool {
+    v.x.is_finite() && v.y.is_finite() && v.z.is_finite()
+}
+
+/// Color de 8 bits con cada canal recortado a [0, 1]. Un NaN o un infinito en cualquier
+/// componente viene de un error de sombreado y pinta el píxel entero de `INVALID_COLOR`.
+pub fn vector3_to_color(v: Vector3) -> Color {
+    if !is_finite_color(v) {
+        return INVALID_COLOR;
+    }
+    Color::new(
+        (v.x * 255.0).min(255.0) as u8,
+        (v.y * 255.0).min(255.0) as u8,
+        (v.z * 255.0).min(255.0) as u8,
+        255,
+    )
+}
+
+pub fn color_to_vector3(color: Color) -> Vector3 {
+    Vector3::new(
+        color.r as f32 / 255.0,
+        color.g as f32 / 255.0,
+        color.b as f32 / 255.0,
+    )
+}
+
+/// Decodifica un canal sRGB en [0, 1] a su valor lineal
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Codifica un canal lineal en [0, 1] con la curva sRGB
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Igual que `color_to_vector3`, pero decodificando el gamma sRGB para obtener color lineal
+pub fn srgb_color_to_vector3(color: Color) -> Vector3 {
+    let v = color_to_vector3(color);
+    Vector3::new(
+        srgb_to_linear(v.x),
+        srgb_to_linear(v.y),
+        srgb_to_linear(v.z),
+    )
+}
+
+/// Igual que `vector3_to_color`, pero codificando en sRGB y redondeando al entero más cercano
+pub fn vector3_to_srgb_color(v: Vector3) -> Color {
+    if !is_finite_color(v) {
+        return INVALID_COLOR;
+    }
+    let encode = |c: f32| (linear_to_srgb(c.clamp(0.0, 1.0)) * 255.0).round() as u8;
+    Color::new(encode(v.x), encode(v.y), encode(v.z), 255)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn luminance_uses_rec709_weights() {
+        assert!((luminance(Vector3::one()) - 1.0).abs() < 1e-6);
+        assert_eq!(luminance(Vector3::zero()), 0.0);
+        assert_eq!(luminance(Vector3::new(0.0, 1.0, 0.0)), 0.7152);
+        // El verde pesa mucho más que el azul con la misma energía
+        assert!(luminance(Vector3::new(0.0, 0.5, 0.0)) > luminance(Vector3::new(0.0, 0.0, 1.0)));
+    }
+
+    #[test]
+    fn clamps_keep_channels_in_range() {
+        let v = Vector3::new(-0.5, 0.25, 3.0);
+        assert_eq!(saturate(v), Vector3::new(0.0, 0.25, 1.0));
+        assert_eq!(non_negative(v), Vector3::new(0.0, 0.25, 3.0));
+
+        // Limitar la luminancia conserva el tono; sin límite no cambia nada
+        let bright = Vector3::new(4.0, 2.0, 1.0);
+        let clamped = clamp_luminance(bright, 1.0);
+        assert!((luminance(clamped) - 1.0).abs() < 1e-5);
+        assert!((clamped.x / clamped.y - 2.0).abs() < 1e-5);
+        assert_eq!(clamp_luminance(bright, f32::INFINITY), bright);
+        assert_eq!(clamp_luminance(bright, 0.0), bright);
+    }
+
+    #[test]
+    fn non_finite_colors_are_flagged() {
+        for bad in [
+            Vector3::new(f32::NAN, 0.5, 0.5),
+            Vector3::new(0.2, f32::INFINITY, 0.0),
+            Vector3::new(0.0, 0.0, f32::NEG_INFINITY),
+        ] {
+            assert_eq!(vector3_to_color(bad), INVALID_COLOR);
+            assert_eq!(vector3_to_srgb_color(bad), INVALID_COLOR);
+        }
+
+        // Los valores finitos fuera de rango se siguen recortando
+        assert_eq!(
+            vector3_to_color(Vector3::new(-1.0, 0.5, 7.0)),
+            Color::new(0, 127, 255, 255)
+        );
+    }
+
+    #[test]
+    fn srgb_mid_gray_round_trips() {
+        let gray = Color::new(128, 128, 128, 255);
+        let linear = srgb_color_to_vector3(gray);
+
+        // El gris medio en sRGB es bastante más oscuro en lineal
+        assert!((linear.x - 0.2158).abs() < 1e-3);
+        assert_eq!(vector3_to_srgb_color(linear), gray);
+    }
+
+    #[test]
+    fn srgb_round_trips_every_channel_value() {
+        for value in 0..=255u8 {
+            let c = srgb_to_linear(value as f32 / 255.0);
+            assert_eq!((linear_to_srgb(c) * 255.0).round() as u8, value);
+        }
+    }
+}
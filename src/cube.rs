@@ -2,10 +2,19 @@ use crate::material::Material;
 use crate::ray_intersect::{Intersect, RayIntersect};
 use raylib::prelude::Vector3;
 
+/// Distancia mínima de un impacto válido; más cerca se considera que el rayo parte de la
+/// superficie y se toma la cara de salida
+pub const HIT_EPSILON: f32 = 1e-3;
+
 pub struct Cube {
     pub min_bounds: Vector3,
     pub max_bounds: Vector3,
     pub material: Material,
+    /// Los objetos deshabilitados siguen en la escena pero los rayos los atraviesan
+    pub enabled: bool,
+    /// Volumen de resta: no se ve, pero vacía la parte de los demás cubos que queda dentro.
+    /// Sus caras pintan con su material las paredes del hueco.
+    pub subtractive: bool,
 }
 
 impl Cube {
@@ -15,6 +24,8 @@ impl Cube {
             min_bounds: center - half_size,
             max_bounds: center + half_size,
             material,
+            enabled: true,
+            subtractive: false,
         }
     }
 
@@ -30,9 +41,85 @@ impl Cube {
             min_bounds: center - half,
             max_bounds: center + half,
             material,
+            enabled: true,
+            subtractive: false,
         }
     }
 
+    /// `point` está dentro de la caja, incluidas sus caras
+    pub fn contains(&self, point: Vector3) -> bool {
+        point.x >= self.min_bounds.x
+            && point.x <= self.max_bounds.x
+            && point.y >= self.min_bounds.y
+            && point.y <= self.max_bounds.y
+            && point.z >= self.min_bounds.z
+            && point.z <= self.max_bounds.z
+    }
+
+    /// Los cubos con transparencia apreciable dejan pasar la luz y no proyectan sombra
+    pub fn is_opaque(&self) -> bool {
+        self.material.transparency <= 0.05
+    }
+
+    /// Normal de sombreado con bisel simulado: a menos de `width` de una arista se inclina
+    /// hacia la normal de la cara vecina, hasta promediar ambas justo sobre la arista
+    pub fn bevel_normal(&self, point: Vector3, normal: Vector3, width: f32) -> Vector3 {
+        if width <= 0.0 {
+            return normal;
+        }
+
+        let axes = [
+            (point.x, self.min_bounds.x, self.max_bounds.x, normal.x),
+            (point.y, self.min_bounds.y, self.max_bounds.y, normal.y),
+            (point.z, self.min_bounds.z, self.max_bounds.z, normal.z),
+        ];
+        let mut tilt = [0.0; 3];
+
+        for (i, &(p, min, max, n)) in axes.iter().enumerate() {
+            // Solo los ejes tangentes a la cara tienen aristas cercanas
+            if n != 0.0 {
+                continue;
+            }
+            let to_min = p - min;
+            let to_max = max - p;
+            if to_min < width && to_min <= to_max {
+                tilt[i] = -(1.0 - to_min / width);
+            } else if to_max < width {
+                tilt[i] = 1.0 - to_max / width;
+            }
+        }
+
+        (normal + Vector3::new(tilt[0], tilt[1], tilt[2])).normalized()
+    }
+
+    /// Dirección en la que crece la coordenada u sobre la cara con esta normal
+    pub fn face_tangent(&self, normal: Vector3) -> Vector3 {
+        if normal.x.abs() > 0.5 {
+            Vector3::new(0.0, 0.0, 1.0)
+        } else {
+            Vector3::new(1.0, 0.0, 0.0)
+        }
+    }
+
+    /// Ancho en (u, v) de la huella de un píxel sobre la cara `normal`, a partir de cuánto
+    /// se mueve el punto de impacto hacia el píxel vecino en x (`point_dx`) y en y (`point_dy`)
+    pub fn uv_footprint(
+        &self,
+        point: Vector3,
+        normal: Vector3,
+        point_dx: Vector3,
+        point_dy: Vector3,
+    ) -> (f32, f32) {
+        // Las UV son afines en la cara, así que la diferencia es exacta aunque salga de ella
+        let (u, v) = self.get_uv(&point, &normal);
+        let (ux, vx) = self.get_uv(&(point + point_dx), &normal);
+        let (uy, vy) = self.get_uv(&(point + point_dy), &normal);
+        (
+            (ux - u).abs().max((uy - u).abs()),
+            (vx - v).abs().max((vy - v).abs()),
+        )
+    }
+
     fn get_uv(&self, point: &Vector3, normal: &Vector3) -> (f32, f32) {
         let size = self.max_bounds - self.min_bounds;
         let u: f32;
@@ -48,7 +135,7 @@ impl Cube {
             u = (point.x - self.min_bounds.x) / size.x;
             v = 1.0 - (point.y - self.min_bounds.y) / size.y;
         }
-        (u, v)
+        self.material.rotate_uv(u, v)
     }
 }
 
@@ -78,11 +165,18 @@ impl RayIntersect for Cube {
             return Intersect::empty();
         }
 
+        // Eje cuyo plano fija la entrada y la salida del rayo: 0 = X, 1 = Y, 2 = Z.
+        // Las comparaciones estrictas dejan ganar al eje anterior en las aristas.
+        let mut entry_axis = 0;
+        let mut exit_axis = 0;
+
         if tymin > tmin {
             tmin = tymin;
+            entry_axis = 1;
         }
         if tymax < tmax {
             tmax = tymax;
+            exit_axis = 1;
         }
 
         let mut tzmin = (self.min_bounds.z - ray_origin.z) * inv_dir.z;
@@ -98,38 +192,290 @@ impl RayIntersect for Cube {
 
         if tzmin > tmin {
             tmin = tzmin;
+            entry_axis = 2;
         }
         if tzmax < tmax {
             tmax = tzmax;
+            exit_axis = 2;
         }
 
-        let distance = if tmin > 0.001 { tmin } else { tmax };
+        let (distance, axis, entering) = if tmin >= HIT_EPSILON {
+            (tmin, entry_axis, true)
+        } else {
+            (tmax, exit_axis, false)
+        };
 
-        if distance < 0.001 {
+        if distance < HIT_EPSILON {
             return Intersect::empty();
         }
 
         let point = *ray_origin + *ray_direction * distance;
 
-        let epsilon = 1e-4;
+        // Al entrar se cruza la cara que mira contra el rayo; al salir, la que mira a favor
+        let direction = [ray_direction.x, ray_direction.y, ray_direction.z][axis];
+        let sign = if (direction > 0.0) == entering {
+            -1.0
+        } else {
+            1.0
+        };
         let mut normal = Vector3::zero();
-
-        if (point.x - self.min_bounds.x).abs() < epsilon {
-            normal.x = -1.0;
-        } else if (point.x - self.max_bounds.x).abs() < epsilon {
-            normal.x = 1.0;
-        } else if (point.y - self.min_bounds.y).abs() < epsilon {
-            normal.y = -1.0;
-        } else if (point.y - self.max_bounds.y).abs() < epsilon {
-            normal.y = 1.0;
-        } else if (point.z - self.min_bounds.z).abs() < epsilon {
-            normal.z = -1.0;
-        } else if (point.z - self.max_bounds.z).abs() < epsilon {
-            normal.z = 1.0;
+        match axis {
+            0 => normal.x = sign,
+            1 => normal.y = sign,
+            _ => normal.z = sign,
         }
 
         let (u, v) = self.get_uv(&point, &normal);
 
-        Intersect::new(self.material.clone(), distance, normal, point, u, v)
+        let mut hit = Intersect::new(self.material.clone(), distance, normal, point, u, v);
+        if !entering {
+            // Desde dentro la cara de salida se ve por su reverso
+            hit.normal = -normal;
+            hit.inside = true;
+        }
+        hit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::MaterialBuilder;
+
+    const EPS: f32 = 1e-4;
+
+    fn test_cube() -> Cube {
+        // min = (-1, -1, -1), max = (1, 1, 1)
+        Cube::new(Vector3::zero(), 2.0, Material::black())
+    }
+
+    #[test]
+    fn uv_footprint_follows_face_size_and_rotation() {
+        // Cara +Z de un cubo de 4 x 2: u recorre x y v recorre y
+        let cube = Cube::new_rect(Vector3::zero(), 4.0, 2.0, 2.0, Material::black());
+        let front = Vector3::new(0.0, 0.0, 1.0);
+        let point = Vector3::new(0.0, 0.0, 1.0);
+        let dx = Vector3::new(0.1, 0.0, 0.0);
+        let dy = Vector3::new(0.0, -0.1, 0.0);
+
+        let (du, dv) = cube.uv_footprint(point, front, dx, dy);
+        assert!((du - 0.025).abs() < EPS && (dv - 0.05).abs() < EPS);
+
+        let rotated = Cube::new_rect(
+            Vector3::zero(),
+            4.0,
+            2.0,
+            2.0,
+            MaterialBuilder::new().uv_rotation(1).build(),
+        );
+        let (du, dv) = rotated.uv_footprint(point, front, dx, dy);
+        assert!((du - 0.05).abs() < EPS && (dv - 0.025).abs() < EPS);
+    }
+
+    fn assert_vec_eq(a: Vector3, b: Vector3) {
+        assert!(
+            (a.x - b.x).abs() < EPS && (a.y - b.y).abs() < EPS && (a.z - b.z).abs() < EPS,
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    /// Dispara un rayo desde fuera del cubo hacia `target`, a lo largo de `-normal`
+    fn hit_face(target: Vector3, normal: Vector3) -> Intersect {
+        let origin = target + normal * 5.0;
+        test_cube().ray_intersect(&origin, &-normal)
+    }
+
+    fn check_face(target: Vector3, normal: Vector3, u: f32, v: f32) {
+        let hit = hit_face(target, normal);
+        assert!(hit.is_intersecting);
+        assert!((hit.distance - 5.0).abs() < EPS);
+        assert_vec_eq(hit.point, target);
+        assert_vec_eq(hit.normal, normal);
+        assert!((hit.u - u).abs() < EPS, "u: {} != {}", hit.u, u);
+        assert!((hit.v - v).abs() < EPS, "v: {} != {}", hit.v, v);
+    }
+
+    #[test]
+    fn x_faces_map_z_to_u_and_flipped_y_to_v() {
+        check_face(
+            Vector3::new(1.0, 0.5, -0.5),
+            Vector3::new(1.0, 0.0, 0.0),
+            0.25,
+            0.25,
+        );
+        check_face(
+            Vector3::new(-1.0, -0.5, 0.5),
+            Vector3::new(-1.0, 0.0, 0.0),
+            0.75,
+            0.75,
+        );
+    }
+
+    #[test]
+    fn y_faces_map_x_to_u_and_z_to_v() {
+        check_face(
+            Vector3::new(0.5, 1.0, -0.5),
+            Vector3::new(0.0, 1.0, 0.0),
+            0.75,
+            0.25,
+        );
+        check_face(
+            Vector3::new(-0.5, -1.0, 0.5),
+            Vector3::new(0.0, -1.0, 0.0),
+            0.25,
+            0.75,
+        );
+    }
+
+    #[test]
+    fn z_faces_map_x_to_u_and_flipped_y_to_v() {
+        check_face(
+            Vector3::new(0.5, 0.5, 1.0),
+            Vector3::new(0.0, 0.0, 1.0),
+            0.75,
+            0.25,
+        );
+        check_face(
+            Vector3::new(-0.5, -0.5, -1.0),
+            Vector3::new(0.0, 0.0, -1.0),
+            0.25,
+            0.75,
+        );
+    }
+
+    #[test]
+    fn rectangular_box_uvs_span_each_face() {
+        let rect = Cube::new_rect(Vector3::zero(), 4.0, 2.0, 1.0, Material::black());
+        let hit = rect.ray_intersect(&Vector3::new(1.0, 0.5, 5.0), &Vector3::new(0.0, 0.0, -1.0));
+        assert_vec_eq(hit.normal, Vector3::new(0.0, 0.0, 1.0));
+        assert!((hit.u - 0.75).abs() < EPS);
+        assert!((hit.v - 0.25).abs() < EPS);
+    }
+
+    #[test]
+    fn uv_rotation_turns_the_face_texture() {
+        // Cerca de la esquina superior izquierda de la cara +Z: (u, v) = (0.25, 0.25)
+        let origin = Vector3::new(-0.5, 0.5, 5.0);
+        let dir = Vector3::new(0.0, 0.0, -1.0);
+        let expected = [(0.25, 0.25), (0.25, 0.75), (0.75, 0.75), (0.75, 0.25)];
+
+        for (turns, (u, v)) in expected.into_iter().enumerate() {
+            let material = MaterialBuilder::new().uv_rotation(turns as u8).build();
+            let hit = Cube::new(Vector3::zero(), 2.0, material).ray_intersect(&origin, &dir);
+            assert!(
+                (hit.u - u).abs() < EPS && (hit.v - v).abs() < EPS,
+                "{}",
+                turns
+            );
+        }
+    }
+
+    #[test]
+    fn edge_hit_prefers_x_face() {
+        // El punto de impacto cae sobre la arista entre +X y +Y; ambas caras empatan
+        let origin = Vector3::new(3.0, 3.0, 0.0);
+        let dir = Vector3::new(-1.0, -1.0, 0.0).normalized();
+        let hit = test_cube().ray_intersect(&origin, &dir);
+
+        assert!(hit.is_intersecting);
+        assert_vec_eq(hit.point, Vector3::new(1.0, 1.0, 0.0));
+        assert_vec_eq(hit.normal, Vector3::new(1.0, 0.0, 0.0));
+        assert!((hit.u - 0.5).abs() < EPS);
+        assert!(hit.v.abs() < EPS);
+    }
+
+    #[test]
+    fn tiny_and_huge_cubes_get_face_normals() {
+        for size in [0.001, 1000.0] {
+            let cube = Cube::new(Vector3::new(3.0, -2.0, 7.0) * size, size, Material::black());
+            let center = (cube.min_bounds + cube.max_bounds) * 0.5;
+            let faces = [
+                Vector3::new(1.0, 0.0, 0.0),
+                Vector3::new(-1.0, 0.0, 0.0),
+                Vector3::new(0.0, 1.0, 0.0),
+                Vector3::new(0.0, -1.0, 0.0),
+                Vector3::new(0.0, 0.0, 1.0),
+                Vector3::new(0.0, 0.0, -1.0),
+            ];
+
+            for normal in faces {
+                // Rayos oblicuos desde lejos, para que el punto acumule error de redondeo
+                let target = center + normal * (size * 0.5) + Vector3::new(0.1, 0.2, 0.15) * size;
+                let origin = target + (normal * 3.0 + Vector3::new(0.3, 0.1, -0.2)) * size * 50.0;
+                let hit = cube.ray_intersect(&origin, &(target - origin).normalized());
+
+                assert!(hit.is_intersecting, "size {} face {:?}", size, normal);
+                assert_eq!(hit.normal, normal, "size {}", size);
+            }
+        }
+    }
+
+    #[test]
+    fn ray_starting_inside_gets_inward_exit_normal() {
+        let dir = Vector3::new(0.0, -1.0, 0.0);
+        let hit = test_cube().ray_intersect(&Vector3::zero(), &dir);
+        assert!(hit.is_intersecting);
+        assert!(hit.inside);
+        assert_eq!(hit.normal, Vector3::new(0.0, 1.0, 0.0));
+        assert!(hit.normal.dot(dir) < 0.0);
+        assert_eq!(hit.outward_normal(), Vector3::new(0.0, -1.0, 0.0));
+
+        let dir = Vector3::new(0.2, 0.3, 1.0).normalized();
+        let hit = test_cube().ray_intersect(&Vector3::new(0.5, 0.5, 0.0), &dir);
+        assert_eq!(hit.normal, Vector3::new(0.0, 0.0, -1.0));
+        assert!(hit.normal.dot(dir) < 0.0);
+        // La cara de salida conserva las UV de siempre
+        assert_vec_eq(hit.point, Vector3::new(0.7, 0.8, 1.0));
+        assert!((hit.u - 0.85).abs() < EPS && (hit.v - 0.1).abs() < EPS);
+    }
+
+    #[test]
+    fn ray_missing_cube_returns_empty() {
+        let hit =
+            test_cube().ray_intersect(&Vector3::new(3.0, 0.0, 5.0), &Vector3::new(0.0, 0.0, -1.0));
+        assert!(!hit.is_intersecting);
+    }
+
+    #[test]
+    fn bevel_tilts_normal_only_near_edges() {
+        let cube = test_cube();
+        let up = Vector3::new(0.0, 1.0, 0.0);
+
+        // Centro de la cara: sin cambios
+        assert_vec_eq(cube.bevel_normal(Vector3::new(0.0, 1.0, 0.0), up, 0.2), up);
+        // Ancho 0: aristas vivas
+        assert_vec_eq(cube.bevel_normal(Vector3::new(0.99, 1.0, 0.0), up, 0.0), up);
+
+        // Sobre la arista +X la normal es el promedio de las dos caras
+        let edge = cube.bevel_normal(Vector3::new(1.0, 1.0, 0.0), up, 0.2);
+        assert_vec_eq(edge, Vector3::new(1.0, 1.0, 0.0).normalized());
+
+        // A mitad del bisel, hacia -Z
+        let half = cube.bevel_normal(Vector3::new(0.0, 1.0, -0.9), up, 0.2);
+        assert_vec_eq(half, Vector3::new(0.0, 1.0, -0.5).normalized());
+    }
+
+    #[test]
+    fn hits_closer_than_epsilon_use_the_exit_face() {
+        let cube = test_cube();
+        let direction = Vector3::new(0.0, 0.0, -1.0);
+
+        let near = cube.ray_intersect(&Vector3::new(0.0, 0.0, 1.0 + 2.0 * HIT_EPSILON), &direction);
+        assert!(near.is_intersecting && !near.inside);
+        assert_vec_eq(near.normal, Vector3::new(0.0, 0.0, 1.0));
+
+        // Desde la propia superficie la entrada se descarta y el rayo sale por detrás
+        let touching =
+            cube.ray_intersect(&Vector3::new(0.0, 0.0, 1.0 + HIT_EPSILON * 0.5), &direction);
+        assert!(touching.is_intersecting && touching.inside);
+        assert!((touching.distance - 2.0).abs() < 1e-2);
+
+        let behind = cube.ray_intersect(
+            &Vector3::new(0.0, 0.0, -1.0 + HIT_EPSILON * 0.5),
+            &direction,
+        );
+        assert!(!behind.is_intersecting);
     }
 }
@@ -0,0 +1,123 @@
+// debug_ray.rs - Traces a single pixel through cast_ray and prints every bounce
+use crate::ao::AOBake;
+use crate::bvh::BVHNode;
+use crate::camera::Camera;
+use crate::cube::Cube;
+use crate::light::Light;
+use crate::ray_intersect::Intersect;
+use crate::scene::ObjectDef;
+use crate::snell::AIR_INDEX;
+use crate::textures::{TextureId, TextureManager};
+use crate::{RenderConfig, cast_ray, primary_ray_differential, primary_ray_direction};
+use raylib::prelude::*;
+
+/// Un rayo dentro del recorrido de `cast_ray`: lo que golpeó y el color que aportó
+pub struct TraceStep {
+    pub depth: u32,
+    pub direction: Vector3,
+    pub hit: Option<Intersect>,
+    pub color: Vector3,
+}
+
+impl TraceStep {
+    pub fn hit(depth: u32, direction: Vector3, intersect: &Intersect) -> Self {
+        TraceStep {
+            depth,
+            direction,
+            hit: Some(intersect.clone()),
+            color: Vector3::zero(),
+        }
+    }
+
+    pub fn miss(depth: u32, direction: Vector3, sky: Vector3) -> Self {
+        TraceStep {
+            depth,
+            direction,
+            hit: None,
+            color: sky,
+        }
+    }
+}
+
+fn format_vector(v: Vector3) -> String {
+    format!("({:.3}, {:.3}, {:.3})", v.x, v.y, v.z)
+}
+
+/// Traza el píxel (x, y) con el mismo `cast_ray` del render y devuelve cada rebote
+pub fn debug_ray(
+    x: i32,
+    y: i32,
+    bvh: &BVHNode,
+    objects: &[Cube],
+    camera: &Camera,
+    lights: &[Light],
+    texture_manager: &TextureManager,
+    config: &RenderConfig,
+    skybox_texture: Option<TextureId>,
+    ao_bake: Option<&AOBake>,
+) -> Vec<TraceStep> {
+    let direction = primary_ray_direction(x as f32, y as f32, camera, config);
+    let origin = camera.eye + direction * config.near_distance;
+    let mut steps = Vec::new();
+
+    cast_ray(
+        &origin,
+        &direction,
+        bvh,
+        objects,
+        lights,
+        0,
+        1.0,
+        AIR_INDEX,
+        primary_ray_differential(x as f32, y as f32, camera, config),
+        texture_manager,
+        skybox_texture,
+        config,
+        ao_bake,
+        Some(&mut steps),
+    );
+
+    steps
+}
+
+/// Imprime el recorrido sangrado por profundidad, con el material de cada objeto de la escena
+pub fn print_trace(x: i32, y: i32, steps: &[TraceStep], object_defs: &[ObjectDef]) {
+    println!("Ray trace for pixel ({}, {}):", x, y);
+
+    for step in steps {
+        let indent = "  ".repeat(step.depth as usize + 1);
+
+        match &step.hit {
+            Some(hit) => {
+                let material = hit
+                    .object_idx
+                    .and_then(|idx| object_defs.get(idx))
+                    .map_or("?", |def| def.material.as_str());
+                let object = hit
+                    .object_idx
+                    .map_or("?".to_string(), |idx| idx.to_string());
+
+                println!(
+                    "{}depth {}: hit object {} ({}) at distance {:.3}",
+                    indent, step.depth, object, material, hit.distance
+                );
+                println!(
+                    "{}  point {}, normal {}, uv ({:.3}, {:.3})",
+                    indent,
+                    format_vector(hit.point),
+                    format_vector(hit.normal),
+                    hit.u,
+                    hit.v
+                );
+                println!("{}  color {}", indent, format_vector(step.color));
+            }
+            None => println!(
+                "{}depth {}: sky in direction {}, color {}",
+                indent,
+                step.depth,
+                format_vector(step.direction),
+                format_vector(step.color)
+            ),
+        }
+    }
+}
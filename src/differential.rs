@@ -0,0 +1,147 @@
+// differential.rs - Ray differentials: how far apart neighbouring pixel rays land on a surface
+use raylib::prelude::*;
+
+/// Derivadas del origen y la dirección de un rayo respecto a x e y de pantalla
+/// (Igehy, "Tracing Ray Differentials"). Al llegar a una superficie dan el tamaño de la
+/// huella del píxel, que decide el ancho del filtro de textura.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayDifferential {
+    pub origin_dx: Vector3,
+    pub origin_dy: Vector3,
+    pub direction_dx: Vector3,
+    pub direction_dy: Vector3,
+}
+
+impl RayDifferential {
+    /// Rayo de cámara estenopeica: todos salen del ojo y solo cambia la dirección, que se
+    /// compara con la de los píxeles vecinos a la derecha y abajo
+    pub fn pinhole(direction: Vector3, right: Vector3, below: Vector3) -> Self {
+        RayDifferential {
+            origin_dx: Vector3::zero(),
+            origin_dy: Vector3::zero(),
+            direction_dx: right - direction,
+            direction_dy: below - direction,
+        }
+    }
+
+    /// Reduce la huella cuando el píxel se reparte entre varias muestras
+    pub fn scaled(self, factor: f32) -> Self {
+        RayDifferential {
+            origin_dx: self.origin_dx * factor,
+            origin_dy: self.origin_dy * factor,
+            direction_dx: self.direction_dx * factor,
+            direction_dy: self.direction_dy * factor,
+        }
+    }
+
+    /// Derivadas del punto de impacto a `distance` sobre un plano con normal `normal`
+    pub fn transfer(
+        &self,
+        direction: Vector3,
+        distance: f32,
+        normal: Vector3,
+    ) -> (Vector3, Vector3) {
+        let cosine = direction.dot(normal);
+        let along = |origin_d: Vector3, direction_d: Vector3| {
+            let offset = origin_d + direction_d * distance;
+            // Rayos casi paralelos al plano: la huella no tiene límite, así que no se corrige
+            if cosine.abs() < 1e-6 {
+                return offset;
+            }
+            offset - direction * (offset.dot(normal) / cosine)
+        };
+        (
+            along(self.origin_dx, self.direction_dx),
+            along(self.origin_dy, self.direction_dy),
+        )
+    }
+
+    /// Diferencial del rayo reflejado en una cara plana, que parte del impacto
+    pub fn reflect(&self, point_dx: Vector3, point_dy: Vector3, normal: Vector3) -> Self {
+        let mirror = |d: Vector3| d - normal * (2.0 * d.dot(normal));
+        RayDifferential {
+            origin_dx: point_dx,
+            origin_dy: point_dy,
+            direction_dx: mirror(self.direction_dx),
+            direction_dy: mirror(self.direction_dy),
+        }
+    }
+
+    /// Diferencial del rayo refractado de `incident` a `refracted` con la razón de índices
+    /// `eta` = n1 / n2; `normal` mira hacia el lado del rayo incidente
+    pub fn refract(
+        &self,
+        point_dx: Vector3,
+        point_dy: Vector3,
+        incident: Vector3,
+        refracted: Vector3,
+        normal: Vector3,
+        eta: f32,
+    ) -> Self {
+        let incident_cos = incident.dot(normal);
+        let refracted_cos = refracted.dot(normal);
+        let mu_rate = if refracted_cos.abs() < 1e-6 {
+            0.0
+        } else {
+            eta - eta * eta * incident_cos / refracted_cos
+        };
+        let bend = |d: Vector3| d * eta - normal * (mu_rate * d.dot(normal));
+        RayDifferential {
+            origin_dx: point_dx,
+            origin_dy: point_dy,
+            direction_dx: bend(self.direction_dx),
+            direction_dy: bend(self.direction_dy),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn differential() -> RayDifferential {
+        let forward = Vector3::new(0.0, 0.0, -1.0);
+        RayDifferential::pinhole(
+            forward,
+            Vector3::new(0.01, 0.0, -1.0),
+            Vector3::new(0.0, -0.01, -1.0),
+        )
+    }
+
+    #[test]
+    fn footprint_grows_with_distance_and_slant() {
+        let forward = Vector3::new(0.0, 0.0, -1.0);
+        let facing = Vector3::new(0.0, 0.0, 1.0);
+        let rays = differential();
+
+        let (near_dx, near_dy) = rays.transfer(forward, 2.0, facing);
+        let (far_dx, _) = rays.transfer(forward, 8.0, facing);
+        assert!((near_dx.x - 0.02).abs() < 1e-6 && near_dx.z.abs() < 1e-6);
+        assert!((near_dy.y + 0.02).abs() < 1e-6);
+        assert!((far_dx.length() - 4.0 * near_dx.length()).abs() < 1e-6);
+
+        // Un plano inclinado 60° estira la huella el doble a lo largo de la pendiente
+        let slanted = Vector3::new(3.0f32.sqrt() / 2.0, 0.0, 0.5);
+        let (slant_dx, _) = rays.transfer(forward, 2.0, slanted);
+        assert!(slant_dx.dot(slanted).abs() < 1e-6);
+        assert!((slant_dx.length() - 2.0 * near_dx.length()).abs() < 1e-4);
+    }
+
+    #[test]
+    fn mirror_keeps_spread_and_glass_narrows_it() {
+        let forward = Vector3::new(0.0, 0.0, -1.0);
+        let facing = Vector3::new(0.0, 0.0, 1.0);
+        let rays = differential();
+        let (point_dx, point_dy) = rays.transfer(forward, 1.0, facing);
+
+        let reflected = rays.reflect(point_dx, point_dy, facing);
+        assert_eq!(reflected.origin_dx, point_dx);
+        assert_eq!(reflected.direction_dx, rays.direction_dx);
+
+        // En incidencia normal el frente de rayos se abre 1/1.5 veces dentro del vidrio
+        let eta = 1.0 / 1.5;
+        let refracted = rays.refract(point_dx, point_dy, forward, forward, facing, eta);
+        assert!((refracted.direction_dx.x - 0.01 * eta).abs() < 1e-6);
+        assert!(refracted.direction_dx.z.abs() < 1e-6);
+    }
+}
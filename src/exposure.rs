@@ -0,0 +1,122 @@
+// exposure.rs - Automatic exposure from the average log luminance of the last frame
+use crate::color::luminance;
+use raylib::prelude::*;
+
+// Evita ln(0) en los píxeles negros sin que cuenten como infinitamente oscuros
+const LOG_DELTA: f32 = 1e-4;
+const MIN_EXPOSURE: f32 = 1.0 / 64.0;
+const MAX_EXPOSURE: f32 = 64.0;
+
+/// Exposición que se adapta poco a poco para que la luminancia media del frame
+/// quede en `key`, como el ojo al pasar de una cueva a una zona iluminada
+pub struct AutoExposure {
+    /// Luminancia media buscada tras la exposición: 0.18 es el gris medio
+    pub key: f32,
+    /// Fracción del camino hacia la exposición objetivo que se recorre en cada paso
+    pub adaptation: f32,
+    exposure: f32,
+    target: f32,
+}
+
+impl AutoExposure {
+    pub fn new(initial_exposure: f32) -> Self {
+        AutoExposure {
+            key: 0.18,
+            adaptation: 0.05,
+            exposure: initial_exposure,
+            target: initial_exposure,
+        }
+    }
+
+    pub fn exposure(&self) -> f32 {
+        self.exposure
+    }
+
+    /// Fija el objetivo a partir de un frame HDR recién renderizado
+    pub fn measure(&mut self, pixels: &[Vector3]) {
+        if let Some(average) = log_average_luminance(pixels) {
+            self.target = (self.key / average).clamp(MIN_EXPOSURE, MAX_EXPOSURE);
+        }
+    }
+
+    /// Avanza hacia el objetivo en escala logarítmica, para que aclarar y oscurecer
+    /// tarden lo mismo, y devuelve la exposición resultante
+    pub fn step(&mut self) -> f32 {
+        let current = self.exposure.ln();
+        let target = self.target.ln();
+        let next = current + (target - current) * self.adaptation.clamp(0.0, 1.0);
+
+        // Cerca del objetivo se ajusta del todo para que deje de cambiar
+        self.exposure = if (target - next).abs() < 1e-3 {
+            self.target
+        } else {
+            next.exp()
+        };
+        self.exposure
+    }
+
+    /// Salta directamente al objetivo, para renders de un solo frame
+    pub fn settle(&mut self) -> f32 {
+        self.exposure = self.target;
+        self.exposure
+    }
+}
+
+/// Media geométrica de la luminancia; los píxeles no finitos se ignoran
+pub fn log_average_luminance(pixels: &[Vector3]) -> Option<f32> {
+    let (sum, count) = pixels
+        .iter()
+        .map(|&p| luminance(p))
+        .filter(|l| l.is_finite())
+        .fold((0.0f64, 0usize), |(sum, count), l| {
+            (sum + (LOG_DELTA + l.max(0.0)).ln() as f64, count + 1)
+        });
+
+    (count > 0).then(|| (sum / count as f64).exp() as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gray(value: f32, count: usize) -> Vec<Vector3> {
+        vec![Vector3::new(value, value, value); count]
+    }
+
+    #[test]
+    fn log_average_is_the_geometric_mean() {
+        let mut pixels = gray(0.1, 8);
+        pixels.extend(gray(10.0, 8));
+        let average = log_average_luminance(&pixels).unwrap();
+        assert!((average - 1.0).abs() < 1e-3, "{}", average);
+
+        assert_eq!(log_average_luminance(&[]), None);
+    }
+
+    #[test]
+    fn exposure_brings_the_average_to_the_key() {
+        let mut auto = AutoExposure::new(1.0);
+        auto.measure(&gray(0.045, 16));
+        assert!((auto.settle() - 4.0).abs() < 0.05);
+
+        auto.measure(&gray(1.8, 16));
+        assert!((auto.settle() - 0.1).abs() < 1e-3);
+    }
+
+    #[test]
+    fn adaptation_is_gradual_and_converges() {
+        let mut auto = AutoExposure::new(1.0);
+        auto.measure(&gray(0.0045, 16));
+
+        let first = auto.step();
+        assert!(first > 1.0 && first < 2.0, "{}", first);
+
+        let mut previous = first;
+        for _ in 0..500 {
+            let exposure = auto.step();
+            assert!(exposure >= previous);
+            previous = exposure;
+        }
+        assert_eq!(previous, auto.settle());
+    }
+}
@@ -1,9 +1,43 @@
 use raylib::prelude::*;
 
+/// Datos auxiliares del impacto primario de un píxel
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AovSample {
+    /// Normal geométrica en el espacio del mundo; cero si el rayo no golpea nada
+    pub normal: Vector3,
+    /// Distancia desde el ojo a lo largo del rayo; infinita si no golpea nada
+    pub depth: f32,
+    /// Color difuso de la superficie (textura o color del material), sin iluminar
+    pub albedo: Vector3,
+}
+
+impl AovSample {
+    pub fn miss() -> Self {
+        AovSample {
+            normal: Vector3::zero(),
+            depth: f32::INFINITY,
+            albedo: Vector3::zero(),
+        }
+    }
+}
+
+/// Búferes auxiliares (AOV) en paralelo a la imagen, para composición y eliminación de ruido
+pub struct AovBuffers {
+    pub normal: Vec<Vector3>,
+    pub depth: Vec<f32>,
+    pub albedo: Vec<Vector3>,
+}
+
 pub struct Framebuffer {
     pub width: i32,
     pub height: i32,
     pub color_buffer: Image,
+    /// Radiancia lineal sin recortar, solo si se activó con `enable_hdr`
+    pub hdr_buffer: Option<Vec<Vector3>>,
+    /// Normal, profundidad y albedo por píxel, solo si se activaron con `enable_aovs`
+    pub aovs: Option<AovBuffers>,
+    /// Píxeles que el focus peaking tiñe al pasar a 8 bits; la radiancia no se toca
+    focus_mask: Option<Vec<bool>>,
     background_color: Color,
     current_color: Color,
 }
@@ -16,6 +50,9 @@ impl Framebuffer {
             width,
             height,
             color_buffer,
+            hdr_buffer: None,
+            aovs: None,
+            focus_mask: None,
             background_color,
             current_color: Color::WHITE,
         }
@@ -39,6 +76,83 @@ impl Framebuffer {
         }
     }
 
+    pub fn enable_hdr(&mut self) {
+        self.hdr_buffer = Some(vec![Vector3::zero(); (self.width * self.height) as usize]);
+    }
+
+    pub fn set_hdr_pixel(&mut self, x: i32, y: i32, value: Vector3) {
+        if let Some(buffer) = self.hdr_buffer.as_mut()
+            && x >= 0
+            && x < self.width
+            && y >= 0
+            && y < self.height
+        {
+            buffer[(y * self.width + x) as usize] = value;
+        }
+    }
+
+    pub fn enable_aovs(&mut self) {
+        let size = (self.width * self.height) as usize;
+        self.aovs = Some(AovBuffers {
+            normal: vec![Vector3::zero(); size],
+            depth: vec![f32::INFINITY; size],
+            albedo: vec![Vector3::zero(); size],
+        });
+    }
+
+    pub fn set_aov_pixel(&mut self, x: i32, y: i32, sample: AovSample) {
+        if let Some(aovs) = self.aovs.as_mut()
+            && x >= 0
+            && x < self.width
+            && y >= 0
+            && y < self.height
+        {
+            let index = (y * self.width + x) as usize;
+            aovs.normal[index] = sample.normal;
+            aovs.depth[index] = sample.depth;
+            aovs.albedo[index] = sample.albedo;
+        }
+    }
+
+    /// La máscara solo se crea con el primer píxel enfocado
+    pub fn set_in_focus(&mut self, x: i32, y: i32, in_focus: bool) {
+        if x < 0 || x >= self.width || y < 0 || y >= self.height {
+            return;
+        }
+        let size = (self.width * self.height) as usize;
+        let mask = match self.focus_mask.as_mut() {
+            Some(mask) => mask,
+            None if in_focus => self.focus_mask.insert(vec![false; size]),
+            None => return,
+        };
+        mask[(y * self.width + x) as usize] = in_focus;
+    }
+
+    pub fn is_in_focus(&self, x: i32, y: i32) -> bool {
+        self.focus_mask
+            .as_ref()
+            .is_some_and(|mask| mask[(y * self.width + x) as usize])
+    }
+
+    /// Anaglifo rojo/cian: conserva el rojo de esta imagen (ojo izquierdo) y toma el verde
+    /// y el azul de `right`, que debe tener el mismo tamaño
+    pub fn combine_anaglyph(&mut self, right: &mut Framebuffer) {
+        for y in 0..self.height.min(right.height) {
+            for x in 0..self.width.min(right.width) {
+                let left_color = self.color_buffer.get_color(x, y);
+                let right_color = right.color_buffer.get_color(x, y);
+                let color = Color::new(left_color.r, right_color.g, right_color.b, 255);
+                self.color_buffer.draw_pixel(x, y, color);
+            }
+        }
+
+        if let (Some(left), Some(right)) = (self.hdr_buffer.as_mut(), right.hdr_buffer.as_ref()) {
+            for (l, r) in left.iter_mut().zip(right) {
+                *l = Vector3::new(l.x, r.y, r.z);
+            }
+        }
+    }
+
     pub fn set_background_color(&mut self, color: Color) {
         self.background_color = color;
     }
@@ -48,10 +162,152 @@ impl Framebuffer {
     }
 
     pub fn swap_buffers(&self, d: &mut RaylibHandle, thread: &RaylibThread) {
+        self.swap_buffers_with_markers(d, thread, &[]);
+    }
+
+    /// Zona de una ventana de `screen_width` x `screen_height` donde se dibuja la imagen
+    pub fn viewport(&self, screen_width: i32, screen_height: i32) -> Viewport {
+        Viewport::fit(self.width, self.height, screen_width, screen_height)
+    }
+
+    /// Como `swap_buffers`, dibujando encima de la imagen los marcadores de depuración.
+    /// Los marcadores están en píxeles de la imagen y se escalan con ella.
+    pub fn swap_buffers_with_markers(
+        &self,
+        d: &mut RaylibHandle,
+        thread: &RaylibThread,
+        markers: &[ScreenMarker],
+    ) {
+        let viewport = self.viewport(d.get_screen_width(), d.get_screen_height());
         if let Ok(texture) = d.load_texture_from_image(thread, &self.color_buffer) {
             let mut d = d.begin_drawing(thread);
-            d.clear_background(self.background_color);
-            d.draw_texture(&texture, 0, 0, Color::WHITE);
+            d.clear_background(Color::BLACK);
+            let source = Rectangle::new(0.0, 0.0, self.width as f32, self.height as f32);
+            let dest = viewport.rect();
+            d.draw_texture_pro(&texture, source, dest, Vector2::zero(), 0.0, Color::WHITE);
+
+            for marker in markers {
+                let (x, y) = viewport.to_screen(marker.x as f32, marker.y as f32);
+                let radius = marker.radius * viewport.scale.max(1.0);
+                // El borde negro lo separa de fondos del mismo color
+                d.draw_circle(x, y, radius + 1.5, Color::BLACK);
+                d.draw_circle(x, y, radius, marker.color);
+            }
+        }
+    }
+}
+
+/// Rectángulo de la ventana que ocupa la imagen: se escala lo máximo posible sin
+/// deformarla y se centra, dejando bandas negras arriba y abajo o a los lados
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    /// Píxeles de pantalla por píxel de la imagen, igual en los dos ejes
+    pub scale: f32,
+    pub image_width: i32,
+    pub image_height: i32,
+}
+
+impl Viewport {
+    pub fn fit(image_width: i32, image_height: i32, screen_width: i32, screen_height: i32) -> Self {
+        let (w, h) = (image_width.max(1) as f32, image_height.max(1) as f32);
+        let scale = (screen_width as f32 / w)
+            .min(screen_height as f32 / h)
+            .max(0.0);
+        Viewport {
+            x: (screen_width as f32 - w * scale) * 0.5,
+            y: (screen_height as f32 - h * scale) * 0.5,
+            scale,
+            image_width,
+            image_height,
+        }
+    }
+
+    pub fn rect(&self) -> Rectangle {
+        Rectangle::new(
+            self.x,
+            self.y,
+            self.image_width as f32 * self.scale,
+            self.image_height as f32 * self.scale,
+        )
+    }
+
+    /// Punto de la imagen llevado a la ventana
+    pub fn to_screen(self, x: f32, y: f32) -> (i32, i32) {
+        (
+            (self.x + x * self.scale).round() as i32,
+            (self.y + y * self.scale).round() as i32,
+        )
+    }
+
+    /// Píxel de la imagen bajo un punto de la ventana; `None` si cae en las bandas
+    pub fn to_image(self, x: i32, y: i32) -> Option<(i32, i32)> {
+        if self.scale <= 0.0 {
+            return None;
         }
-    } 
+        let ix = ((x as f32 - self.x) / self.scale).floor() as i32;
+        let iy = ((y as f32 - self.y) / self.scale).floor() as i32;
+        ((0..self.image_width).contains(&ix) && (0..self.image_height).contains(&iy))
+            .then_some((ix, iy))
+    }
+}
+
+/// Círculo dibujado sobre la imagen ya trazada, sin afectar a los búferes
+#[derive(Debug, Clone, Copy)]
+pub struct ScreenMarker {
+    pub x: i32,
+    pub y: i32,
+    pub radius: f32,
+    pub color: Color,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wide_image_is_letterboxed_in_square_window() {
+        // 16:9 en una ventana 4:3: ocupa todo el ancho y deja bandas arriba y abajo
+        let viewport = Viewport::fit(160, 90, 800, 600);
+        let rect = viewport.rect();
+        assert_eq!(viewport.scale, 5.0);
+        assert_eq!((rect.x, rect.width), (0.0, 800.0));
+        assert_eq!((rect.y, rect.height), (75.0, 450.0));
+        assert_eq!(rect.width / rect.height, 160.0 / 90.0);
+
+        assert_eq!(viewport.to_image(400, 50), None);
+        assert_eq!(viewport.to_image(0, 75), Some((0, 0)));
+        assert_eq!(viewport.to_image(799, 524), Some((159, 89)));
+        assert_eq!(viewport.to_screen(80.0, 45.0), (400, 300));
+    }
+
+    #[test]
+    fn tall_image_is_pillarboxed() {
+        let viewport = Viewport::fit(90, 160, 800, 600);
+        let rect = viewport.rect();
+        assert_eq!((rect.y, rect.height), (0.0, 600.0));
+        assert_eq!(rect.width, 90.0 * 600.0 / 160.0);
+        assert_eq!(rect.x, (800.0 - rect.width) / 2.0);
+        assert_eq!(viewport.to_image(10, 300), None);
+
+        // Con el mismo tamaño que la ventana no hay escala ni bandas
+        let exact = Viewport::fit(800, 600, 800, 600);
+        assert_eq!((exact.x, exact.y, exact.scale), (0.0, 0.0, 1.0));
+        assert_eq!(exact.to_image(123, 456), Some((123, 456)));
+    }
+    #[test]
+    fn focus_mask_leaves_radiance_untouched() {
+        let mut framebuffer = Framebuffer::new(4, 2);
+        framebuffer.enable_hdr();
+        framebuffer.set_hdr_pixel(1, 1, Vector3::one());
+        framebuffer.set_in_focus(1, 1, true);
+
+        assert!(framebuffer.is_in_focus(1, 1));
+        assert!(!framebuffer.is_in_focus(0, 1));
+        assert_eq!(framebuffer.hdr_buffer.as_ref().unwrap()[5], Vector3::one());
+
+        framebuffer.set_in_focus(1, 1, false);
+        assert!(!framebuffer.is_in_focus(1, 1));
+    }
 }
@@ -0,0 +1,155 @@
+// hdr.rs - Radiance RGBE (.hdr) writer for the unclamped render buffer
+use raylib::prelude::*;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+// El formato RLE de Radiance solo admite anchos en este rango
+const MIN_RLE_WIDTH: i32 = 8;
+const MAX_RLE_WIDTH: i32 = 0x7fff;
+const MAX_RUN: usize = 128;
+
+pub fn is_hdr_path(path: &str) -> bool {
+    path.to_lowercase().ends_with(".hdr")
+}
+
+/// Codifica un color lineal como mantisa compartida por canal y exponente común
+pub fn to_rgbe(color: Vector3) -> [u8; 4] {
+    let r = color.x.max(0.0);
+    let g = color.y.max(0.0);
+    let b = color.z.max(0.0);
+    let v = r.max(g).max(b);
+
+    if !v.is_finite() || v < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+
+    // v = m * 2^e con m en [0.5, 1)
+    let mut e = v.log2().floor() as i32 + 1;
+    let mut m = v / 2.0_f32.powi(e);
+    if m >= 1.0 {
+        m *= 0.5;
+        e += 1;
+    } else if m < 0.5 {
+        m *= 2.0;
+        e -= 1;
+    }
+
+    let scale = m * 256.0 / v;
+    [
+        (r * scale) as u8,
+        (g * scale) as u8,
+        (b * scale) as u8,
+        (e + 128).clamp(0, 255) as u8,
+    ]
+}
+
+/// Escribe la línea con corridas literales por canal, sin comprimir repeticiones.
+/// Así ningún lector confunde la línea con el formato plano antiguo.
+fn write_rle_scanline(out: &mut impl Write, scanline: &[[u8; 4]]) -> std::io::Result<()> {
+    let width = scanline.len();
+    out.write_all(&[2, 2, (width >> 8) as u8, (width & 0xff) as u8])?;
+
+    for channel in 0..4 {
+        let values: Vec<u8> = scanline.iter().map(|p| p[channel]).collect();
+        for chunk in values.chunks(MAX_RUN) {
+            out.write_all(&[chunk.len() as u8])?;
+            out.write_all(chunk)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn write_hdr(path: &str, width: i32, height: i32, pixels: &[Vector3]) -> Result<(), String> {
+    if pixels.len() != (width * height) as usize {
+        return Err(format!(
+            "Expected {} HDR pixels, got {}",
+            width * height,
+            pixels.len()
+        ));
+    }
+
+    let file = File::create(path).map_err(|e| format!("Failed to create {}: {}", path, e))?;
+    let mut out = BufWriter::new(file);
+    encode(&mut out, width, pixels).map_err(|e| format!("Failed to write {}: {}", path, e))
+}
+
+fn encode(out: &mut impl Write, width: i32, pixels: &[Vector3]) -> std::io::Result<()> {
+    let height = pixels.len() / width.max(1) as usize;
+    write!(
+        out,
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        height, width
+    )?;
+
+    let use_rle = (MIN_RLE_WIDTH..=MAX_RLE_WIDTH).contains(&width);
+    for row in pixels.chunks(width.max(1) as usize) {
+        let scanline: Vec<[u8; 4]> = row.iter().map(|&p| to_rgbe(p)).collect();
+        if use_rle {
+            write_rle_scanline(out, &scanline)?;
+        } else {
+            for pixel in &scanline {
+                out.write_all(pixel)?;
+            }
+        }
+    }
+
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(rgbe: [u8; 4]) -> Vector3 {
+        if rgbe[3] == 0 {
+            return Vector3::zero();
+        }
+        let f = 2.0_f32.powi(rgbe[3] as i32 - 136);
+        Vector3::new(
+            (rgbe[0] as f32 + 0.5) * f,
+            (rgbe[1] as f32 + 0.5) * f,
+            (rgbe[2] as f32 + 0.5) * f,
+        )
+    }
+
+    #[test]
+    fn rgbe_keeps_values_above_one() {
+        assert_eq!(to_rgbe(Vector3::new(1.0, 1.0, 1.0)), [128, 128, 128, 129]);
+
+        for color in [
+            Vector3::new(6.5, 2.0, 0.25),
+            Vector3::new(0.01, 0.02, 0.005),
+            Vector3::new(300.0, 0.0, 40.0),
+        ] {
+            let decoded = decode(to_rgbe(color));
+            let max = color.x.max(color.y).max(color.z);
+            assert!((decoded.x - color.x).abs() <= max / 128.0);
+            assert!((decoded.y - color.y).abs() <= max / 128.0);
+            assert!((decoded.z - color.z).abs() <= max / 128.0);
+        }
+    }
+
+    #[test]
+    fn black_and_invalid_values_encode_as_zero() {
+        assert_eq!(to_rgbe(Vector3::zero()), [0, 0, 0, 0]);
+        assert_eq!(to_rgbe(Vector3::new(-1.0, -2.0, 0.0)), [0, 0, 0, 0]);
+        assert_eq!(to_rgbe(Vector3::new(f32::NAN, 0.0, 0.0)), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn scanlines_use_literal_runs() {
+        let width = 200;
+        let pixels = vec![Vector3::new(1.0, 0.5, 0.25); width * 2];
+        let mut out = Vec::new();
+        encode(&mut out, width as i32, &pixels).unwrap();
+
+        let header = "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 2 +X 200\n";
+        assert!(out.starts_with(header.as_bytes()));
+
+        // Por línea: 4 bytes de cabecera y, por canal, corridas de 128 y 72 con su byte de conteo
+        let body = &out[header.len()..];
+        assert_eq!(body.len(), 2 * (4 + 4 * (width + 2)));
+        assert_eq!(&body[..5], &[2, 2, 0, 200, 128]);
+    }
+}
@@ -0,0 +1,167 @@
+// instance.rs - Shared prototypes placed many times in the scene with only an offset each
+use crate::bvh::{AABB, BVHNode};
+use crate::cube::Cube;
+use crate::ray_intersect::Intersect;
+use raylib::prelude::*;
+use std::fmt;
+use std::sync::Arc;
+
+/// Grupo de cubos que se guarda una sola vez, con su material y su propio BVH, y se
+/// coloca en la escena tantas veces como instancias lo usen
+pub struct Prototype {
+    pub name: String,
+    pub cubes: Vec<Cube>,
+    pub bvh: BVHNode,
+}
+
+impl Prototype {
+    pub fn new(name: &str, cubes: Vec<Cube>, max_leaf_size: usize) -> Self {
+        let mut indices: Vec<usize> = (0..cubes.len()).collect();
+        let bvh = BVHNode::build_with_leaf_size(&cubes, &mut indices, max_leaf_size);
+        Prototype {
+            name: name.to_string(),
+            cubes,
+            bvh,
+        }
+    }
+}
+
+/// Una copia de un prototipo desplazada `offset`; solo ocupa el puntero y el vector
+#[derive(Clone)]
+pub struct Instance {
+    pub prototype: Arc<Prototype>,
+    pub offset: Vector3,
+}
+
+impl Instance {
+    pub fn bounds(&self) -> AABB {
+        let local = self.prototype.bvh.bounds();
+        AABB {
+            min: local.min + self.offset,
+            max: local.max + self.offset,
+        }
+    }
+
+    /// Impacto con el prototipo, trazando en su espacio y devolviendo el punto a la escena.
+    /// No apunta a ningún objeto de la escena (`object_idx` es `None`); el cubo del
+    /// prototipo queda en `instance`.
+    pub fn intersect_within(
+        &self,
+        ray_origin: &Vector3,
+        ray_direction: &Vector3,
+        inv_dir: &Vector3,
+        max_distance: f32,
+    ) -> Intersect {
+        let local_origin = *ray_origin - self.offset;
+        let mut hit = self.prototype.bvh.intersect_within(
+            &self.prototype.cubes,
+            &local_origin,
+            ray_direction,
+            inv_dir,
+            max_distance,
+        );
+        if hit.is_intersecting {
+            hit.point += self.offset;
+            hit.instance = hit.object_idx.map(|cube| InstanceHit {
+                prototype: Arc::clone(&self.prototype),
+                cube,
+                offset: self.offset,
+            });
+        }
+        hit.object_idx = None;
+        hit
+    }
+
+    pub fn occluded(
+        &self,
+        ray_origin: &Vector3,
+        ray_direction: &Vector3,
+        inv_dir: &Vector3,
+        max_distance: f32,
+        opaque_only: bool,
+    ) -> bool {
+        self.prototype.bvh.occluded(
+            &self.prototype.cubes,
+            &(*ray_origin - self.offset),
+            ray_direction,
+            inv_dir,
+            max_distance,
+            opaque_only,
+        )
+    }
+
+    pub fn contains_solid(&self, point: Vector3) -> bool {
+        self.prototype
+            .bvh
+            .contains_solid(&self.prototype.cubes, point - self.offset)
+    }
+}
+
+/// Cubo de un prototipo alcanzado a través de una instancia
+#[derive(Clone)]
+pub struct InstanceHit {
+    pub prototype: Arc<Prototype>,
+    pub cube: usize,
+    pub offset: Vector3,
+}
+
+impl InstanceHit {
+    pub fn cube(&self) -> &Cube {
+        &self.prototype.cubes[self.cube]
+    }
+}
+
+impl fmt::Debug for InstanceHit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} cube {} at {:?}",
+            self.prototype.name, self.cube, self.offset
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bvh::DEFAULT_LEAF_SIZE;
+    use crate::material::Material;
+
+    #[test]
+    fn instances_share_one_prototype() {
+        let prototype = Arc::new(Prototype::new(
+            "stem",
+            vec![
+                Cube::new(Vector3::zero(), 1.0, Material::black()),
+                Cube::new(Vector3::new(0.0, 1.0, 0.0), 1.0, Material::black()),
+            ],
+            DEFAULT_LEAF_SIZE,
+        ));
+        let instances: Vec<Instance> = (0..100)
+            .map(|i| Instance {
+                prototype: Arc::clone(&prototype),
+                offset: Vector3::new(i as f32 * 3.0, 0.0, 0.0),
+            })
+            .collect();
+        assert_eq!(Arc::strong_count(&prototype), 101);
+
+        let bvh = BVHNode::build_with_instances(&[], &mut [], &instances, DEFAULT_LEAF_SIZE);
+        let origin = Vector3::new(30.0, 1.0, 10.0);
+        let dir = Vector3::new(0.0, 0.0, -1.0);
+        let inv_dir = Vector3::new(1.0 / dir.x, 1.0 / dir.y, 1.0 / dir.z);
+
+        // El cubo superior de la instancia 10, ya en coordenadas de la escena
+        let hit = bvh.intersect(&[], &origin, &dir, &inv_dir);
+        assert!(hit.is_intersecting);
+        assert!((hit.distance - 9.5).abs() < 1e-4);
+        assert!((hit.point - Vector3::new(30.0, 1.0, 0.5)).length() < 1e-4);
+        assert_eq!(hit.object_idx, None);
+        let instance = hit.instance.as_ref().unwrap();
+        assert_eq!(instance.cube, 1);
+        assert_eq!(instance.offset, Vector3::new(30.0, 0.0, 0.0));
+
+        assert!(bvh.occluded(&[], &origin, &dir, &inv_dir, 20.0, false));
+        let between = Vector3::new(31.5, 1.0, 10.0);
+        assert!(!bvh.intersect(&[], &between, &dir, &inv_dir).is_intersecting);
+    }
+}
@@ -0,0 +1,133 @@
+// irradiance.rs - Spherical-harmonics irradiance probe precomputed from the sky
+use crate::color::non_negative;
+use raylib::prelude::*;
+use std::f32::consts::PI;
+
+// Direcciones del cielo que se promedian al construir la sonda
+pub const PROBE_SAMPLES: u32 = 4096;
+
+/// Luz del entorno comprimida en los 9 coeficientes de armónicos esféricos de orden 2
+/// (Ramamoorthi y Hanrahan). Basta para la iluminación difusa, que es muy suave, y se
+/// evalúa con unas pocas multiplicaciones en lugar de muestrear el cielo en cada impacto.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IrradianceProbe {
+    coefficients: [Vector3; 9],
+}
+
+impl IrradianceProbe {
+    /// Proyecta `environment` (radiancia por dirección) con `samples` direcciones repartidas
+    /// uniformemente sobre la esfera
+    pub fn from_environment(samples: u32, environment: impl Fn(Vector3) -> Vector3) -> Self {
+        let samples = samples.max(1);
+        let mut coefficients = [Vector3::zero(); 9];
+
+        for i in 0..samples {
+            let direction = fibonacci_direction(i, samples);
+            let radiance = environment(direction);
+            for (coefficient, basis) in coefficients.iter_mut().zip(sh_basis(direction)) {
+                *coefficient += radiance * basis;
+            }
+        }
+
+        // Cada muestra representa el mismo ángulo sólido, 4π / samples
+        let weight = 4.0 * PI / samples as f32;
+        IrradianceProbe {
+            coefficients: coefficients.map(|c| c * weight),
+        }
+    }
+
+    /// Radiancia media del entorno ponderada por el coseno con `normal` (irradiancia / π):
+    /// lo que el cielo aporta a una superficie difusa blanca sin ocluir
+    pub fn irradiance(&self, normal: Vector3) -> Vector3 {
+        // Convolución con el lóbulo del coseno, por banda: π, 2π/3 y π/4
+        const BAND: [f32; 9] = [
+            PI,
+            2.0 * PI / 3.0,
+            2.0 * PI / 3.0,
+            2.0 * PI / 3.0,
+            PI / 4.0,
+            PI / 4.0,
+            PI / 4.0,
+            PI / 4.0,
+            PI / 4.0,
+        ];
+
+        let mut sum = Vector3::zero();
+        for ((coefficient, basis), band) in self
+            .coefficients
+            .iter()
+            .zip(sh_basis(normal.normalized()))
+            .zip(BAND)
+        {
+            sum += *coefficient * (basis * band);
+        }
+
+        // El orden 2 puede oscilar por debajo de cero frente a cielos muy contrastados
+        non_negative(sum / PI)
+    }
+}
+
+/// Las 9 funciones base reales de armónicos esféricos hasta la banda 2
+fn sh_basis(d: Vector3) -> [f32; 9] {
+    [
+        0.282_095,
+        0.488_603 * d.y,
+        0.488_603 * d.z,
+        0.488_603 * d.x,
+        1.092_548 * d.x * d.y,
+        1.092_548 * d.y * d.z,
+        0.315_392 * (3.0 * d.z * d.z - 1.0),
+        1.092_548 * d.x * d.z,
+        0.546_274 * (d.x * d.x - d.y * d.y),
+    ]
+}
+
+/// Dirección `i` de `n` sobre la espiral de Fibonacci, casi uniforme en la esfera
+fn fibonacci_direction(i: u32, n: u32) -> Vector3 {
+    let golden_angle = PI * (3.0 - 5.0f32.sqrt());
+    let y = 1.0 - 2.0 * (i as f32 + 0.5) / n as f32;
+    let radius = (1.0 - y * y).max(0.0).sqrt();
+    let phi = golden_angle * i as f32;
+    Vector3::new(radius * phi.cos(), y, radius * phi.sin())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constant_sky_gives_constant_irradiance() {
+        let color = Vector3::new(0.4, 0.1, 0.2);
+        let probe = IrradianceProbe::from_environment(PROBE_SAMPLES, |_| color);
+
+        for normal in [
+            Vector3::new(0.0, 1.0, 0.0),
+            Vector3::new(0.0, -1.0, 0.0),
+            Vector3::new(1.0, 1.0, 0.0),
+            Vector3::new(0.0, 0.0, -1.0),
+        ] {
+            let irradiance = probe.irradiance(normal);
+            assert!((irradiance - color).length() < 1e-3, "{:?}", irradiance);
+        }
+    }
+
+    #[test]
+    fn bright_sky_lights_upward_faces() {
+        // Cielo blanco arriba y suelo negro: una cara hacia arriba recibe todo el cielo,
+        // una vertical la mitad y una hacia abajo casi nada
+        let probe = IrradianceProbe::from_environment(PROBE_SAMPLES, |d| {
+            if d.y > 0.0 {
+                Vector3::one()
+            } else {
+                Vector3::zero()
+            }
+        });
+
+        let up = probe.irradiance(Vector3::new(0.0, 1.0, 0.0)).x;
+        let side = probe.irradiance(Vector3::new(1.0, 0.0, 0.0)).x;
+        let down = probe.irradiance(Vector3::new(0.0, -1.0, 0.0)).x;
+        assert!((up - 1.0).abs() < 0.1, "{}", up);
+        assert!((side - 0.5).abs() < 0.02, "{}", side);
+        assert!(down < 0.1, "{}", down);
+    }
+}
@@ -0,0 +1,91 @@
+// json_scene.rs - JSON scene format, enabled with the `json-scene` feature
+use crate::scene::Scene;
+
+/// Serializa `Vector3` como un arreglo `[x, y, z]`
+pub mod vec3 {
+    use raylib::prelude::Vector3;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(v: &Vector3, serializer: S) -> Result<S::Ok, S::Error> {
+        [v.x, v.y, v.z].serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vector3, D::Error> {
+        let [x, y, z] = <[f32; 3]>::deserialize(deserializer)?;
+        Ok(Vector3::new(x, y, z))
+    }
+}
+
+impl Scene {
+    pub fn parse_json(contents: &str) -> Result<Scene, String> {
+        let scene: Scene =
+            serde_json::from_str(contents).map_err(|e| format!("Invalid JSON scene: {}", e))?;
+        scene.validate()?;
+        for warning in scene.warnings() {
+            eprintln!("Warning: {}", warning);
+        }
+        Ok(scene)
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| format!("Failed to serialize scene: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"{
+        "materials": {
+            "obsidian": {
+                "diffuse": [0.15, 0.1, 0.2],
+                "albedo": [0.9, 0.1],
+                "specular": 90.0,
+                "reflectivity": 0.1,
+                "transparency": 0.0,
+                "refractive_index": 0.0,
+                "texture": "assets/obsidian.png",
+                "emission": [0.0, 0.0, 0.0],
+                "emission_strength": 0.0
+            }
+        },
+        "objects": [
+            { "center": [0.0, -2.0, 0.0], "size": [0.5, 0.5, 0.5], "material": "obsidian" },
+            { "center": [1.0, 0.0, 0.0], "size": [1.0, 2.0, 1.0], "material": "obsidian" }
+        ],
+        "lights": [
+            { "position": [5.0, 8.0, 5.0], "color": [1.0, 0.7, 0.5], "intensity": 1.3 }
+        ],
+        "camera": { "eye": [0.0, 2.0, 8.0], "center": [0.0, 0.0, 0.0], "up": [0.0, 1.0, 0.0] },
+        "skybox": "assets/nether_skybox.png"
+    }"#;
+
+    #[test]
+    fn sample_scene_round_trips() {
+        let scene = Scene::parse_json(SAMPLE).unwrap();
+
+        assert_eq!(scene.objects.len(), 2);
+        assert_eq!(
+            scene.objects[1].size,
+            raylib::prelude::Vector3::new(1.0, 2.0, 1.0)
+        );
+        assert_eq!(scene.lights[0].intensity, 1.3);
+        assert_eq!(scene.materials["obsidian"].normal_map_id, None);
+        assert_eq!(scene.skybox.as_deref(), Some("assets/nether_skybox.png"));
+
+        let json = scene.to_json().unwrap();
+        let reloaded = Scene::parse_json(&json).unwrap();
+        assert_eq!(reloaded.to_json().unwrap(), json);
+        assert_eq!(reloaded.to_text(), scene.to_text());
+    }
+
+    #[test]
+    fn unknown_material_is_rejected() {
+        let broken = SAMPLE.replace(
+            r#""size": [0.5, 0.5, 0.5], "material": "obsidian""#,
+            r#""size": [0.5, 0.5, 0.5], "material": "glass""#,
+        );
+        assert!(Scene::parse_json(&broken).is_err());
+    }
+}
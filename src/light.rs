@@ -1,10 +1,104 @@
 // light.rs
+use crate::color::saturate;
+use crate::cube::Cube;
 use raylib::prelude::*;
 
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "json-scene", derive(serde::Serialize, serde::Deserialize))]
 pub struct Light {
+    #[cfg_attr(feature = "json-scene", serde(with = "crate::json_scene::vec3"))]
     pub position: Vector3,
+    #[cfg_attr(feature = "json-scene", serde(with = "crate::json_scene::vec3"))]
     pub color: Vector3,
     pub intensity: f32,
+    /// Si es falso, la luz no lanza rayos de sombra e ilumina con intensidad completa
+    #[cfg_attr(feature = "json-scene", serde(default = "default_casts_shadows"))]
+    pub casts_shadows: bool,
+    /// Radio de la esfera virtual de la luz; mayor que 0 suaviza los bordes de las sombras
+    #[cfg_attr(feature = "json-scene", serde(default))]
+    pub radius: f32,
+    /// Variación de la intensidad con el tiempo; `None` la deja constante
+    #[cfg_attr(feature = "json-scene", serde(default))]
+    pub flicker: Option<Flicker>,
+}
+
+/// Forma de la oscilación de una luz que parpadea
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "json-scene",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum FlickerShape {
+    /// Pulso suave y regular
+    Sine,
+    /// Ruido suavizado, como una llama
+    Noise,
+}
+
+impl FlickerShape {
+    pub fn name(self) -> &'static str {
+        match self {
+            FlickerShape::Sine => "sine",
+            FlickerShape::Noise => "noise",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "sine" => Some(FlickerShape::Sine),
+            "noise" => Some(FlickerShape::Noise),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "json-scene", derive(serde::Serialize, serde::Deserialize))]
+pub struct Flicker {
+    pub shape: FlickerShape,
+    /// Fracción de la intensidad que oscila: 0.2 la mueve entre el 80 % y el 120 %
+    pub amount: f32,
+    /// Ciclos (o cambios de ruido) por segundo
+    pub frequency: f32,
+}
+
+impl Flicker {
+    /// Factor que multiplica la intensidad en el instante `time`, nunca negativo
+    pub fn factor(&self, time: f32) -> f32 {
+        let t = time * self.frequency;
+        let wave = match self.shape {
+            FlickerShape::Sine => (2.0 * std::f32::consts::PI * t).sin(),
+            FlickerShape::Noise => value_noise(t),
+        };
+        (1.0 + self.amount * wave).max(0.0)
+    }
+}
+
+/// Ruido de valor 1D en [-1, 1]: valores pseudoaleatorios en los enteros interpolados con smoothstep
+fn value_noise(t: f32) -> f32 {
+    let hash = |i: i32| {
+        let mut x = (i as u32).wrapping_mul(0x9e37_79b9);
+        x ^= x >> 16;
+        x = x.wrapping_mul(0x85eb_ca6b);
+        x ^= x >> 13;
+        x as f32 / u32::MAX as f32 * 2.0 - 1.0
+    };
+
+    let i = t.floor();
+    let f = t - i;
+    let s = f * f * (3.0 - 2.0 * f);
+    let a = hash(i as i32);
+    let b = hash(i as i32 + 1);
+    a + (b - a) * s
+}
+
+// Intensidad desde la que se sube una luz apagada con `scale_intensity`
+const MIN_ADJUSTED_INTENSITY: f32 = 0.05;
+
+#[cfg(feature = "json-scene")]
+fn default_casts_shadows() -> bool {
+    true
 }
 
 impl Light {
@@ -13,6 +107,183 @@ impl Light {
             position,
             color,
             intensity,
+            casts_shadows: true,
+            radius: 0.0,
+            flicker: None,
+        }
+    }
+
+    /// Copia de la luz con la intensidad que le corresponde en el instante `time`
+    pub fn at_time(&self, time: f32) -> Light {
+        let mut light = self.clone();
+        if let Some(flicker) = &self.flicker {
+            light.intensity *= flicker.factor(time);
+        }
+        light
+    }
+
+    /// Multiplica la intensidad; una luz apagada arranca desde un mínimo para poder encenderla
+    pub fn scale_intensity(&mut self, factor: f32) {
+        self.intensity = (self.intensity.max(MIN_ADJUSTED_INTENSITY) * factor).min(1000.0);
+    }
+
+    /// Suma `delta` a un canal del color (0 = rojo, 1 = verde, 2 = azul), dentro de [0, 1]
+    pub fn adjust_channel(&mut self, channel: usize, delta: f32) {
+        let value = match channel {
+            0 => &mut self.color.x,
+            1 => &mut self.color.y,
+            _ => &mut self.color.z,
+        };
+        *value = (*value + delta).clamp(0.0, 1.0);
+    }
+
+    /// Color ya escalado por la intensidad, lo que llega a una superficie sin sombra
+    pub fn radiance(&self) -> Vector3 {
+        self.color * self.intensity
+    }
+
+    /// Luz con el color de un cuerpo negro a `kelvin` grados: ~1900 K es una vela,
+    /// 6500 K es blanco neutro y por encima se vuelve azulada
+    pub fn from_temperature(position: Vector3, kelvin: f32, intensity: f32) -> Self {
+        Light::new(position, kelvin_to_rgb(kelvin), intensity)
+    }
+}
+
+/// Aproximación del color de un cuerpo negro (Tanner Helland), válida de 1000 K a 40000 K.
+/// Devuelve componentes en [0, 1] con el canal dominante a 1.
+pub fn kelvin_to_rgb(kelvin: f32) -> Vector3 {
+    let t = kelvin.clamp(1000.0, 40000.0) / 100.0;
+
+    let red = if t <= 66.0 {
+        255.0
+    } else {
+        329.698_73 * (t - 60.0).powf(-0.133_204_76)
+    };
+
+    let green = if t <= 66.0 {
+        99.470_8 * t.ln() - 161.119_57
+    } else {
+        288.122_16 * (t - 60.0).powf(-0.075_514_85)
+    };
+
+    let blue = if t >= 66.0 {
+        255.0
+    } else if t <= 19.0 {
+        0.0
+    } else {
+        138.517_73 * (t - 10.0).ln() - 305.044_8
+    };
+
+    saturate(Vector3::new(red, green, blue) / 255.0)
+}
+
+/// Una luz puntual en el centro de cada objeto visible con emisión, con el color de su
+/// brillo y el doble de su intensidad. Solo depende de los objetos, así que basta con
+/// recalcularla cuando estos cambian.
+pub fn derive_emissive_lights(objects: &[Cube]) -> Vec<Light> {
+    objects
+        .iter()
+        .filter(|obj| obj.enabled && obj.material.emission_strength > 0.0)
+        .map(|obj| {
+            let center = (obj.min_bounds + obj.max_bounds) * 0.5;
+            Light::new(
+                center,
+                obj.material.emission,
+                obj.material.emission_strength * 2.0,
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::{Material, MaterialBuilder};
+
+    #[test]
+    fn warm_temperatures_are_red_and_cool_ones_blue() {
+        let candle = kelvin_to_rgb(1900.0);
+        assert_eq!(candle.x, 1.0);
+        assert!(candle.y < 0.6 && candle.z < 0.3);
+
+        let sky = kelvin_to_rgb(12000.0);
+        assert_eq!(sky.z, 1.0);
+        assert!(sky.x < sky.z);
+    }
+
+    #[test]
+    fn daylight_is_close_to_white() {
+        let white = kelvin_to_rgb(6600.0);
+        assert!(white.x > 0.95 && white.y > 0.95 && white.z > 0.95);
+
+        let light = Light::from_temperature(Vector3::zero(), 6600.0, 2.0);
+        assert_eq!(light.color, white);
+        assert_eq!(light.intensity, 2.0);
+        assert_eq!(light.radiance(), white * 2.0);
+    }
+
+    #[test]
+    fn visible_emissive_objects_become_lights() {
+        let glow = MaterialBuilder::new()
+            .emission(Vector3::new(1.0, 0.5, 0.2), 1.5)
+            .build();
+        let mut hidden = Cube::new(Vector3::new(4.0, 0.0, 0.0), 1.0, glow.clone());
+        hidden.enabled = false;
+        let objects = vec![
+            Cube::new(Vector3::zero(), 1.0, Material::black()),
+            Cube::new(Vector3::new(0.0, 2.0, 0.0), 1.0, glow),
+            hidden,
+        ];
+
+        let lights = derive_emissive_lights(&objects);
+        assert_eq!(lights.len(), 1);
+        assert_eq!(lights[0].position, Vector3::new(0.0, 2.0, 0.0));
+        assert_eq!(lights[0].color, Vector3::new(1.0, 0.5, 0.2));
+        assert_eq!(lights[0].intensity, 3.0);
+    }
+
+    #[test]
+    fn adjustments_stay_in_range() {
+        let mut light = Light::new(Vector3::zero(), Vector3::new(1.0, 0.5, 0.0), 2.0);
+        light.scale_intensity(1.25);
+        assert_eq!(light.intensity, 2.5);
+
+        // Una luz apagada vuelve a encenderse al subirla
+        light.intensity = 0.0;
+        light.scale_intensity(1.25);
+        assert!(light.intensity > 0.0);
+
+        light.adjust_channel(0, 0.1);
+        light.adjust_channel(1, 0.1);
+        light.adjust_channel(2, -0.1);
+        assert_eq!(light.color, Vector3::new(1.0, 0.6, 0.0));
+    }
+
+    #[test]
+    fn flicker_modulates_intensity_around_the_base() {
+        let mut light = Light::new(Vector3::zero(), Vector3::one(), 2.0);
+        assert_eq!(light.at_time(0.37).intensity, 2.0);
+
+        light.flicker = Some(Flicker {
+            shape: FlickerShape::Sine,
+            amount: 0.25,
+            frequency: 1.0,
+        });
+        assert!((light.at_time(0.25).intensity - 2.5).abs() < 1e-5);
+        assert!((light.at_time(0.75).intensity - 1.5).abs() < 1e-5);
+
+        light.flicker = Some(Flicker {
+            shape: FlickerShape::Noise,
+            amount: 0.25,
+            frequency: 8.0,
+        });
+        for i in 0..200 {
+            let intensity = light.at_time(i as f32 * 0.013).intensity;
+            assert!((1.5..=2.5).contains(&intensity), "{}", intensity);
         }
+        // Continuo: dos instantes muy cercanos casi no cambian
+        let a = light.at_time(1.0).intensity;
+        let b = light.at_time(1.001).intensity;
+        assert!((a - b).abs() < 0.05);
     }
 }
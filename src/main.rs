@@ -2,29 +2,63 @@
 #![allow(dead_code)]
 
 use raylib::prelude::*;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::f32::consts::PI;
 use std::thread;
 
+mod animation;
+mod ao;
+mod axis;
+mod bench;
 mod bvh;
 mod camera;
+mod cli;
+mod color;
 mod cube;
+mod debug_ray;
+mod denoise;
+mod differential;
+mod exposure;
 mod framebuffer;
+mod hdr;
+mod instance;
+mod irradiance;
+#[cfg(feature = "json-scene")]
+mod json_scene;
 mod light;
 mod material;
+mod noise;
 mod ray_intersect;
+mod sampling;
+mod scene;
+mod settings;
 mod snell;
+mod sphere;
+mod stats;
 mod textures;
 
+use animation::Animator;
+use ao::{AOBake, AmbientOcclusion};
+use axis::UpAxis;
 use bvh::BVHNode;
 use camera::Camera;
+use color::{clamp_luminance, vector3_to_color};
 use cube::Cube;
-use framebuffer::Framebuffer;
-use light::Light;
-use material::{Material, vector3_to_color};
-use ray_intersect::{Intersect, RayIntersect};
-use snell::{reflect, refract};
-use textures::TextureManager;
+use debug_ray::TraceStep;
+use denoise::Denoiser;
+use differential::RayDifferential;
+use exposure::AutoExposure;
+use framebuffer::{AovSample, Framebuffer, ScreenMarker};
+use irradiance::{IrradianceProbe, PROBE_SAMPLES};
+use light::{Light, derive_emissive_lights};
+use material::{Material, MaterialBuilder, anisotropic_highlight, clamp_bounce_weights};
+use ray_intersect::{Intersect, RayIntersect, SHADOW_LIGHT_BIAS};
+use sampling::SamplePattern;
+use scene::{CameraSetup, ObjectDef, RenderSetup, Scene, load_scene_files, load_scene_from_file};
+use settings::{PROCEDURAL_SKY, SETTINGS_FILE, ViewSettings};
+use snell::{AIR_INDEX, reflect, refract_between};
+use stats::SceneStats;
+use textures::{TextureId, TextureManager};
 
 pub enum SceneObject {
     Cube(Cube),
@@ -38,89 +72,45 @@ impl RayIntersect for SceneObject {
     }
 }
 
-fn load_scene_from_file(
-    filepath: &str,
-    materials: &std::collections::HashMap<String, Material>,
-) -> Result<Vec<Cube>, String> {
-    let contents = std::fs::read_to_string(filepath)
-        .map_err(|e| format!("Failed to read scene file '{}': {}", filepath, e))?;
-
-    let mut cubes = Vec::new();
-
-    for (line_num, line) in contents.lines().enumerate() {
-        let trimmed = line.trim();
-
-        if trimmed.is_empty() || trimmed.starts_with('#') {
-            continue;
+/// Cielo en la dirección `dir` de la escena; el skybox y el degradado se orientan con
+/// `config.up_axis`, de modo que el horizonte queda horizontal también en escenas Z arriba.
+/// Con `config.sky_blend` se mezcla con un segundo skybox según su peso actual.
+fn procedural_sky(
+    dir: Vector3,
+    texture_manager: &TextureManager,
+    skybox_texture: Option<TextureId>,
+    config: &RenderConfig,
+) -> Vector3 {
+    let dir = config.up_axis.to_y_up(dir);
+    match config.sky_blend {
+        Some((other, weight)) if weight >= 1.0 => sky_color(dir, texture_manager, Some(other)),
+        Some((other, weight)) if weight > 0.0 => {
+            let base = sky_color(dir, texture_manager, skybox_texture);
+            let blended = sky_color(dir, texture_manager, Some(other));
+            base * (1.0 - weight) + blended * weight
         }
-
-        let parts: Vec<&str> = trimmed.split_whitespace().collect();
-
-        if parts.len() != 5 {
-            return Err(format!(
-                "Line {}: Expected 5 parameters (x y z size material), got {}",
-                line_num + 1,
-                parts.len()
-            ));
-        }
-
-        let x = parts[0]
-            .parse::<f32>()
-            .map_err(|_| format!("Line {}: Invalid x coordinate '{}'", line_num + 1, parts[0]))?;
-        let y = parts[1]
-            .parse::<f32>()
-            .map_err(|_| format!("Line {}: Invalid y coordinate '{}'", line_num + 1, parts[1]))?;
-        let z = parts[2]
-            .parse::<f32>()
-            .map_err(|_| format!("Line {}: Invalid z coordinate '{}'", line_num + 1, parts[2]))?;
-        let size = parts[3]
-            .parse::<f32>()
-            .map_err(|_| format!("Line {}: Invalid size '{}'", line_num + 1, parts[3]))?;
-        let material_name = parts[4];
-
-        let material = materials.get(material_name).ok_or_else(|| {
-            format!(
-                "Line {}: Unknown material '{}'. Available: {}",
-                line_num + 1,
-                material_name,
-                materials
-                    .keys()
-                    .map(|s| s.as_str())
-                    .collect::<Vec<_>>()
-                    .join(", ")
-            )
-        })?;
-
-        cubes.push(Cube::new(Vector3::new(x, y, z), size, material.clone()));
+        _ => sky_color(dir, texture_manager, skybox_texture),
     }
-
-    Ok(cubes)
 }
 
-fn procedural_sky(
+/// Un solo cielo en la dirección `dir`, ya con Y arriba: el skybox si se puede muestrear y,
+/// si no, el degradado
+fn sky_color(
     dir: Vector3,
     texture_manager: &TextureManager,
-    skybox_texture: Option<&str>,
+    skybox_texture: Option<TextureId>,
 ) -> Vector3 {
-    if let Some(skybox_path) = skybox_texture {
-        if let Some(texture) = texture_manager.get_texture(skybox_path) {
-            let d = dir.normalized();
-
-            let theta = (-d.x).atan2(-d.z);
-            let phi = d.y.asin();
-
-            let u = 0.5 + theta / (2.0 * PI);
-            let v = 0.5 - phi / PI;
+    if let Some(skybox) = skybox_texture {
+        let d = dir.normalized();
 
-            let u_clamped = u.clamp(0.0, 0.9999);
-            let v_clamped = v.clamp(0.0, 0.9999);
+        let theta = (-d.x).atan2(-d.z);
+        let phi = d.y.asin();
 
-            let width = texture.width() as f32;
-            let height = texture.height() as f32;
-            let tx = (u_clamped * width) as u32;
-            let ty = (v_clamped * height) as u32;
+        let u = 0.5 + theta / (2.0 * PI);
+        let v = 0.5 - phi / PI;
 
-            return texture_manager.get_pixel_color(skybox_path, tx, ty);
+        if let Some(color) = texture_manager.sample(skybox, u, v) {
+            return color;
         }
     }
 
@@ -142,20 +132,74 @@ fn procedural_sky(
     }
 }
 
-fn cast_shadow(intersect: &Intersect, light: &Light, bvh: &BVHNode, objects: &[Cube]) -> f32 {
-    let light_dir = (light.position - intersect.point).normalized();
-    let shadow_origin = intersect.point + intersect.normal * 1e-4;
-    let inv_dir = Vector3::new(1.0 / light_dir.x, 1.0 / light_dir.y, 1.0 / light_dir.z);
+/// Punto `i` de `samples` repartidos de forma determinista dentro de una esfera de radio 1
+fn light_sample_offset(i: u32, samples: u32) -> Vector3 {
+    let golden_angle = PI * (3.0 - 5.0_f32.sqrt());
+    let t = (i as f32 + 0.5) / samples as f32;
+    let z = 1.0 - 2.0 * t;
+    let ring = (1.0 - z * z).max(0.0).sqrt();
+    let phi = i as f32 * golden_angle;
+    Vector3::new(ring * phi.cos(), ring * phi.sin(), z) * t.cbrt()
+}
+
+/// Oclusión hacia la luz; con radio mayor que 0 promedia varios rayos hacia puntos de su esfera.
+/// Las luces puntuales pueden promediar rayos con la dirección ligeramente desviada
+/// (`shadow_jitter`), que suaviza el borde de la sombra sin darle tamaño a la luz.
+fn cast_shadow(
+    intersect: &Intersect,
+    light: &Light,
+    bvh: &BVHNode,
+    objects: &[Cube],
+    config: &RenderConfig,
+) -> f32 {
+    let jitter = light.radius <= 0.0 && config.shadow_jitter > 0.0;
+    let samples = if light.radius > 0.0 {
+        config.shadow_samples.max(1)
+    } else if jitter {
+        config.shadow_jitter_samples.max(1)
+    } else {
+        1
+    };
+    let mut occluded = 0;
+
+    for i in 0..samples {
+        let target = if light.radius > 0.0 {
+            light.position + light_sample_offset(i, samples) * light.radius
+        } else {
+            light.position
+        };
 
-    let shadow_hit = bvh.intersect(objects, &shadow_origin, &light_dir, &inv_dir);
+        let mut light_dir = (target - intersect.point).normalized();
+        if jitter && samples > 1 {
+            light_dir =
+                (light_dir + light_sample_offset(i, samples) * config.shadow_jitter).normalized();
+        }
+        let inv_dir = Vector3::new(1.0 / light_dir.x, 1.0 / light_dir.y, 1.0 / light_dir.z);
+        let light_distance = (target - intersect.point).length() - SHADOW_LIGHT_BIAS;
 
-    if shadow_hit.is_intersecting {
-        let light_distance = (light.position - intersect.point).length();
-        if shadow_hit.distance < light_distance {
-            return 0.7;
+        if bvh.occluded(
+            objects,
+            &intersect.shadow_origin(light_dir),
+            &light_dir,
+            &inv_dir,
+            light_distance,
+            !config.transparent_shadows,
+        ) {
+            occluded += 1;
         }
     }
-    0.0
+
+    0.7 * occluded as f32 / samples as f32
+}
+
+/// Cubo alcanzado y cuánto está desplazado respecto a su propio espacio: los objetos de la
+/// escena están en su sitio y las instancias guardan el cubo en el prototipo compartido
+fn hit_cube<'a>(intersect: &'a Intersect, objects: &'a [Cube]) -> Option<(&'a Cube, Vector3)> {
+    match (&intersect.instance, intersect.object_idx) {
+        (Some(instance), _) => Some((instance.cube(), instance.offset)),
+        (None, Some(idx)) => Some((&objects[idx], Vector3::zero())),
+        (None, None) => None,
+    }
 }
 
 const ORIGIN_BIAS: f32 = 1e-4;
@@ -176,30 +220,106 @@ pub fn cast_ray(
     objects: &[Cube],
     lights: &[Light],
     depth: u32,
+    throughput: f32,
+    medium_index: f32,
+    differential: Option<RayDifferential>,
     texture_manager: &TextureManager,
-    skybox_texture: Option<&str>,
+    skybox_texture: Option<TextureId>,
+    config: &RenderConfig,
+    ao_bake: Option<&AOBake>,
+    mut trace: Option<&mut Vec<TraceStep>>,
 ) -> Vector3 {
-    if depth > 2 {
-        return procedural_sky(*ray_direction, texture_manager, skybox_texture);
-    }
-
     let inv_dir = Vector3::new(
         1.0 / ray_direction.x,
         1.0 / ray_direction.y,
         1.0 / ray_direction.z,
     );
 
-    let intersect = bvh.intersect(objects, ray_origin, ray_direction, &inv_dir);
+    let intersect = bvh.intersect_within(
+        objects,
+        ray_origin,
+        ray_direction,
+        &inv_dir,
+        config.max_ray_distance,
+    );
+
+    // Pasado el límite de rebotes ya no se sombrea, pero un emisor alcanzado devuelve su
+    // brillo en lugar del cielo, para que se vea en los reflejos y a través del vidrio
+    if depth > 2 && intersect.is_intersecting && intersect.material.emission_strength > 0.01 {
+        let glow = intersect
+            .material
+            .emitted(surface_color(&intersect, texture_manager, None));
+        let glow = clamp_luminance(glow, config.max_luminance);
+        if let Some(steps) = trace {
+            let mut step = TraceStep::hit(depth, *ray_direction, &intersect);
+            step.color = glow;
+            steps.push(step);
+        }
+        return glow;
+    }
+
+    if depth > 2 || !intersect.is_intersecting {
+        let sky = config.background.unwrap_or_else(|| {
+            procedural_sky(*ray_direction, texture_manager, skybox_texture, config)
+        });
+        if let Some(steps) = trace {
+            steps.push(TraceStep::miss(depth, *ray_direction, sky));
+        }
+        return sky;
+    }
+
+    // El color se completa al final, después de los rebotes que cuelgan de este paso
+    let trace_idx = trace.as_deref_mut().map(|steps| {
+        steps.push(TraceStep::hit(depth, *ray_direction, &intersect));
+        steps.len() - 1
+    });
 
-    if !intersect.is_intersecting {
-        return procedural_sky(*ray_direction, texture_manager, skybox_texture);
+    // El contorno tapa el sombreado, así que no hace falta calcular la iluminación
+    if intersect.material.is_on_edge(intersect.u, intersect.v) {
+        let color = intersect.material.edge_color;
+        if let (Some(steps), Some(idx)) = (trace, trace_idx) {
+            steps[idx].color = color;
+        }
+        return color;
     }
 
     let view_direction = (*ray_origin - intersect.point).normalized();
-    let normal = intersect.normal;
+    let cube = hit_cube(&intersect, objects);
+    let normal = match cube {
+        Some((cube, offset)) if intersect.material.bevel > 0.0 => {
+            // El bisel se calcula sobre la normal exterior y se invierte para los impactos interiores
+            let beveled = cube.bevel_normal(
+                intersect.point - offset,
+                intersect.outward_normal(),
+                intersect.material.bevel,
+            );
+            if intersect.inside { -beveled } else { beveled }
+        }
+        _ => intersect.normal,
+    };
+    let normal = intersect.material.facing_normal(normal, *ray_direction);
+
+    // Cuánto se desplaza el impacto hacia los píxeles vecinos, en la cara geométrica
+    let point_differential = differential
+        .map(|rays| rays.transfer(*ray_direction, intersect.distance, intersect.normal));
+    let uv_footprint = match (point_differential, cube) {
+        (Some((point_dx, point_dy)), Some((cube, offset))) => Some(cube.uv_footprint(
+            intersect.point - offset,
+            intersect.normal,
+            point_dx,
+            point_dy,
+        )),
+        _ => None,
+    };
 
     let mut total_diffuse = Vector3::zero();
     let mut total_specular = Vector3::zero();
+    // El BRDF de Lambert es albedo / π; el modo heredado omite la división
+    let lambert_norm = if config.energy_conserving {
+        1.0 / PI
+    } else {
+        1.0
+    };
 
     for light in lights {
         let light_direction = (light.position - intersect.point).normalized();
@@ -210,44 +330,94 @@ pub fn cast_ray(
             continue;
         }
 
-        let shadow_intensity = cast_shadow(&intersect, light, bvh, objects);
-        let light_intensity = light.intensity * (1.0 - shadow_intensity);
-        let final_diffuse_intensity = diffuse_intensity * light_intensity;
+        let shadow_intensity = if light.casts_shadows {
+            cast_shadow(&intersect, light, bvh, objects, config)
+        } else {
+            0.0
+        };
+        let radiance = light.radiance() * (1.0 - shadow_intensity);
+
+        total_diffuse += radiance * (diffuse_intensity * lambert_norm);
+
+        let highlight = match cube {
+            Some((cube, _)) if intersect.material.is_anisotropic() => {
+                // Tangente de la cara reortogonalizada respecto a la normal biselada
+                let face_tangent = cube.face_tangent(intersect.normal);
+                let tangent = (face_tangent - normal * normal.dot(face_tangent)).normalized();
+                anisotropic_highlight(
+                    normal,
+                    tangent,
+                    view_direction,
+                    light_direction,
+                    intersect.material.roughness_u,
+                    intersect.material.roughness_v,
+                )
+            }
+            _ => {
+                let reflection_direction = reflect(&-light_direction, &normal).normalized();
+                intersect
+                    .material
+                    .phong_highlight(view_direction.dot(reflection_direction))
+            }
+        };
+        total_specular += radiance * highlight;
+    }
+
+    let diffuse_color = surface_color(&intersect, texture_manager, uv_footprint);
 
-        total_diffuse = total_diffuse + light.color * final_diffuse_intensity;
+    let visibility = if config.ao.enabled {
+        let baked = match (ao_bake, intersect.object_idx) {
+            // El horneado se indexa por la cara geométrica, no por la normal biselada
+            (Some(bake), Some(idx)) if !config.ao.force_per_frame => {
+                bake.sample(objects, idx, intersect.point, intersect.outward_normal())
+            }
+            _ => None,
+        };
+        let visibility =
+            baked.unwrap_or_else(|| config.ao.visibility(intersect.point, normal, bvh, objects));
+        total_diffuse += config.ao.ambient * visibility;
+        visibility
+    } else {
+        1.0
+    };
 
-        let reflection_direction = reflect(&-light_direction, &normal).normalized();
-        let specular_intensity = view_direction
-            .dot(reflection_direction)
-            .max(0.0)
-            .powf(intersect.material.specular)
-            * light_intensity;
-        total_specular = total_specular + light.color * specular_intensity;
+    // Iluminación del entorno: el cielo en la dirección de la normal, atenuado por la oclusión
+    if config.ibl_strength > 0.0 {
+        let environment = procedural_sky(normal, texture_manager, skybox_texture, config);
+        total_diffuse += environment * (config.ibl_strength * visibility);
+    }
+    if let Some(probe) = &config.irradiance_probe {
+        total_diffuse += probe.irradiance(normal) * (config.irradiance_strength * visibility);
     }
 
-    let diffuse_color = if let Some(texture_path) = &intersect.material.texture {
-        if let Some(texture) = texture_manager.get_texture(texture_path) {
-            let width = texture.width() as u32;
-            let height = texture.height() as u32;
-            let tx = (intersect.u * width as f32) as u32;
-            let ty = (intersect.v * height as f32) as u32;
-            texture_manager.get_pixel_color(texture_path, tx, ty)
-        } else {
-            intersect.material.diffuse
-        }
+    let diffuse = diffuse_color * total_diffuse;
+    let specular = total_specular * intersect.material.specular_tint(diffuse_color);
+
+    let weights = intersect.material.term_weights(config.energy_conserving);
+    let [
+        diffuse_weight,
+        specular_weight,
+        reflection_weight,
+        refraction_weight,
+    ] = if config.clamp_bounces {
+        clamp_bounce_weights(weights)
     } else {
-        intersect.material.diffuse
+        weights
     };
 
-    let diffuse = diffuse_color * total_diffuse;
-    let specular = total_specular;
+    // Fracción del color del píxel que aportaría cada rebote; por debajo del umbral no se lanza
+    let reflection_throughput = throughput * reflection_weight;
+    let refraction_throughput = throughput * refraction_weight;
 
     let mut reflection_color = Vector3::zero();
-    let reflectivity = intersect.material.reflectivity;
+    let reflectivity = intersect.material.effective_reflectivity();
 
-    if reflectivity > 0.05 {
+    if reflectivity > 0.05 && reflection_throughput >= config.min_throughput {
         let reflect_direction = reflect(ray_direction, &normal);
         let reflect_origin = intersect.point + normal * ORIGIN_BIAS;
+        let reflect_differential = differential
+            .zip(point_differential)
+            .map(|(rays, (point_dx, point_dy))| rays.reflect(point_dx, point_dy, normal));
         reflection_color = cast_ray(
             &reflect_origin,
             &reflect_direction,
@@ -255,48 +425,335 @@ pub fn cast_ray(
             objects,
             lights,
             depth + 1,
+            reflection_throughput,
+            medium_index,
+            reflect_differential,
             texture_manager,
             skybox_texture,
+            config,
+            ao_bake,
+            trace.as_deref_mut(),
         );
     }
 
     let transparency = intersect.material.transparency;
     let mut refraction_color = Vector3::zero();
 
-    if transparency > 0.05 {
-        let refract_direction =
-            refract(ray_direction, &normal, intersect.material.refractive_index);
-        let refract_origin = offset_origin(&intersect, &refract_direction);
-        refraction_color = cast_ray(
-            &refract_origin,
-            &refract_direction,
-            bvh,
-            objects,
-            lights,
-            depth + 1,
-            texture_manager,
-            skybox_texture,
-        );
+    if transparency > 0.05 && refraction_throughput >= config.min_throughput {
+        let refractive_index = intersect.material.refractive_index;
+        let dispersion = intersect.material.dispersion;
+
+        // Al entrar se pasa del medio actual al del material; al salir, del material al
+        // aire, porque los cubos no se anidan. La normal se orienta hacia el rayo.
+        let facing = if ray_direction.dot(normal) > 0.0 {
+            -normal
+        } else {
+            normal
+        };
+        let mut refract_channel = |index: f32| {
+            let (from_index, to_index) = if intersect.inside {
+                (index, AIR_INDEX)
+            } else {
+                (medium_index, index)
+            };
+            // Con reflexión total interna el rayo rebota y sigue en el mismo medio
+            let refracted = refract_between(ray_direction, &facing, from_index, to_index);
+            let (refract_direction, next_medium) = match refracted {
+                Some(direction) => (direction, to_index),
+                None => (reflect(ray_direction, &facing), from_index),
+            };
+            let refract_differential =
+                differential
+                    .zip(point_differential)
+                    .map(|(rays, (point_dx, point_dy))| match refracted {
+                        Some(direction) => rays.refract(
+                            point_dx,
+                            point_dy,
+                            *ray_direction,
+                            direction,
+                            facing,
+                            from_index / to_index,
+                        ),
+                        None => rays.reflect(point_dx, point_dy, facing),
+                    });
+            let refract_origin = offset_origin(&intersect, &refract_direction);
+            cast_ray(
+                &refract_origin,
+                &refract_direction,
+                bvh,
+                objects,
+                lights,
+                depth + 1,
+                refraction_throughput,
+                next_medium,
+                refract_differential,
+                texture_manager,
+                skybox_texture,
+                config,
+                ao_bake,
+                trace.as_deref_mut(),
+            )
+        };
+
+        // Con dispersión cada canal se refracta con su propio índice; el azul se desvía más
+        refraction_color = if dispersion > 0.0 && refractive_index > 0.0 {
+            let red = refract_channel(refractive_index - dispersion);
+            let green = refract_channel(refractive_index);
+            let blue = refract_channel(refractive_index + dispersion);
+            Vector3::new(red.x, green.y, blue.z)
+        } else {
+            refract_channel(refractive_index)
+        };
     }
 
-    let emissive = if intersect.material.emission_strength > 0.01 {
-        diffuse_color * intersect.material.emission * intersect.material.emission_strength
-    } else {
-        Vector3::zero()
+    let emissive = intersect.material.emitted(diffuse_color);
+
+    let diffuse_term = diffuse * diffuse_weight;
+    let specular_term = specular * specular_weight;
+    let reflection_term =
+        reflection_color * intersect.material.reflection_tint(diffuse_color) * reflection_weight;
+    let refraction_term = refraction_color * refraction_weight;
+
+    // Los modos de depuración solo aíslan el término del impacto primario;
+    // los rebotes se calculan completos para que cada término muestre lo que aporta
+    let color = match config.debug_mode {
+        DebugMode::Diffuse if depth == 0 => diffuse_term,
+        DebugMode::Specular if depth == 0 => specular_term,
+        DebugMode::Reflection if depth == 0 => reflection_term,
+        DebugMode::Refraction if depth == 0 => refraction_term,
+        DebugMode::Emission if depth == 0 => emissive,
+        _ => diffuse_term + specular_term + reflection_term + refraction_term + emissive,
     };
 
-    diffuse * intersect.material.albedo[0]
-        + specular * intersect.material.albedo[1]
-        + reflection_color * reflectivity
-        + refraction_color * transparency
-        + emissive
+    let color = clamp_luminance(color, config.max_luminance);
+
+    if let (Some(steps), Some(idx)) = (trace, trace_idx) {
+        steps[idx].color = color;
+    }
+
+    color
+}
+
+/// Color difuso de la superficie: la textura en (u, v) o, sin textura, el color base del
+/// material (su ruido procedural o su difuso). Con `footprint` (ancho y alto en UV de la
+/// huella del píxel) se promedian los texels que cubre.
+fn surface_color(
+    intersect: &Intersect,
+    texture_manager: &TextureManager,
+    footprint: Option<(f32, f32)>,
+) -> Vector3 {
+    let base = || intersect.material.base_color(intersect.point);
+    match intersect.material.texture {
+        Some(texture) => match footprint {
+            Some((width, height)) => {
+                texture_manager.sample_filtered(texture, intersect.u, intersect.v, width, height)
+            }
+            None => texture_manager.sample(texture, intersect.u, intersect.v),
+        }
+        .unwrap_or_else(base),
+        None => base(),
+    }
+}
+
+/// Oscurecimiento hacia las esquinas: 1.0 dentro de `vignette_radius`, baja hasta
+/// `1 - vignette_strength` en las esquinas. La distancia se normaliza para que las esquinas valgan 1.
+fn vignette(x: i32, y: i32, width: i32, height: i32, config: &RenderConfig) -> f32 {
+    if config.vignette_strength <= 0.0 {
+        return 1.0;
+    }
+
+    let nx = (x as f32 + 0.5) / width as f32 * 2.0 - 1.0;
+    let ny = (y as f32 + 0.5) / height as f32 * 2.0 - 1.0;
+    let distance = (nx * nx + ny * ny).sqrt() / std::f32::consts::SQRT_2;
+
+    let radius = config.vignette_radius.clamp(0.0, 0.999);
+    let t = ((distance - radius) / (1.0 - radius)).clamp(0.0, 1.0);
+    let falloff = t * t * (3.0 - 2.0 * t);
+
+    (1.0 - config.vignette_strength * falloff).max(0.0)
+}
+
+/// Curva que comprime la radiancia lineal al rango [0, 1] antes de convertirla a 8 bits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToneMap {
+    /// Recorta cada canal en 1.0, como siempre
+    None,
+    Reinhard,
+    /// Ajuste de Narkowicz de la curva fílmica ACES
+    Aces,
+}
+
+impl ToneMap {
+    pub fn apply(self, color: Vector3) -> Vector3 {
+        match self {
+            ToneMap::None => color,
+            ToneMap::Reinhard => Vector3::new(
+                color.x / (1.0 + color.x),
+                color.y / (1.0 + color.y),
+                color.z / (1.0 + color.z),
+            ),
+            ToneMap::Aces => {
+                let aces = |x: f32| {
+                    let x = x.max(0.0);
+                    ((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)).clamp(0.0, 1.0)
+                };
+                Vector3::new(aces(color.x), aces(color.y), aces(color.z))
+            }
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            ToneMap::None => ToneMap::Reinhard,
+            ToneMap::Reinhard => ToneMap::Aces,
+            ToneMap::Aces => ToneMap::None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ToneMap::None => "none",
+            ToneMap::Reinhard => "reinhard",
+            ToneMap::Aces => "aces",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "none" => Some(ToneMap::None),
+            "reinhard" => Some(ToneMap::Reinhard),
+            "aces" => Some(ToneMap::Aces),
+            _ => None,
+        }
+    }
+}
+
+/// Término de sombreado que se muestra en lugar de la imagen completa
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugMode {
+    Off,
+    Diffuse,
+    Specular,
+    Reflection,
+    Refraction,
+    Emission,
+    /// Distancia del impacto primario en grises, de blanco (cerca) a negro (lejos)
+    Depth,
+}
+
+impl DebugMode {
+    pub fn next(self) -> Self {
+        match self {
+            DebugMode::Off => DebugMode::Diffuse,
+            DebugMode::Diffuse => DebugMode::Specular,
+            DebugMode::Specular => DebugMode::Reflection,
+            DebugMode::Reflection => DebugMode::Refraction,
+            DebugMode::Refraction => DebugMode::Emission,
+            DebugMode::Emission => DebugMode::Depth,
+            DebugMode::Depth => DebugMode::Off,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DebugMode::Off => "off",
+            DebugMode::Diffuse => "diffuse",
+            DebugMode::Specular => "specular",
+            DebugMode::Reflection => "reflection",
+            DebugMode::Refraction => "refraction",
+            DebugMode::Emission => "emission",
+            DebugMode::Depth => "depth",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let mut mode = DebugMode::Off;
+        loop {
+            if mode.name() == name {
+                return Some(mode);
+            }
+            mode = mode.next();
+            if mode == DebugMode::Off {
+                return None;
+            }
+        }
+    }
 }
 
+#[derive(Clone)]
 pub struct RenderConfig {
     pub aspect_ratio: f32,
     pub perspective_scale: f32,
     pub inv_width: f32,
     pub inv_height: f32,
+    pub ao: AmbientOcclusion,
+    pub max_luminance: f32,
+    /// Número de hilos de render; `None` o `Some(0)` usa todos los núcleos disponibles y
+    /// `Some(1)` renderiza en el hilo principal
+    pub threads: Option<usize>,
+    /// Distancia máxima de los rayos de cámara, reflexión y refracción; más allá se ve el cielo
+    pub max_ray_distance: f32,
+    /// Color plano para los rayos que no golpean nada; `None` usa el skybox o el degradado
+    pub background: Option<Vector3>,
+    /// Intensidad del viñeteado aplicado antes de convertir a 8 bits; 0 lo desactiva
+    pub vignette_strength: f32,
+    /// Distancia normalizada al centro (esquinas = 1) desde la que empieza a oscurecer
+    pub vignette_radius: f32,
+    pub debug_mode: DebugMode,
+    /// Distancias que `DebugMode::Depth` pinta de blanco y de negro; `None` las ajusta a la
+    /// caja que envuelve la escena vista desde el ojo
+    pub depth_range: Option<(f32, f32)>,
+    /// Eje vertical de la escena, para orientar el cielo
+    pub up_axis: UpAxis,
+    /// Segundo skybox y su peso actual en la mezcla con el principal; `None` usa solo este
+    pub sky_blend: Option<(TextureId, f32)>,
+    /// Rayos de sombra por luz con radio; las luces puntuales usan siempre uno
+    pub shadow_samples: u32,
+    /// Rayos de sombra por luz puntual cuando `shadow_jitter` es mayor que 0; con uno la
+    /// sombra queda dura
+    pub shadow_jitter_samples: u32,
+    /// Desviación máxima de la dirección de esos rayos (tangente del ángulo); 0 la desactiva
+    pub shadow_jitter: f32,
+    /// Si es falso, los objetos transparentes no proyectan sombra
+    pub transparent_shadows: bool,
+    /// Peso del skybox como luz ambiental; 0 lo desactiva
+    pub ibl_strength: f32,
+    /// Luz del cielo precalculada que se evalúa por la normal, más barata que `ibl_strength`;
+    /// `None` la desactiva
+    pub irradiance_probe: Option<IrradianceProbe>,
+    pub irradiance_strength: f32,
+    /// Rayos por píxel; con más de uno se reparten dentro del píxel para suavizar bordes
+    pub samples_per_pixel: u32,
+    /// Reparto de esos rayos dentro del píxel
+    pub sample_pattern: SamplePattern,
+    /// Semilla del azar del reparto estratificado; cambiarla da otro ruido con la misma calidad
+    pub sample_seed: u32,
+    /// Sigue la huella de cada píxel a lo largo de los rebotes para promediar los texels que
+    /// cubre; falso toma un único texel, como siempre
+    pub ray_differentials: bool,
+    /// Curva aplicada a la imagen de 8 bits; el búfer HDR guarda la radiancia sin comprimir
+    pub tone_map: ToneMap,
+    /// Multiplicador de la radiancia antes de la curva de tonos; solo afecta a la imagen de 8 bits
+    pub exposure: f32,
+    /// Filtro de ruido aplicado al búfer HDR al terminar cada vista; necesita los AOV activos
+    pub denoiser: Denoiser,
+    /// Tiñe de verde los píxeles cuyo impacto cae dentro de `focus_band` del plano de enfoque
+    pub focus_peaking: bool,
+    /// Distancia de enfoque; `None` usa la distancia de la cámara al punto que mira
+    pub focus_distance: Option<f32>,
+    pub focus_band: f32,
+    /// Los rayos de cámara empiezan a esta distancia del ojo; lo que quede más cerca se
+    /// recorta en lugar de parpadear al acercar mucho la cámara
+    pub near_distance: f32,
+    /// Difuso de Lambert normalizado (dividido entre π) y pesos de material reescalados para
+    /// no reflejar más energía de la que llega; falso mantiene el aspecto de siempre
+    pub energy_conserving: bool,
+    /// Reflexión y refracción reescaladas para que no sumen más de 1; el resto de pesos y
+    /// los materiales dentro del presupuesto no cambian
+    pub clamp_bounces: bool,
+    /// Los rayos de reflexión y refracción cuya aportación al píxel queda por debajo de este
+    /// valor no se lanzan; 1/256 es menos de un nivel de la imagen de 8 bits
+    pub min_throughput: f32,
 }
 
 impl RenderConfig {
@@ -308,54 +765,339 @@ impl RenderConfig {
             perspective_scale: (fov * 0.5).tan(),
             inv_width: 1.0 / w,
             inv_height: 1.0 / h,
+            ao: AmbientOcclusion::new(16, 1.0),
+            max_luminance: f32::INFINITY,
+            threads: None,
+            max_ray_distance: f32::INFINITY,
+            background: None,
+            vignette_strength: 0.0,
+            vignette_radius: 0.5,
+            debug_mode: DebugMode::Off,
+            depth_range: None,
+            up_axis: UpAxis::Y,
+            sky_blend: None,
+            shadow_samples: 8,
+            shadow_jitter_samples: 4,
+            shadow_jitter: 0.0,
+            transparent_shadows: false,
+            ibl_strength: 0.0,
+            irradiance_probe: None,
+            irradiance_strength: 0.0,
+            samples_per_pixel: 1,
+            sample_pattern: SamplePattern::Lattice,
+            sample_seed: 0,
+            ray_differentials: false,
+            tone_map: ToneMap::None,
+            exposure: 1.0,
+            denoiser: Denoiser::default(),
+            focus_peaking: false,
+            focus_distance: None,
+            focus_band: 0.25,
+            near_distance: 0.0,
+            energy_conserving: false,
+            clamp_bounces: false,
+            min_throughput: 1.0 / 256.0,
+        }
+    }
+
+    /// Cambia el campo de visión vertical (en radianes) sin tocar el resto
+    pub fn set_fov(&mut self, fov: f32) {
+        self.perspective_scale = (fov * 0.5).tan();
+    }
+
+    /// Copia de la configuración para una vista de `width` x `height` píxeles con el mismo
+    /// campo de visión vertical
+    pub fn with_viewport(&self, width: i32, height: i32) -> Self {
+        let w = width as f32;
+        let h = height as f32;
+        RenderConfig {
+            aspect_ratio: w / h,
+            inv_width: 1.0 / w,
+            inv_height: 1.0 / h,
+            ..self.clone()
+        }
+    }
+
+    /// Desplazamiento dentro del píxel de la muestra `i`, en [-0.5, 0.5). `pixel_seed` solo
+    /// cambia el reparto estratificado. Con una sola muestra el rayo pasa por la esquina del
+    /// píxel como siempre.
+    fn sample_offset(&self, i: u32, pixel_seed: u32) -> (f32, f32) {
+        if self.samples_per_pixel <= 1 {
+            return (0.0, 0.0);
         }
+        match self.sample_pattern {
+            SamplePattern::Lattice => {
+                let n = self.samples_per_pixel as f32;
+                let golden = 0.618_034;
+                let dx = (i as f32 + 0.5) / n - 0.5;
+                let dy = (i as f32 * golden).fract() - 0.5;
+                (dx, dy)
+            }
+            SamplePattern::Stratified => {
+                let (u, v) = sampling::stratified(i, self.samples_per_pixel, pixel_seed);
+                (u - 0.5, v - 0.5)
+            }
+        }
+    }
+}
+
+/// Rectángulo de píxeles de la imagen, con el origen en la esquina superior izquierda
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Region {
+    pub fn new(x: i32, y: i32, width: i32, height: i32) -> Self {
+        Region {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn full(width: i32, height: i32) -> Self {
+        Region::new(0, 0, width, height)
+    }
+
+    /// Recorta la región a una imagen de `width` x `height`; puede quedar vacía
+    pub fn clamp_to(self, width: i32, height: i32) -> Self {
+        let x = self.x.clamp(0, width);
+        let y = self.y.clamp(0, height);
+        let right = (self.x + self.width).clamp(x, width);
+        let bottom = (self.y + self.height).clamp(y, height);
+        Region::new(x, y, right - x, bottom - y)
     }
 }
 
 struct RowRange {
     start: i32,
     end: i32,
-    pixels: Vec<Color>,
+    pixels: Vec<Vector3>,
+    /// Vacío salvo que el framebuffer tenga los AOV activados
+    aovs: Vec<AovSample>,
+    /// Vacío salvo con focus peaking
+    in_focus: Vec<bool>,
+}
+
+/// Dirección en el mundo del rayo de cámara que pasa por el punto (x, y) de la pantalla
+pub fn primary_ray_direction(x: f32, y: f32, camera: &Camera, config: &RenderConfig) -> Vector3 {
+    let screen_x =
+        (2.0 * x * config.inv_width - 1.0) * config.aspect_ratio * config.perspective_scale;
+    let screen_y = (1.0 - 2.0 * y * config.inv_height) * config.perspective_scale;
+
+    let ray_direction = Vector3::new(screen_x, screen_y, -1.0).normalized();
+    camera.basis_change(&ray_direction)
+}
+
+/// Sonda de irradiancia del cielo actual (skybox o degradado procedural, con su mezcla)
+fn sky_probe(
+    texture_manager: &TextureManager,
+    skybox_texture: Option<TextureId>,
+    config: &RenderConfig,
+) -> IrradianceProbe {
+    IrradianceProbe::from_environment(PROBE_SAMPLES, |direction| {
+        procedural_sky(direction, texture_manager, skybox_texture, config)
+    })
+}
+
+/// Diferencial del rayo de cámara del punto (x, y), o `None` si `ray_differentials` está apagado
+pub fn primary_ray_differential(
+    x: f32,
+    y: f32,
+    camera: &Camera,
+    config: &RenderConfig,
+) -> Option<RayDifferential> {
+    if !config.ray_differentials {
+        return None;
+    }
+
+    let direction = primary_ray_direction(x, y, camera, config);
+    let mut rays = RayDifferential::pinhole(
+        direction,
+        primary_ray_direction(x + 1.0, y, camera, config),
+        primary_ray_direction(x, y + 1.0, camera, config),
+    );
+    // Los rayos salen del plano cercano, no del ojo
+    rays.origin_dx = rays.direction_dx * config.near_distance;
+    rays.origin_dy = rays.direction_dy * config.near_distance;
+    Some(rays)
+}
+
+/// Píxel en el que se ve el punto `point`, inverso de `primary_ray_direction`;
+/// `None` si queda detrás de la cámara
+fn project_to_screen(point: Vector3, camera: &Camera, config: &RenderConfig) -> Option<(f32, f32)> {
+    let p = camera.to_camera_space(&point);
+    if p.z >= 0.0 {
+        return None;
+    }
+
+    let screen_x = p.x / -p.z;
+    let screen_y = p.y / -p.z;
+    let x = (screen_x / (config.aspect_ratio * config.perspective_scale) + 1.0) * 0.5
+        / config.inv_width;
+    let y = (1.0 - screen_y / config.perspective_scale) * 0.5 / config.inv_height;
+    Some((x, y))
+}
+
+/// Marcador de cada luz sobre la imagen, con su color y un tamaño que crece con la intensidad
+fn light_markers(lights: &[Light], camera: &Camera, config: &RenderConfig) -> Vec<ScreenMarker> {
+    lights
+        .iter()
+        .filter_map(|light| {
+            let (x, y) = project_to_screen(light.position, camera, config)?;
+            Some(ScreenMarker {
+                x: x as i32,
+                y: y as i32,
+                radius: (3.0 + 4.0 * light.intensity.max(0.0).sqrt()).min(16.0),
+                color: vector3_to_color(light.color),
+            })
+        })
+        .collect()
+}
+
+/// Impacto del rayo de cámara por el punto (x, y), con la distancia medida desde el ojo
+fn primary_hit(
+    x: i32,
+    y: i32,
+    bvh: &BVHNode,
+    objects: &[Cube],
+    camera: &Camera,
+    config: &RenderConfig,
+) -> Intersect {
+    let direction = primary_ray_direction(x as f32, y as f32, camera, config);
+    let inv_dir = Vector3::new(1.0 / direction.x, 1.0 / direction.y, 1.0 / direction.z);
+    let origin = camera.eye + direction * config.near_distance;
+    let mut hit = bvh.intersect_within(
+        objects,
+        &origin,
+        &direction,
+        &inv_dir,
+        config.max_ray_distance,
+    );
+    hit.distance += config.near_distance;
+    hit
+}
+
+/// Gris de la vista de profundidad: blanco en `near`, negro en `far` y más allá, y negro
+/// también para los rayos que no golpean nada
+fn depth_shade(hit: &Intersect, (near, far): (f32, f32)) -> Vector3 {
+    if !hit.is_intersecting {
+        return Vector3::zero();
+    }
+    let t = ((hit.distance - near) / (far - near).max(1e-6)).clamp(0.0, 1.0);
+    Vector3::one() * (1.0 - t)
+}
+
+/// La distancia del impacto primario está dentro de la banda de enfoque. Los rayos que no
+/// golpean nada nunca se resaltan.
+fn in_focus_band(depth: Option<f32>, focus: f32, band: f32) -> bool {
+    depth.is_some_and(|d| (d - focus).abs() <= band)
+}
+
+/// Mezcla con verde un color ya preparado para mostrarse, nunca la radiancia
+fn focus_peaking_tint(color: Vector3) -> Vector3 {
+    color * 0.5 + Vector3::new(0.0, 0.5, 0.0)
 }
 
 pub fn render_row_range(
     start_y: i32,
     end_y: i32,
-    width: i32,
+    start_x: i32,
+    end_x: i32,
+    viewport: Region,
     bvh: &BVHNode,
     objects: &[Cube],
     camera: &Camera,
     lights: &[Light],
     texture_manager: &TextureManager,
     config: &RenderConfig,
-    skybox_texture: Option<String>,
-) -> Vec<Color> {
-    let mut pixels = Vec::with_capacity(((end_y - start_y) * width) as usize);
+    skybox_texture: Option<TextureId>,
+    ao_bake: Option<&AOBake>,
+    mut aovs: Option<&mut Vec<AovSample>>,
+    in_focus: &mut Vec<bool>,
+) -> Vec<Vector3> {
+    let mut pixels = Vec::with_capacity(((end_y - start_y) * (end_x - start_x)) as usize);
+
+    let samples = config.samples_per_pixel.max(1);
+    let focus = config
+        .focus_distance
+        .unwrap_or_else(|| (camera.center - camera.eye).length());
+    // La vista de profundidad solo necesita el impacto primario de cada píxel
+    let depth_range = (config.debug_mode == DebugMode::Depth).then(|| {
+        config
+            .depth_range
+            .unwrap_or_else(|| bvh.bounds().distance_range(camera.eye))
+    });
 
     for y in start_y..end_y {
-        for x in 0..width {
-            let screen_x = (2.0 * x as f32 * config.inv_width - 1.0)
-                * config.aspect_ratio
-                * config.perspective_scale;
-            let screen_y = (1.0 - 2.0 * y as f32 * config.inv_height) * config.perspective_scale;
-
-            let ray_direction = Vector3::new(screen_x, screen_y, -1.0).normalized();
-            let rotated_direction = camera.basis_change(&ray_direction);
-
-            let skybox_ref = skybox_texture.as_deref();
-            let pixel_color_vec = cast_ray(
-                &camera.eye,
-                &rotated_direction,
-                bvh,
-                objects,
-                lights,
-                0,
-                texture_manager,
-                skybox_ref,
-            );
-            let pixel_color = vector3_to_color(pixel_color_vec);
+        for x in start_x..end_x {
+            // Coordenadas relativas a la vista, que puede ocupar solo parte de la imagen
+            let seed = sampling::pixel_seed(x, y, config.sample_seed);
+            let (x, y) = (x - viewport.x, y - viewport.y);
+            let mut accumulated = Vector3::zero();
+
+            // El impacto primario solo se vuelve a buscar si algo lo necesita
+            let primary = (config.focus_peaking || aovs.is_some() || depth_range.is_some())
+                .then(|| primary_hit(x, y, bvh, objects, camera, config));
+
+            let traced_samples = if depth_range.is_some() { 0 } else { samples };
+            for sample in 0..traced_samples {
+                let (dx, dy) = config.sample_offset(sample, seed);
+                let rotated_direction =
+                    primary_ray_direction(x as f32 + dx, y as f32 + dy, camera, config);
+                let origin = camera.eye + rotated_direction * config.near_distance;
+                // Con varias muestras cada una cubre solo una parte del píxel
+                let differential =
+                    primary_ray_differential(x as f32 + dx, y as f32 + dy, camera, config)
+                        .map(|rays| rays.scaled(1.0 / (samples as f32).sqrt()));
+
+                accumulated += cast_ray(
+                    &origin,
+                    &rotated_direction,
+                    bvh,
+                    objects,
+                    lights,
+                    0,
+                    1.0,
+                    AIR_INDEX,
+                    differential,
+                    texture_manager,
+                    skybox_texture,
+                    config,
+                    ao_bake,
+                    None,
+                );
+            }
+
+            let color = match (depth_range, &primary) {
+                (Some(range), Some(hit)) => depth_shade(hit, range),
+                _ => accumulated / samples as f32,
+            };
+            // El tinte se aplica al pasar a 8 bits, fuera del HDR, la exposición y los AOV
+            if config.focus_peaking
+                && let Some(hit) = &primary
+            {
+                let depth = hit.is_intersecting.then_some(hit.distance);
+                in_focus.push(in_focus_band(depth, focus, config.focus_band));
+            }
+            if let (Some(aovs), Some(hit)) = (aovs.as_deref_mut(), &primary) {
+                aovs.push(if hit.is_intersecting {
+                    AovSample {
+                        normal: hit.normal,
+                        depth: hit.distance,
+                        albedo: surface_color(hit, texture_manager, None),
+                    }
+                } else {
+                    AovSample::miss()
+                });
+            }
 
-            pixels.push(pixel_color);
+            pixels.push(color);
         }
     }
 
@@ -370,265 +1112,1086 @@ pub fn render(
     lights: &[Light],
     texture_manager: &TextureManager,
     config: &RenderConfig,
-    skybox_texture: Option<String>,
+    skybox_texture: Option<TextureId>,
+    ao_bake: Option<&AOBake>,
+    crop: Option<Region>,
 ) {
-    let num_threads = thread::available_parallelism()
-        .map(|n| n.get())
-        .unwrap_or(4);
-
-    let height = framebuffer.height;
-    let width = framebuffer.width;
-    let rows_per_thread = (height as f32 / num_threads as f32).ceil() as i32;
+    let full = Region::full(framebuffer.width, framebuffer.height);
+    // Fuera del recorte el framebuffer queda tal como estaba
+    let region = crop.unwrap_or(full);
+
+    render_viewport(
+        framebuffer,
+        bvh,
+        objects,
+        camera,
+        lights,
+        texture_manager,
+        config,
+        skybox_texture,
+        ao_bake,
+        full,
+        region,
+    );
+}
 
-    let results = thread::scope(|s| {
-        let mut handles = vec![];
+/// Renderiza la escena desde dos ojos separados `eye_distance` a lo largo de `right` y los
+/// combina en un anaglifo rojo/cian dentro de `framebuffer`
+pub fn render_anaglyph(
+    framebuffer: &mut Framebuffer,
+    bvh: &BVHNode,
+    objects: &[Cube],
+    camera: &Camera,
+    eye_distance: f32,
+    lights: &[Light],
+    texture_manager: &TextureManager,
+    config: &RenderConfig,
+    skybox_texture: Option<TextureId>,
+    ao_bake: Option<&AOBake>,
+    crop: Option<Region>,
+) {
+    let left_eye = camera.stereo_eye(-eye_distance * 0.5);
+    let right_eye = camera.stereo_eye(eye_distance * 0.5);
 
-        for thread_id in 0..num_threads {
-            let start_y = thread_id as i32 * rows_per_thread;
-            let end_y = ((thread_id as i32 + 1) * rows_per_thread).min(height);
+    let mut right = Framebuffer::new(framebuffer.width, framebuffer.height);
+    if framebuffer.hdr_buffer.is_some() {
+        right.enable_hdr();
+    }
 
-            if start_y >= height {
-                break;
-            }
+    for (target, eye) in [(&mut *framebuffer, &left_eye), (&mut right, &right_eye)] {
+        render(
+            target,
+            bvh,
+            objects,
+            eye,
+            lights,
+            texture_manager,
+            config,
+            skybox_texture,
+            ao_bake,
+            crop,
+        );
+    }
 
-            let skybox_clone = skybox_texture.clone();
+    framebuffer.combine_anaglyph(&mut right);
+}
 
-            let handle = s.spawn(move || {
-                let pixels = render_row_range(
-                    start_y,
-                    end_y,
-                    width,
-                    bvh,
-                    objects,
-                    camera,
-                    lights,
-                    texture_manager,
-                    config,
-                    skybox_clone,
-                );
+/// Cámara que se dibuja en una región de la imagen
+pub struct View<'a> {
+    pub camera: &'a Camera,
+    pub region: Region,
+}
 
-                RowRange {
-                    start: start_y,
-                    end: end_y,
-                    pixels,
-                }
-            });
+/// Dos vistas lado a lado que se reparten el ancho de la imagen
+pub fn side_by_side<'a>(
+    left: &'a Camera,
+    right: &'a Camera,
+    width: i32,
+    height: i32,
+) -> [View<'a>; 2] {
+    let half = width / 2;
+    [
+        View {
+            camera: left,
+            region: Region::new(0, 0, half, height),
+        },
+        View {
+            camera: right,
+            region: Region::new(half, 0, width - half, height),
+        },
+    ]
+}
 
-            handles.push(handle);
-        }
+/// Renderiza varias cámaras en una pasada, cada una en su región y con su propia
+/// relación de aspecto. Lo que no cubre ninguna vista queda intacto.
+pub fn render_views(
+    framebuffer: &mut Framebuffer,
+    bvh: &BVHNode,
+    objects: &[Cube],
+    views: &[View],
+    lights: &[Light],
+    texture_manager: &TextureManager,
+    config: &RenderConfig,
+    skybox_texture: Option<TextureId>,
+    ao_bake: Option<&AOBake>,
+) {
+    for view in views {
+        if view.region.width <= 0 || view.region.height <= 0 {
+            continue;
+        }
+        let view_config = config.with_viewport(view.region.width, view.region.height);
+        render_viewport(
+            framebuffer,
+            bvh,
+            objects,
+            view.camera,
+            lights,
+            texture_manager,
+            &view_config,
+            skybox_texture,
+            ao_bake,
+            view.region,
+            view.region,
+        );
+    }
+}
+
+/// Reparte las filas de `region` entre `num_threads` hilos
+fn render_rows_parallel(
+    num_threads: usize,
+    region: Region,
+    viewport: Region,
+    bvh: &BVHNode,
+    objects: &[Cube],
+    camera: &Camera,
+    lights: &[Light],
+    texture_manager: &TextureManager,
+    config: &RenderConfig,
+    skybox_texture: Option<TextureId>,
+    ao_bake: Option<&AOBake>,
+    with_aovs: bool,
+) -> Vec<RowRange> {
+    let (start_x, end_x) = (region.x, region.x + region.width);
+    let region_end_y = region.y + region.height;
+    let rows_per_thread = (region.height as f32 / num_threads as f32).ceil() as i32;
+
+    thread::scope(|s| {
+        let mut handles = vec![];
+
+        for thread_id in 0..num_threads {
+            let start_y = region.y + thread_id as i32 * rows_per_thread;
+            let end_y = (start_y + rows_per_thread).min(region_end_y);
+
+            if start_y >= region_end_y {
+                break;
+            }
+
+            let handle = s.spawn(move || {
+                let mut aovs = Vec::new();
+                let mut in_focus = Vec::new();
+                let pixels = render_row_range(
+                    start_y,
+                    end_y,
+                    start_x,
+                    end_x,
+                    viewport,
+                    bvh,
+                    objects,
+                    camera,
+                    lights,
+                    texture_manager,
+                    config,
+                    skybox_texture,
+                    ao_bake,
+                    with_aovs.then_some(&mut aovs),
+                    &mut in_focus,
+                );
+
+                RowRange {
+                    start: start_y,
+                    end: end_y,
+                    pixels,
+                    aovs,
+                    in_focus,
+                }
+            });
+
+            handles.push(handle);
+        }
 
         handles
             .into_iter()
             .map(|h| h.join().unwrap())
             .collect::<Vec<_>>()
-    });
+    })
+}
+
+/// Traza los píxeles de `region` proyectando la cámara sobre `viewport`, el rectángulo
+/// que ocupa la imagen completa de esa cámara
+fn render_viewport(
+    framebuffer: &mut Framebuffer,
+    bvh: &BVHNode,
+    objects: &[Cube],
+    camera: &Camera,
+    lights: &[Light],
+    texture_manager: &TextureManager,
+    config: &RenderConfig,
+    skybox_texture: Option<TextureId>,
+    ao_bake: Option<&AOBake>,
+    viewport: Region,
+    region: Region,
+) {
+    let num_threads = match config.threads {
+        Some(n) if n > 0 => n,
+        _ => thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(4),
+    };
+
+    let region = region.clamp_to(framebuffer.width, framebuffer.height);
+    if region.width <= 0 || region.height <= 0 {
+        return;
+    }
+    let (start_x, end_x) = (region.x, region.x + region.width);
+    let region_end_y = region.y + region.height;
+    let with_aovs = framebuffer.aovs.is_some();
+
+    // Con un solo hilo todo corre en el hilo principal, sin hilos auxiliares: así se puede
+    // depurar cast_ray con puntos de interrupción y un panic muestra la traza completa
+    let results = if num_threads == 1 {
+        let mut aovs = Vec::new();
+        let mut in_focus = Vec::new();
+        let pixels = render_row_range(
+            region.y,
+            region_end_y,
+            start_x,
+            end_x,
+            viewport,
+            bvh,
+            objects,
+            camera,
+            lights,
+            texture_manager,
+            config,
+            skybox_texture,
+            ao_bake,
+            with_aovs.then_some(&mut aovs),
+            &mut in_focus,
+        );
+        vec![RowRange {
+            start: region.y,
+            end: region_end_y,
+            pixels,
+            aovs,
+            in_focus,
+        }]
+    } else {
+        render_rows_parallel(
+            num_threads,
+            region,
+            viewport,
+            bvh,
+            objects,
+            camera,
+            lights,
+            texture_manager,
+            config,
+            skybox_texture,
+            ao_bake,
+            with_aovs,
+        )
+    };
 
     for row_range in results {
         let mut pixel_idx = 0;
 
         for y in row_range.start..row_range.end {
-            for x in 0..width {
-                let color = row_range.pixels[pixel_idx];
-                framebuffer.set_current_color(color);
+            for x in start_x..end_x {
+                let value = row_range.pixels[pixel_idx]
+                    * vignette(
+                        x - viewport.x,
+                        y - viewport.y,
+                        viewport.width,
+                        viewport.height,
+                        config,
+                    );
+                let in_focus = row_range.in_focus.get(pixel_idx).copied().unwrap_or(false);
+                framebuffer.set_in_focus(x, y, in_focus);
+                framebuffer.set_current_color(resolve_color(value, in_focus, config));
                 framebuffer.set_pixel(x, y);
+                framebuffer.set_hdr_pixel(x, y, value);
+                if let Some(&sample) = row_range.aovs.get(pixel_idx) {
+                    framebuffer.set_aov_pixel(x, y, sample);
+                }
                 pixel_idx += 1;
             }
         }
     }
+
+    if config.denoiser.is_enabled() {
+        denoise_region(framebuffer, region, config);
+    }
 }
 
-fn main() {
-    let window_width = 1300;
-    let window_height = 900;
+/// Filtra el búfer HDR de `region` guiado por los AOV y vuelve a convertir esos píxeles a
+/// 8 bits. Sin búfer HDR o sin AOV no hace nada.
+fn denoise_region(framebuffer: &mut Framebuffer, region: Region, config: &RenderConfig) {
+    let (Some(hdr), Some(aovs)) = (&framebuffer.hdr_buffer, &framebuffer.aovs) else {
+        return;
+    };
 
-    let (mut window, raylib_thread) = raylib::init()
-        .size(window_width, window_height)
-        .title("Raytracer - Nether Crimson Forest")
-        .log_level(TraceLogLevel::LOG_WARNING)
-        .build();
+    let width = framebuffer.width;
+    let indices: Vec<usize> = (region.y..region.y + region.height)
+        .flat_map(|y| (region.x..region.x + region.width).map(move |x| (y * width + x) as usize))
+        .collect();
+    let pixels: Vec<Vector3> = indices.iter().map(|&i| hdr[i]).collect();
+    let normals: Vec<Vector3> = indices.iter().map(|&i| aovs.normal[i]).collect();
+    let depths: Vec<f32> = indices.iter().map(|&i| aovs.depth[i]).collect();
+
+    let filtered = config
+        .denoiser
+        .filter(region.width as usize, &pixels, &normals, &depths);
+    for (i, value) in filtered.into_iter().enumerate() {
+        let x = region.x + i as i32 % region.width;
+        let y = region.y + i as i32 / region.width;
+        let in_focus = framebuffer.is_in_focus(x, y);
+        framebuffer.set_current_color(resolve_color(value, in_focus, config));
+        framebuffer.set_pixel(x, y);
+        framebuffer.set_hdr_pixel(x, y, value);
+    }
+}
 
-    let mut texture_manager = TextureManager::new();
-    texture_manager.load_texture(&mut window, &raylib_thread, "assets/obsidian.png");
-    texture_manager.load_texture(&mut window, &raylib_thread, "assets/shroomlight.png");
-    texture_manager.load_texture(&mut window, &raylib_thread, "assets/crimson_nylium.png");
-    texture_manager.load_texture(&mut window, &raylib_thread, "assets/crimson_stem.png");
-    texture_manager.load_texture(&mut window, &raylib_thread, "assets/nether_wart_block.png");
-    texture_manager.load_texture(&mut window, &raylib_thread, "assets/portal.png");
-
-    let skybox_texture = if std::path::Path::new("assets/nether_skybox.png").exists() {
-        texture_manager.load_texture(&mut window, &raylib_thread, "assets/nether_skybox.png");
-        Some("assets/nether_skybox.png".to_string())
+fn resolve_color(value: Vector3, in_focus: bool, config: &RenderConfig) -> Color {
+    let color = config.tone_map.apply(value * config.exposure);
+    vector3_to_color(if in_focus {
+        focus_peaking_tint(color)
     } else {
-        None
+        color
+    })
+}
+
+/// Vuelve a convertir a 8 bits el búfer HDR con la exposición actual, sin trazar rayos
+fn resolve_hdr(framebuffer: &mut Framebuffer, config: &RenderConfig) {
+    let Some(pixels) = framebuffer.hdr_buffer.take() else {
+        return;
     };
 
-    let mut framebuffer = Framebuffer::new(window_width as i32, window_height as i32);
-    framebuffer.set_background_color(Color::new(51, 13, 13, 255));
+    for (i, &value) in pixels.iter().enumerate() {
+        let (x, y) = (i as i32 % framebuffer.width, i as i32 / framebuffer.width);
+        let in_focus = framebuffer.is_in_focus(x, y);
+        framebuffer.set_current_color(resolve_color(value, in_focus, config));
+        framebuffer.set_pixel(x, y);
+    }
+    framebuffer.hdr_buffer = Some(pixels);
+}
 
-    let obsidian = Material {
-        diffuse: Vector3::new(0.15, 0.1, 0.2),
-        albedo: [0.9, 0.1],
-        specular: 90.0,
-        reflectivity: 0.1,
-        transparency: 0.0,
-        refractive_index: 0.0,
-        texture: Some("assets/obsidian.png".to_string()),
-        normal_map_id: None,
-        emission: Vector3::zero(),
-        emission_strength: 0.0,
-    };
+const SCENE_FILE: &str = "scene.txt";
+const FULL_SCENE_FILE: &str = "scene.scn";
+#[cfg(feature = "json-scene")]
+const JSON_SCENE_FILE: &str = "scene.json";
+const SCREENSHOT_DIR: &str = "screenshots";
+const SKYBOX_FILE: &str = "assets/nether_skybox.png";
+const ASSET_DIR: &str = "assets";
+// Tiempo con la vista quieta antes de trazar el frame final con --final-samples
+const FINAL_RENDER_DELAY: std::time::Duration = std::time::Duration::from_millis(250);
+
+fn print_selection(selected: usize, scene: &Scene, objects: &[Cube]) {
+    println!(
+        "Selected object {} ({}){}",
+        selected,
+        scene.objects[selected].material,
+        if objects[selected].enabled {
+            ""
+        } else {
+            " [hidden]"
+        }
+    );
+}
+
+fn print_light_selection(selected: usize, lights: &[Light]) {
+    let light = &lights[selected];
+    println!(
+        "Selected light {}: intensity {:.2}, color ({:.2}, {:.2}, {:.2})",
+        selected, light.intensity, light.color.x, light.color.y, light.color.z
+    );
+}
 
-    let shroomlight = Material {
-        diffuse: Vector3::new(0.95, 0.6, 0.3),
-        albedo: [0.9, 0.1],
-        specular: 15.0,
-        reflectivity: 0.0,
-        transparency: 0.0,
-        refractive_index: 0.0,
-        texture: Some("assets/shroomlight.png".to_string()),
-        normal_map_id: None,
-        emission: Vector3::new(1.0, 0.45, 0.15),
-        emission_strength: 1.2,
+/// Archivo de escena completa presente en la raíz, si lo hay
+fn full_scene_path() -> Option<&'static str> {
+    #[cfg(feature = "json-scene")]
+    if std::path::Path::new(JSON_SCENE_FILE).exists() {
+        return Some(JSON_SCENE_FILE);
+    }
+
+    if std::path::Path::new(FULL_SCENE_FILE).exists() {
+        Some(FULL_SCENE_FILE)
+    } else {
+        None
+    }
+}
+
+fn default_materials() -> BTreeMap<String, Material> {
+    let obsidian = MaterialBuilder::new()
+        .diffuse(Vector3::new(0.15, 0.1, 0.2))
+        .albedo(0.9, 0.1)
+        .specular(90.0)
+        .reflective(0.1)
+        .texture("assets/obsidian.png")
+        .build();
+
+    let shroomlight = MaterialBuilder::new()
+        .diffuse(Vector3::new(0.95, 0.6, 0.3))
+        .albedo(0.9, 0.1)
+        .specular(15.0)
+        .texture("assets/shroomlight.png")
+        .emission(Vector3::new(1.0, 0.45, 0.15), 1.2)
+        .build();
+
+    let crimson_nylium = MaterialBuilder::new()
+        .diffuse(Vector3::new(0.5, 0.1, 0.15))
+        .albedo(0.95, 0.05)
+        .specular(5.0)
+        .texture("assets/crimson_nylium.png")
+        .build();
+
+    let crimson_stem = MaterialBuilder::new()
+        .diffuse(Vector3::new(0.4, 0.15, 0.35))
+        .albedo(0.85, 0.15)
+        .specular(15.0)
+        .texture("assets/crimson_stem.png")
+        .build();
+
+    let nether_wart_block = MaterialBuilder::new()
+        .diffuse(Vector3::new(0.5, 0.05, 0.08))
+        .albedo(0.95, 0.05)
+        .specular(8.0)
+        .texture("assets/nether_wart_block.png")
+        .build();
+
+    let portal = MaterialBuilder::new()
+        .diffuse(Vector3::new(0.8, 0.8, 0.8))
+        .albedo(0.9, 0.1)
+        .specular(10.0)
+        .transparent(0.5, 1.3)
+        .texture("assets/portal.png")
+        .build();
+
+    let mut materials = BTreeMap::new();
+    materials.insert("obsidian".to_string(), obsidian);
+    materials.insert("shroomlight".to_string(), shroomlight);
+    materials.insert("crimson_nylium".to_string(), crimson_nylium);
+    materials.insert("crimson_stem".to_string(), crimson_stem);
+    materials.insert("nether_wart_block".to_string(), nether_wart_block);
+    materials.insert("portal".to_string(), portal);
+
+    materials
+}
+
+fn default_objects() -> Vec<ObjectDef> {
+    let cube = |x: f32, y: f32, z: f32, material: &str| ObjectDef {
+        center: Vector3::new(x, y, z),
+        size: Vector3::new(1.5, 1.5, 1.5),
+        material: material.to_string(),
+        overrides: Vec::new(),
+        subtractive: false,
     };
 
-    let crimson_nylium = Material {
-        diffuse: Vector3::new(0.5, 0.1, 0.15),
-        albedo: [0.95, 0.05],
-        specular: 5.0,
-        reflectivity: 0.0,
-        transparency: 0.0,
-        refractive_index: 0.0,
-        texture: Some("assets/crimson_nylium.png".to_string()),
-        normal_map_id: None,
-        emission: Vector3::zero(),
-        emission_strength: 0.0,
+    vec![
+        cube(-2.5, 0.0, 0.0, "obsidian"),
+        cube(0.0, 0.0, -1.0, "shroomlight"),
+        cube(2.5, 0.0, 0.0, "crimson_nylium"),
+        cube(-1.5, 0.0, 2.0, "crimson_stem"),
+        cube(1.5, 0.0, 2.0, "nether_wart_block"),
+        cube(0.0, 0.0, 3.0, "portal"),
+    ]
+}
+
+/// Escena por defecto: materiales y luz definidos en código, cubos desde `scene.txt`
+fn default_scene() -> Scene {
+    let materials = default_materials();
+
+    let objects = if std::path::Path::new(SCENE_FILE).exists() {
+        match load_scene_from_file(SCENE_FILE, &materials) {
+            Ok(objects) => objects,
+            Err(e) => {
+                eprintln!("Error loading scene: {}", e);
+                eprintln!("Using default scene instead.");
+                default_objects()
+            }
+        }
+    } else {
+        default_objects()
     };
 
-    let crimson_stem = Material {
-        diffuse: Vector3::new(0.4, 0.15, 0.35),
-        albedo: [0.85, 0.15],
-        specular: 15.0,
-        reflectivity: 0.0,
-        transparency: 0.0,
-        refractive_index: 0.0,
-        texture: Some("assets/crimson_stem.png".to_string()),
-        normal_map_id: None,
-        emission: Vector3::zero(),
-        emission_strength: 0.0,
+    let light1 = Light::new(
+        Vector3::new(5.0, 8.0, 5.0),
+        Vector3::new(1.0, 0.7, 0.5),
+        1.3,
+    );
+
+    let skybox = if std::path::Path::new(SKYBOX_FILE).exists() {
+        Some(SKYBOX_FILE.to_string())
+    } else {
+        None
     };
 
-    let nether_wart_block = Material {
-        diffuse: Vector3::new(0.5, 0.05, 0.08),
-        albedo: [0.95, 0.05],
-        specular: 8.0,
-        reflectivity: 0.0,
-        transparency: 0.0,
-        refractive_index: 0.0,
-        texture: Some("assets/nether_wart_block.png".to_string()),
-        normal_map_id: None,
-        emission: Vector3::zero(),
-        emission_strength: 0.0,
+    Scene {
+        materials,
+        objects,
+        lights: vec![light1],
+        camera: CameraSetup {
+            eye: Vector3::new(0.0, 2.0, 8.0),
+            center: Vector3::new(0.0, 0.0, 0.0),
+            up: Vector3::new(0.0, 1.0, 0.0),
+        },
+        skybox,
+        sky_blend: None,
+        animations: Vec::new(),
+        render: RenderSetup::default(),
+        prototypes: BTreeMap::new(),
+        instances: Vec::new(),
+    }
+}
+
+/// Exporta el render por lotes; la extensión `.hdr` conserva la radiancia sin recortar
+fn export_render(framebuffer: &Framebuffer, path: &str) -> Result<(), String> {
+    match &framebuffer.hdr_buffer {
+        Some(pixels) if hdr::is_hdr_path(path) => {
+            hdr::write_hdr(path, framebuffer.width, framebuffer.height, pixels)
+        }
+        _ => {
+            framebuffer.color_buffer.export_image(path);
+            Ok(())
+        }
+    }
+}
+
+/// Vuelta completa de la cámara alrededor de su centro en `frames` pasos, guardando cada
+/// fotograma como `<dir>/frame_0000.png` para montarlos después en un vídeo
+fn render_turntable(
+    framebuffer: &mut Framebuffer,
+    bvh: &BVHNode,
+    objects: &[Cube],
+    camera: &mut Camera,
+    lights: &[Light],
+    texture_manager: &TextureManager,
+    config: &RenderConfig,
+    skybox_texture: Option<TextureId>,
+    ao_bake: Option<&AOBake>,
+    options: &cli::CliOptions,
+    frames: u32,
+) -> Result<(), String> {
+    let dir = &options.turntable_dir;
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir, e))?;
+
+    camera.place_on_orbit(
+        options.turntable_radius,
+        options.turntable_pitch.map(f32::to_radians),
+    );
+    let step = 2.0 * PI / frames as f32;
+    // Cada fotograma se expone por su cuenta, como el render de una sola imagen
+    let mut config = config.clone();
+    let mut auto_exposure = options
+        .auto_exposure
+        .then(|| AutoExposure::new(config.exposure));
+
+    for frame in 0..frames {
+        let start = std::time::Instant::now();
+        framebuffer.clear();
+        render(
+            framebuffer,
+            bvh,
+            objects,
+            camera,
+            lights,
+            texture_manager,
+            &config,
+            skybox_texture,
+            ao_bake,
+            options.crop,
+        );
+        if let (Some(auto), Some(pixels)) = (auto_exposure.as_mut(), &framebuffer.hdr_buffer) {
+            auto.measure(pixels);
+            config.exposure = auto.settle();
+            resolve_hdr(framebuffer, &config);
+        }
+
+        let path = format!("{}/frame_{:04}.png", dir, frame);
+        export_render(framebuffer, &path)?;
+        println!(
+            "Frame {}/{} saved to {} ({} ms)",
+            frame + 1,
+            frames,
+            path,
+            start.elapsed().as_millis()
+        );
+
+        camera.orbit(step, 0.0);
+    }
+
+    Ok(())
+}
+
+/// Escribe los AOV junto a la salida del render (`render_normal.hdr`, `render_depth.hdr` y
+/// `render_albedo.hdr`). La normal se lleva a [0, 1] y los píxeles sin impacto quedan negros.
+fn export_aovs(framebuffer: &Framebuffer, output: &str) -> Result<Vec<String>, String> {
+    let Some(aovs) = &framebuffer.aovs else {
+        return Ok(Vec::new());
     };
 
-    let portal = Material {
-        diffuse: Vector3::new(0.8, 0.8, 0.8),
-        albedo: [0.9, 0.1],
-        specular: 10.0,
-        reflectivity: 0.0,
-        transparency: 0.5,
-        refractive_index: 1.3,
-        texture: Some("assets/portal.png".to_string()),
-        normal_map_id: None,
-        emission: Vector3::zero(),
-        emission_strength: 0.0,
+    let stem = std::path::Path::new(output).with_extension("");
+    let stem = stem.display();
+    let normal: Vec<Vector3> = aovs
+        .normal
+        .iter()
+        .map(|&n| {
+            if n == Vector3::zero() {
+                n
+            } else {
+                n * 0.5 + Vector3::one() * 0.5
+            }
+        })
+        .collect();
+    let depth: Vec<Vector3> = aovs
+        .depth
+        .iter()
+        .map(|&d| {
+            let d = if d.is_finite() { d } else { 0.0 };
+            Vector3::new(d, d, d)
+        })
+        .collect();
+
+    let mut written = Vec::new();
+    for (name, pixels) in [
+        ("normal", &normal),
+        ("depth", &depth),
+        ("albedo", &aovs.albedo),
+    ] {
+        let path = format!("{}_{}.hdr", stem, name);
+        hdr::write_hdr(&path, framebuffer.width, framebuffer.height, pixels)?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// Guarda el frame actual en `screenshots/` con la hora Unix en el nombre
+fn save_screenshot(framebuffer: &Framebuffer) -> Result<String, String> {
+    std::fs::create_dir_all(SCREENSHOT_DIR)
+        .map_err(|e| format!("Failed to create {}: {}", SCREENSHOT_DIR, e))?;
+
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| format!("Invalid system time: {}", e))?;
+    let path = format!(
+        "{}/screenshot_{}_{:03}.png",
+        SCREENSHOT_DIR,
+        timestamp.as_secs(),
+        timestamp.subsec_millis()
+    );
+
+    framebuffer.color_buffer.export_image(&path);
+    Ok(path)
+}
+
+/// Escena inicial y ruta donde se guardará con F5.
+/// `--scene` acepta una escena completa (`.scn`/`.json`) o una lista de cubos como `scene.txt`.
+/// Skyboxes entre los que alterna `B`: el de la escena primero, después cualquier imagen de
+/// `assets/` con "skybox" en el nombre y al final `None`, el degradado procedural
+fn skybox_candidates(scene_skybox: Option<TextureId>) -> Vec<Option<TextureId>> {
+    let mut found: Vec<String> = std::fs::read_dir(ASSET_DIR)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| {
+                    path.file_name()
+                        .is_some_and(|name| name.to_string_lossy().contains("skybox"))
+                })
+                .map(|path| path.display().to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    found.sort();
+
+    let mut candidates = vec![scene_skybox];
+    for id in found.iter().map(|path| Some(TextureId::from_path(path))) {
+        if !candidates.contains(&id) {
+            candidates.push(id);
+        }
+    }
+    if !candidates.contains(&None) {
+        candidates.push(None);
+    }
+    candidates
+}
+
+fn initial_scene(scene_arg: Option<&str>) -> (Scene, String) {
+    let load_full = |path: &str| match Scene::load(path) {
+        Ok(scene) => scene,
+        Err(e) => {
+            eprintln!("Error loading scene: {}", e);
+            eprintln!("Using default scene instead.");
+            default_scene()
+        }
     };
 
-    let mut materials = std::collections::HashMap::new();
-    materials.insert("obsidian".to_string(), obsidian.clone());
-    materials.insert("shroomlight".to_string(), shroomlight.clone());
-    materials.insert("crimson_nylium".to_string(), crimson_nylium.clone());
-    materials.insert("crimson_stem".to_string(), crimson_stem.clone());
-    materials.insert("nether_wart_block".to_string(), nether_wart_block.clone());
-    materials.insert("portal".to_string(), portal.clone());
-
-    let objects = if std::path::Path::new("scene.txt").exists() {
-        match load_scene_from_file("scene.txt", &materials) {
-            Ok(cubes) => cubes,
-            Err(e) => {
-                eprintln!("Error loading scene: {}", e);
-                eprintln!("Using default scene instead.");
-                vec![
-                    Cube::new(Vector3::new(-2.5, 0.0, 0.0), 1.5, obsidian),
-                    Cube::new(Vector3::new(0.0, 0.0, -1.0), 1.5, shroomlight),
-                    Cube::new(Vector3::new(2.5, 0.0, 0.0), 1.5, crimson_nylium),
-                    Cube::new(Vector3::new(-1.5, 0.0, 2.0), 1.5, crimson_stem),
-                    Cube::new(Vector3::new(1.5, 0.0, 2.0), 1.5, nether_wart_block),
-                    Cube::new(Vector3::new(0.0, 0.0, 3.0), 1.5, portal),
-                ]
+    match scene_arg {
+        Some(path) if path.ends_with(".scn") || path.ends_with(".json") => {
+            (load_full(path), path.to_string())
+        }
+        Some(path) => {
+            let mut scene = default_scene();
+            match load_scene_files(path, &scene.materials) {
+                Ok(objects) => scene.objects = objects,
+                Err(e) => {
+                    eprintln!("Error loading scene: {}", e);
+                    eprintln!("Using default scene instead.");
+                }
             }
+            (scene, FULL_SCENE_FILE.to_string())
         }
+        None => match full_scene_path() {
+            Some(path) => (load_full(path), path.to_string()),
+            None => (default_scene(), FULL_SCENE_FILE.to_string()),
+        },
+    }
+}
+
+fn main() {
+    let options = match cli::parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("{}", cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    if options.help {
+        println!("{}", cli::USAGE);
+        return;
+    }
+
+    // Modo de medición sin ventana: `cargo run --release -- --bench`
+    if options.bench {
+        bench::run(options.bvh_leaf_size, options.ao);
+        return;
+    }
+
+    let (mut scene, scene_path) = initial_scene(options.scene.as_deref());
+
+    // La línea de comandos manda sobre la escena, y la escena sobre los valores por defecto
+    let window_width = options
+        .width
+        .or(scene.render.width)
+        .unwrap_or(cli::DEFAULT_WIDTH);
+    let window_height = options
+        .height
+        .or(scene.render.height)
+        .unwrap_or(cli::DEFAULT_HEIGHT);
+
+    let mut window = if options.headless {
+        None
+    } else {
+        Some(
+            raylib::init()
+                .size(window_width, window_height)
+                .title("Raytracer - Nether Crimson Forest")
+                .resizable()
+                .log_level(TraceLogLevel::LOG_WARNING)
+                .build(),
+        )
+    };
+
+    // Las preferencias de vista solo se recuperan en la ventana; el render por lotes
+    // depende únicamente de sus argumentos y de la escena
+    let mut settings = if window.is_some() {
+        ViewSettings::load(SETTINGS_FILE)
+    } else {
+        ViewSettings::default()
+    };
+    // Un campo de visión fijado en la escena tiene prioridad sobre el guardado, pero no lo
+    // reemplaza: solo `[`/`]` cambian la preferencia
+    let mut effective_fov = scene.render.fov.unwrap_or(settings.fov_degrees);
+
+    let mut skybox_texture = match &scene.skybox {
+        Some(path) if std::path::Path::new(path).exists() => Some(TextureId::from_path(path)),
+        _ => None,
+    };
+    // Solo la ventana puede cambiar de skybox, así que sin ella no se cargan los demás
+    let skyboxes = if window.is_some() {
+        skybox_candidates(skybox_texture)
     } else {
-        vec![
-            Cube::new(Vector3::new(-2.5, 0.0, 0.0), 1.5, obsidian),
-            Cube::new(Vector3::new(0.0, 0.0, -1.0), 1.5, shroomlight),
-            Cube::new(Vector3::new(2.5, 0.0, 0.0), 1.5, crimson_nylium),
-            Cube::new(Vector3::new(-1.5, 0.0, 2.0), 1.5, crimson_stem),
-            Cube::new(Vector3::new(1.5, 0.0, 2.0), 1.5, nether_wart_block),
-            Cube::new(Vector3::new(0.0, 0.0, 3.0), 1.5, portal),
-        ]
+        vec![skybox_texture]
     };
+    let mut skybox_idx = match settings.sky.as_deref() {
+        Some(PROCEDURAL_SKY) => skyboxes.iter().position(Option::is_none),
+        Some(path) => skyboxes
+            .iter()
+            .position(|sky| sky.is_some_and(|id| id.path() == path)),
+        None => None,
+    }
+    .unwrap_or(0);
+    skybox_texture = skyboxes[skybox_idx];
+
+    let mut texture_ids = scene.texture_ids();
+    texture_ids.extend(skyboxes.iter().flatten());
+    // Una mezcla con un archivo que no existe se ignora, igual que un skybox ausente
+    let sky_blend = scene
+        .sky_blend
+        .as_ref()
+        .filter(|blend| std::path::Path::new(&blend.texture).exists());
+    if let Some(blend) = &scene.sky_blend
+        && sky_blend.is_none()
+    {
+        eprintln!("Warning: sky blend texture '{}' not found", blend.texture);
+    }
+    let sky_blend_texture = sky_blend.map(|blend| TextureId::from_path(&blend.texture));
+    texture_ids.extend(sky_blend_texture);
 
+    // Sin ventana no hay contexto de GPU: solo se cargan las copias en CPU
+    let mut texture_manager = TextureManager::new();
+    texture_manager.set_missing_placeholder(options.placeholder_textures);
+    texture_manager.set_memory_budget(options.texture_budget_mb.map(|mb| mb * 1024 * 1024));
+    let gpu = window
+        .as_mut()
+        .map(|(window, raylib_thread)| (window, &*raylib_thread));
+    for result in texture_manager.load_textures(gpu, &texture_ids) {
+        if let Err(e) = result {
+            eprintln!("Warning: {}", e);
+        }
+    }
+
+    let mut framebuffer = Framebuffer::new(window_width, window_height);
+    framebuffer.set_background_color(Color::new(51, 13, 13, 255));
+
+    let mut objects = scene.build_objects();
+
+    // Sin animaciones la escena es estática y nunca se actualiza el BVH
+    let animator = Animator::new(&scene.animations, &objects);
+    animator.update(0.0, &mut objects);
+
+    let instances = scene.build_instances(options.bvh_leaf_size);
     let mut indices: Vec<usize> = (0..objects.len()).collect();
-    let bvh = BVHNode::build(&objects, &mut indices);
+    let mut bvh =
+        BVHNode::build_with_instances(&objects, &mut indices, &instances, options.bvh_leaf_size);
 
-    let mut camera = Camera::new(
-        Vector3::new(0.0, 2.0, 8.0),
-        Vector3::new(0.0, 0.0, 0.0),
-        Vector3::new(0.0, 1.0, 0.0),
-    );
+    if let Some(path) = &options.export_bvh {
+        match bvh.export(path) {
+            Ok(()) => println!("BVH written to {}", path),
+            Err(e) => eprintln!("Error exporting BVH: {}", e),
+        }
+    }
+
+    let mut render_config =
+        RenderConfig::new(window_width, window_height, effective_fov.to_radians());
+    render_config.threads = options.threads;
+    render_config.up_axis = options.up_axis.or(scene.render.up_axis).unwrap_or_default();
+    render_config.sky_blend = sky_blend_texture.zip(sky_blend.map(|blend| blend.factor(0.0)));
+    // Los renders sin ventana son ya el frame final; la ventana empieza por la vista previa
+    render_config.samples_per_pixel = options.final_samples.unwrap_or(options.samples);
+    render_config.sample_pattern = options.sample_pattern;
+    render_config.sample_seed = options.seed;
+    render_config.ibl_strength = options.ibl_strength;
+    if options.irradiance_strength > 0.0 {
+        render_config.irradiance_strength = options.irradiance_strength;
+        render_config.irradiance_probe =
+            Some(sky_probe(&texture_manager, skybox_texture, &render_config));
+    }
+    render_config.transparent_shadows = options.transparent_shadows;
+    render_config.ray_differentials = options.ray_differentials;
+    render_config.shadow_jitter = options.shadow_jitter;
+    render_config.shadow_jitter_samples = options.shadow_jitter_samples;
+    render_config.tone_map = options.tone_map.unwrap_or(settings.tone_map);
+    render_config.exposure = options.exposure.unwrap_or(settings.exposure);
+    render_config.denoiser = options.denoiser;
+    render_config.max_ray_distance = options.max_distance;
+    render_config.near_distance = options.near;
+    render_config.energy_conserving = options.energy_conserving;
+    render_config.clamp_bounces = options.clamp_bounces;
+    render_config.background = options.background;
+    render_config.vignette_strength = options.vignette;
+    render_config.debug_mode = options.debug_mode;
+    render_config.depth_range = options.depth_range;
+    render_config.ao.enabled = options.ao;
+
+    // La oclusión horneada deja de ser válida cuando los cubos se mueven
+    if !animator.is_empty() {
+        render_config.ao.force_per_frame = true;
+    }
+
+    // La escena es estática, así que la oclusión se hornea una sola vez
+    let mut ao_bake = if render_config.ao.enabled && !render_config.ao.force_per_frame {
+        Some(AOBake::bake(&bvh, &objects, &render_config.ao))
+    } else {
+        None
+    };
+
+    let mut camera = scene.camera.to_camera();
+    camera.up_axis = render_config.up_axis;
 
     let rotation_speed = PI / 100.0;
     let zoom_speed = 0.1;
 
-    let light1 = Light::new(
-        Vector3::new(5.0, 8.0, 5.0),
-        Vector3::new(1.0, 0.7, 0.5),
-        1.3,
+    // Con --no-emissive-lights los objetos emisivos solo brillan, sin iluminar a los demás.
+    // Las luces derivadas se guardan aparte y solo se recalculan cuando cambian los objetos;
+    // las de la escena se evalúan en cada instante para el parpadeo
+    let emissive_lights = options.emissive_lights;
+    let derive_lights = |objects: &[Cube]| {
+        if emissive_lights {
+            derive_emissive_lights(objects)
+        } else {
+            Vec::new()
+        }
+    };
+    let merge_lights = |scene_lights: &[Light], derived: &[Light], time: f32| -> Vec<Light> {
+        scene_lights
+            .iter()
+            .map(|l| l.at_time(time))
+            .chain(derived.iter().cloned())
+            .collect()
+    };
+    let mut derived_lights = derive_lights(&objects);
+    let mut lights = merge_lights(&scene.lights, &derived_lights, 0.0);
+
+    let stats = SceneStats::new(
+        &objects,
+        &instances,
+        &bvh,
+        lights.len(),
+        derived_lights.len(),
     );
+    println!("{}", stats);
+    let flickering = scene.lights.iter().any(|l| l.flicker.is_some());
+
+    // La exposición automática mide la luminancia del búfer HDR del frame anterior
+    let mut auto_exposure = options
+        .auto_exposure
+        .then(|| AutoExposure::new(render_config.exposure));
+    if auto_exposure.is_some() {
+        framebuffer.enable_hdr();
+    }
+    // El filtro de ruido trabaja sobre el búfer HDR y se guía por la normal y la profundidad
+    if render_config.denoiser.is_enabled() {
+        framebuffer.enable_hdr();
+        framebuffer.enable_aovs();
+    }
 
-    let mut lights = vec![light1];
-
-    for obj in objects.iter() {
-        if obj.material.emission_strength > 0.0 {
-            let center = (obj.min_bounds + obj.max_bounds) * 0.5;
-            let emissive_light = Light::new(
-                center,
-                obj.material.emission,
-                obj.material.emission_strength * 2.0,
+    let Some((mut window, raylib_thread)) = window else {
+        if let Some(frames) = options.turntable {
+            if let Err(e) = render_turntable(
+                &mut framebuffer,
+                &bvh,
+                &objects,
+                &mut camera,
+                &lights,
+                &texture_manager,
+                &render_config,
+                skybox_texture,
+                ao_bake.as_ref(),
+                &options,
+                frames,
+            ) {
+                eprintln!("Error rendering turntable: {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        if hdr::is_hdr_path(&options.output) {
+            framebuffer.enable_hdr();
+        }
+        if options.aovs {
+            framebuffer.enable_aovs();
+        }
+        framebuffer.clear();
+        if options.split {
+            let top = camera.top_view();
+            let views = side_by_side(&camera, &top, framebuffer.width, framebuffer.height);
+            render_views(
+                &mut framebuffer,
+                &bvh,
+                &objects,
+                &views,
+                &lights,
+                &texture_manager,
+                &render_config,
+                skybox_texture,
+                ao_bake.as_ref(),
+            );
+        } else if options.stereo > 0.0 {
+            render_anaglyph(
+                &mut framebuffer,
+                &bvh,
+                &objects,
+                &camera,
+                options.stereo,
+                &lights,
+                &texture_manager,
+                &render_config,
+                skybox_texture,
+                ao_bake.as_ref(),
+                options.crop,
+            );
+        } else {
+            render(
+                &mut framebuffer,
+                &bvh,
+                &objects,
+                &camera,
+                &lights,
+                &texture_manager,
+                &render_config,
+                skybox_texture,
+                ao_bake.as_ref(),
+                options.crop,
             );
-            lights.push(emissive_light);
         }
-    }
-
-    let render_config = RenderConfig::new(window_width as i32, window_height as i32, PI / 3.0);
+        // Con un solo frame no hay adaptación: se expone directamente para lo renderizado
+        if let (Some(auto), Some(pixels)) = (auto_exposure.as_mut(), &framebuffer.hdr_buffer) {
+            auto.measure(pixels);
+            render_config.exposure = auto.settle();
+            resolve_hdr(&mut framebuffer, &render_config);
+        }
+        if let Err(e) = export_render(&framebuffer, &options.output) {
+            eprintln!("Error saving render: {}", e);
+            std::process::exit(1);
+        }
+        println!("Render saved to {}", options.output);
+        if options.aovs {
+            match export_aovs(&framebuffer, &options.output) {
+                Ok(paths) => {
+                    for path in paths {
+                        println!("AOV saved to {}", path);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Error saving AOVs: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        return;
+    };
 
     let mut frame_count = 0;
     let mut fps_timer = std::time::Instant::now();
+    let mut selected = 0;
+    let mut selected_light = 0;
+    let mut show_lights = false;
+    let mut snap_orbit = false;
+    let animation_start = std::time::Instant::now();
+    // Con --final-samples cada cambio se traza con las muestras de la vista previa y, cuando
+    // la vista se queda quieta, se repite una vez con las del frame final
+    let mut last_change = std::time::Instant::now();
+    let mut final_pending = false;
 
     while !window.window_should_close() {
-        framebuffer.clear();
-
-        if window.is_key_down(KeyboardKey::KEY_LEFT) {
-            camera.orbit(rotation_speed, 0.0);
-        }
-        if window.is_key_down(KeyboardKey::KEY_RIGHT) {
-            camera.orbit(-rotation_speed, 0.0);
-        }
-        if window.is_key_down(KeyboardKey::KEY_UP) {
-            camera.orbit(0.0, -rotation_speed);
+        let mut scene_changed = false;
+
+        if window.is_key_pressed(KeyboardKey::KEY_O) {
+            snap_orbit = !snap_orbit;
+            println!(
+                "Orbit: {}",
+                if snap_orbit {
+                    format!("snapped to {}°", options.orbit_step)
+                } else {
+                    "continuous".to_string()
+                }
+            );
         }
-        if window.is_key_down(KeyboardKey::KEY_DOWN) {
-            camera.orbit(0.0, rotation_speed);
+        // En la órbita por pasos cada pulsación avanza un salto fijo en lugar de girar sin parar
+        for (key, yaw, pitch) in [
+            (KeyboardKey::KEY_LEFT, 1, 0),
+            (KeyboardKey::KEY_RIGHT, -1, 0),
+            (KeyboardKey::KEY_UP, 0, -1),
+            (KeyboardKey::KEY_DOWN, 0, 1),
+        ] {
+            if snap_orbit {
+                if window.is_key_pressed(key) {
+                    camera.orbit_snapped(yaw, pitch, options.orbit_step.to_radians());
+                }
+            } else if window.is_key_down(key) {
+                camera.orbit(yaw as f32 * rotation_speed, pitch as f32 * rotation_speed);
+            }
         }
         if window.is_key_down(KeyboardKey::KEY_W) {
             camera.zoom(zoom_speed);
@@ -636,28 +2199,337 @@ fn main() {
         if window.is_key_down(KeyboardKey::KEY_S) {
             camera.zoom(-zoom_speed);
         }
+        // Inicio encuadra la escena completa desde el mismo ángulo
+        if window.is_key_pressed(KeyboardKey::KEY_HOME)
+            && camera.frame_objects(
+                &objects,
+                &instances,
+                effective_fov.to_radians(),
+                render_config.aspect_ratio,
+            )
+        {
+            println!(
+                "Camera framed on the scene: center {:.2} {:.2} {:.2}",
+                camera.center.x, camera.center.y, camera.center.z
+            );
+        }
+        if window.is_key_pressed(KeyboardKey::KEY_F5) {
+            scene.camera = CameraSetup::from_camera(&camera);
+            if scene.render.fov.is_some() {
+                scene.render.fov = Some(effective_fov);
+            }
+            match scene.save(&scene_path) {
+                Ok(()) => println!("Scene saved to {}", scene_path),
+                Err(e) => eprintln!("Error saving scene: {}", e),
+            }
+        }
 
-        render(
-            &mut framebuffer,
-            &bvh,
-            &objects,
-            &camera,
-            &lights,
-            &texture_manager,
-            &render_config,
-            skybox_texture.clone(),
-        );
+        if window.is_key_pressed(KeyboardKey::KEY_F2) {
+            render_config.debug_mode = render_config.debug_mode.next();
+            println!("Debug mode: {}", render_config.debug_mode.name());
+            scene_changed = true;
+        }
+        if window.is_key_pressed(KeyboardKey::KEY_F) {
+            render_config.focus_peaking = !render_config.focus_peaking;
+            println!(
+                "Focus peaking: {}",
+                if render_config.focus_peaking {
+                    "on"
+                } else {
+                    "off"
+                }
+            );
+            scene_changed = true;
+        }
+        if window.is_key_pressed(KeyboardKey::KEY_L) {
+            show_lights = !show_lights;
+            println!("Light markers: {}", if show_lights { "on" } else { "off" });
+        }
+        if window.is_key_pressed(KeyboardKey::KEY_B) {
+            skybox_idx = (skybox_idx + 1) % skyboxes.len();
+            skybox_texture = skyboxes[skybox_idx];
+            settings.sky = Some(match skybox_texture {
+                Some(id) => id.path(),
+                None => PROCEDURAL_SKY.to_string(),
+            });
+            println!("Skybox: {}", settings.sky.as_deref().unwrap_or_default());
+            if render_config.irradiance_probe.is_some() {
+                render_config.irradiance_probe =
+                    Some(sky_probe(&texture_manager, skybox_texture, &render_config));
+            }
+            scene_changed = true;
+        }
+        if window.is_key_pressed(KeyboardKey::KEY_T) {
+            render_config.tone_map = render_config.tone_map.next();
+            settings.tone_map = render_config.tone_map;
+            println!("Tone mapping: {}", render_config.tone_map.name());
+            scene_changed = true;
+        }
+        // Con exposición automática las teclas cambian el brillo buscado en lugar de la exposición
+        for (key, factor) in [
+            (KeyboardKey::KEY_EQUAL, 1.25),
+            (KeyboardKey::KEY_MINUS, 0.8),
+        ] {
+            if !window.is_key_pressed(key) {
+                continue;
+            }
+            if let Some(auto) = auto_exposure.as_mut() {
+                auto.key = (auto.key * factor).clamp(0.01, 1.0);
+                println!("Auto exposure key: {:.3}", auto.key);
+            } else {
+                render_config.exposure = (render_config.exposure * factor).clamp(1.0 / 64.0, 64.0);
+                settings.exposure = render_config.exposure;
+                println!("Exposure: {:.2}", render_config.exposure);
+                // Con el búfer HDR basta con volver a convertir a 8 bits, sin trazar rayos
+                if framebuffer.hdr_buffer.is_some() {
+                    resolve_hdr(&mut framebuffer, &render_config);
+                } else {
+                    scene_changed = true;
+                }
+            }
+        }
+        for (key, step) in [
+            (KeyboardKey::KEY_LEFT_BRACKET, -5.0),
+            (KeyboardKey::KEY_RIGHT_BRACKET, 5.0),
+        ] {
+            if window.is_key_pressed(key) {
+                effective_fov = (effective_fov + step).clamp(20.0, 120.0);
+                settings.fov_degrees = effective_fov;
+                render_config.set_fov(effective_fov.to_radians());
+                println!("FOV: {}°", effective_fov);
+                scene_changed = true;
+            }
+        }
+        if window.is_key_pressed(KeyboardKey::KEY_TAB) && !objects.is_empty() {
+            selected = (selected + 1) % objects.len();
+            print_selection(selected, &scene, &objects);
+        }
+        if window.is_key_pressed(KeyboardKey::KEY_H) && !objects.is_empty() {
+            objects[selected].enabled = !objects[selected].enabled;
+            println!(
+                "Object {} {}",
+                selected,
+                if objects[selected].enabled {
+                    "shown"
+                } else {
+                    "hidden"
+                }
+            );
 
-        framebuffer.swap_buffers(&mut window, &raylib_thread);
+            // La oclusión horneada y las luces emisivas dependen de qué objetos están visibles
+            derived_lights = derive_lights(&objects);
+            lights = merge_lights(
+                &scene.lights,
+                &derived_lights,
+                animation_start.elapsed().as_secs_f32(),
+            );
+            if ao_bake.is_some() {
+                ao_bake = Some(AOBake::bake(&bvh, &objects, &render_config.ao));
+            }
+            scene_changed = true;
+        }
+
+        // Ajustes de la luz de la escena seleccionada con K; F5 los guarda en el archivo
+        if window.is_key_pressed(KeyboardKey::KEY_K) && !scene.lights.is_empty() {
+            selected_light = (selected_light + 1) % scene.lights.len();
+            print_light_selection(selected_light, &scene.lights);
+        }
+        if let Some(light) = scene.lights.get_mut(selected_light) {
+            let mut light_changed = false;
+            for (key, factor) in [
+                (KeyboardKey::KEY_PAGE_UP, 1.25),
+                (KeyboardKey::KEY_PAGE_DOWN, 0.8),
+            ] {
+                if window.is_key_pressed(key) {
+                    light.scale_intensity(factor);
+                    light_changed = true;
+                }
+            }
+            // 1, 2 y 3 suben el rojo, el verde y el azul; con Shift los bajan
+            let shift = window.is_key_down(KeyboardKey::KEY_LEFT_SHIFT)
+                || window.is_key_down(KeyboardKey::KEY_RIGHT_SHIFT);
+            for (channel, key) in [
+                KeyboardKey::KEY_ONE,
+                KeyboardKey::KEY_TWO,
+                KeyboardKey::KEY_THREE,
+            ]
+            .into_iter()
+            .enumerate()
+            {
+                if window.is_key_pressed(key) {
+                    light.adjust_channel(channel, if shift { -0.05 } else { 0.05 });
+                    light_changed = true;
+                }
+            }
+
+            if light_changed {
+                print_light_selection(selected_light, &scene.lights);
+                lights = merge_lights(
+                    &scene.lights,
+                    &derived_lights,
+                    animation_start.elapsed().as_secs_f32(),
+                );
+                scene_changed = true;
+            }
+        }
+
+        let time = animation_start.elapsed().as_secs_f32();
+        if !animator.is_empty() {
+            animator.update(time, &mut objects);
+            bvh.refit(&objects);
+            derived_lights = derive_lights(&objects);
+        }
+        if !animator.is_empty() || flickering {
+            lights = merge_lights(&scene.lights, &derived_lights, time);
+            scene_changed = true;
+        }
+        if let (Some(blend), Some((texture, _))) = (sky_blend, render_config.sky_blend)
+            && blend.is_animated()
+        {
+            render_config.sky_blend = Some((texture, blend.factor(time)));
+            if render_config.irradiance_probe.is_some() {
+                render_config.irradiance_probe =
+                    Some(sky_probe(&texture_manager, skybox_texture, &render_config));
+            }
+            scene_changed = true;
+        }
+
+        // Con la cámara y la escena quietas se vuelve a presentar el último frame sin trazar rayos
+        let changed = camera.is_changed() || scene_changed;
+        if changed {
+            last_change = std::time::Instant::now();
+        }
+        let final_pass = !changed && final_pending && last_change.elapsed() >= FINAL_RENDER_DELAY;
+        if let Some(final_samples) = options.final_samples
+            && (changed || final_pass)
+        {
+            render_config.samples_per_pixel = if final_pass {
+                final_samples
+            } else {
+                options.samples
+            };
+            final_pending = changed;
+        }
+        if changed || final_pass {
+            if options.crop.is_none() {
+                framebuffer.clear();
+            }
+            if options.split {
+                let top = camera.top_view();
+                let views = side_by_side(&camera, &top, framebuffer.width, framebuffer.height);
+                render_views(
+                    &mut framebuffer,
+                    &bvh,
+                    &objects,
+                    &views,
+                    &lights,
+                    &texture_manager,
+                    &render_config,
+                    skybox_texture,
+                    ao_bake.as_ref(),
+                );
+            } else if options.stereo > 0.0 {
+                render_anaglyph(
+                    &mut framebuffer,
+                    &bvh,
+                    &objects,
+                    &camera,
+                    options.stereo,
+                    &lights,
+                    &texture_manager,
+                    &render_config,
+                    skybox_texture,
+                    ao_bake.as_ref(),
+                    options.crop,
+                );
+            } else {
+                render(
+                    &mut framebuffer,
+                    &bvh,
+                    &objects,
+                    &camera,
+                    &lights,
+                    &texture_manager,
+                    &render_config,
+                    skybox_texture,
+                    ao_bake.as_ref(),
+                    options.crop,
+                );
+            }
+            frame_count += 1;
+            texture_manager.end_frame();
+
+            if let (Some(auto), Some(pixels)) = (auto_exposure.as_mut(), &framebuffer.hdr_buffer) {
+                auto.measure(pixels);
+            }
+        }
+
+        // La adaptación continúa con la cámara quieta, reexponiendo el último frame
+        if let Some(auto) = auto_exposure.as_mut() {
+            let exposure = auto.step();
+            if exposure != render_config.exposure {
+                render_config.exposure = exposure;
+                resolve_hdr(&mut framebuffer, &render_config);
+            }
+        }
+
+        // El clic se traduce a píxeles de la imagen, que puede estar escalada o con bandas
+        let viewport = framebuffer.viewport(window.get_screen_width(), window.get_screen_height());
+        if window.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT)
+            && let Some((x, y)) = viewport.to_image(window.get_mouse_x(), window.get_mouse_y())
+        {
+            let steps = debug_ray::debug_ray(
+                x,
+                y,
+                &bvh,
+                &objects,
+                &camera,
+                &lights,
+                &texture_manager,
+                &render_config,
+                skybox_texture,
+                ao_bake.as_ref(),
+            );
+            debug_ray::print_trace(x, y, &steps, &scene.objects);
+
+            // El clic también selecciona el objeto bajo el cursor para H
+            let direction = primary_ray_direction(x as f32, y as f32, &camera, &render_config);
+            let origin = camera.eye + direction * render_config.near_distance;
+            if let Some(idx) = bvh.pick(&objects, &origin, &direction) {
+                selected = idx;
+                print_selection(selected, &scene, &objects);
+            }
+        }
+
+        if window.is_key_pressed(KeyboardKey::KEY_P) {
+            match save_screenshot(&framebuffer) {
+                Ok(path) => println!("Screenshot saved to {}", path),
+                Err(e) => eprintln!("Error saving screenshot: {}", e),
+            }
+        }
+
+        // Los marcadores se dibujan al mostrar el frame, sin tocar la imagen trazada
+        if show_lights {
+            let markers = light_markers(&lights, &camera, &render_config);
+            framebuffer.swap_buffers_with_markers(&mut window, &raylib_thread, &markers);
+        } else {
+            framebuffer.swap_buffers(&mut window, &raylib_thread);
+        }
 
-        frame_count += 1;
         let elapsed = fps_timer.elapsed().as_secs_f32();
         if elapsed >= 2.0 {
-            let fps = frame_count as f32 / elapsed;
-            println!("FPS: {:.1}", fps);
+            if frame_count > 0 {
+                let fps = frame_count as f32 / elapsed;
+                println!("FPS: {:.1}", fps);
+            }
             frame_count = 0;
             fps_timer = std::time::Instant::now();
         }
     }
-}
 
+    // Solo se guardan los ajustes hechos con las teclas: ni `--tonemap`/`--exposure` ni la
+    // exposición automática cambian las preferencias de las próximas sesiones
+    if let Err(e) = settings.save(SETTINGS_FILE) {
+        eprintln!("Error saving settings: {}", e);
+    }
+}
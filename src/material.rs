@@ -1,18 +1,89 @@
 // material.rs
+use crate::noise::NoisePattern;
+use crate::textures::TextureId;
 use raylib::prelude::*;
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "json-scene", derive(serde::Serialize, serde::Deserialize))]
 pub struct Material {
+    #[cfg_attr(feature = "json-scene", serde(with = "crate::json_scene::vec3"))]
     pub diffuse: Vector3,
+    /// Pesos [difuso, especular]: fracción de la luz que devuelve cada término. Junto con
+    /// `reflectivity` y `transparency` deberían sumar como mucho 1; si no, solo el modo de
+    /// conservación de energía los reescala (ver `term_weights`).
     pub albedo: [f32; 2],
+    /// Exponente de Phong, entre 0 y `MAX_SPECULAR_EXPONENT`: más alto, brillo más pequeño.
+    /// 0 deja la superficie sin brillo.
     pub specular: f32,
+    /// Tinte del brillo especular. Blanco deja el color de la luz, como en los dieléctricos;
+    /// los metales usan su propio color
+    #[cfg_attr(
+        feature = "json-scene",
+        serde(default = "default_specular_color", with = "crate::json_scene::vec3")
+    )]
+    pub specular_color: Vector3,
     pub reflectivity: f32,
     pub transparency: f32,
     pub refractive_index: f32,
-    pub texture: Option<String>,
-    pub normal_map_id: Option<String>,
+    /// Flujo de trabajo de metalicidad simplificado: con 1 el difuso desaparece, su peso pasa a
+    /// la reflexión y reflejo y brillo toman el color de la superficie. `None` usa tal cual
+    /// `albedo`, `reflectivity` y `specular_color`.
+    #[cfg_attr(feature = "json-scene", serde(default))]
+    pub metalness: Option<f32>,
+    pub texture: Option<TextureId>,
+    pub normal_map_id: Option<TextureId>,
+    #[cfg_attr(feature = "json-scene", serde(with = "crate::json_scene::vec3"))]
     pub emission: Vector3,
     pub emission_strength: f32,
+    /// Si es verdadero, la emisión se multiplica por el color difuso (o la textura); si es
+    /// falso emite `emission` tal cual, aunque la textura sea oscura
+    #[cfg_attr(feature = "json-scene", serde(default = "default_tint_emission"))]
+    pub tint_emission: bool,
+    /// Separación del índice de refracción entre los canales R, G y B; 0 refracta sin dispersión
+    #[cfg_attr(feature = "json-scene", serde(default))]
+    pub dispersion: f32,
+    /// Ancho en unidades del mundo del bisel simulado en las aristas; 0 deja aristas vivas
+    #[cfg_attr(feature = "json-scene", serde(default))]
+    pub bevel: f32,
+    /// Rugosidad del brillo a lo largo de la tangente U de la cara; junto con `roughness_v`
+    /// activa el brillo anisotrópico de metal cepillado. 0 usa el brillo de Phong.
+    #[cfg_attr(feature = "json-scene", serde(default))]
+    pub roughness_u: f32,
+    /// Rugosidad del brillo a lo largo de la tangente V de la cara
+    #[cfg_attr(feature = "json-scene", serde(default))]
+    pub roughness_v: f32,
+    /// Ancho, en fracción de la cara, del contorno dibujado en los bordes; 0 lo desactiva
+    #[cfg_attr(feature = "json-scene", serde(default))]
+    pub edge_width: f32,
+    #[cfg_attr(
+        feature = "json-scene",
+        serde(default, with = "crate::json_scene::vec3")
+    )]
+    pub edge_color: Vector3,
+    /// Si es verdadero, las caras vistas por detrás se sombrean con la normal invertida en
+    /// lugar de quedar sin luz; pensado para hojas y planos delgados
+    #[cfg_attr(feature = "json-scene", serde(default))]
+    pub two_sided: bool,
+    /// Cuartos de vuelta en sentido horario que se gira la textura en cada cara, para
+    /// alinear bloques con orientación sin rotar la imagen
+    #[cfg_attr(feature = "json-scene", serde(default))]
+    pub uv_quarter_turns: u8,
+    /// Ruido procedural que reemplaza a `diffuse` como color base; una textura lo tapa
+    #[cfg_attr(feature = "json-scene", serde(default))]
+    pub noise: Option<NoisePattern>,
+}
+
+// Exponentes mayores ya no achican el brillo a la vista y solo acercan powf a sus límites
+pub const MAX_SPECULAR_EXPONENT: f32 = 10_000.0;
+
+#[cfg(feature = "json-scene")]
+fn default_tint_emission() -> bool {
+    true
+}
+
+#[cfg(feature = "json-scene")]
+fn default_specular_color() -> Vector3 {
+    Vector3::one()
 }
 
 impl Material {
@@ -23,8 +94,8 @@ impl Material {
         reflectivity: f32,
         transparency: f32,
         refractive_index: f32,
-        texture: Option<String>,
-        normal_map_id: Option<String>,
+        texture: Option<TextureId>,
+        normal_map_id: Option<TextureId>,
         emission: Vector3,
         emission_strength: f32,
     ) -> Self {
@@ -32,45 +103,578 @@ impl Material {
             diffuse,
             albedo,
             specular,
+            specular_color: Vector3::one(),
             reflectivity,
             transparency,
             refractive_index,
+            metalness: None,
             texture,
             normal_map_id,
             emission,
             emission_strength,
+            tint_emission: true,
+            dispersion: 0.0,
+            bevel: 0.0,
+            roughness_u: 0.0,
+            roughness_v: 0.0,
+            edge_width: 0.0,
+            edge_color: Vector3::zero(),
+            two_sided: false,
+            uv_quarter_turns: 0,
+            noise: None,
+        }
+    }
+
+    /// Rechaza exponentes especulares negativos, no finitos o mayores que el máximo, y
+    /// patrones de ruido sin escala o con octavas fuera de rango
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=MAX_SPECULAR_EXPONENT).contains(&self.specular) {
+            return Err(format!(
+                "Specular exponent must be between 0 and {}, got {}",
+                MAX_SPECULAR_EXPONENT, self.specular
+            ));
+        }
+        if let Some(noise) = &self.noise {
+            noise.validate()?;
+        }
+        Ok(())
+    }
+
+    /// Brillo de Phong para `cosine`, el coseno entre la vista y la luz reflejada. Un
+    /// exponente de 0 o menos no tiene brillo: `powf(0.0)` daría 1 en todas las direcciones
+    /// e iluminaría la cara entera. Los exponentes enormes se recortan al máximo.
+    pub fn phong_highlight(&self, cosine: f32) -> f32 {
+        if self.specular.is_nan() || self.specular <= 0.0 {
+            return 0.0;
+        }
+        cosine
+            .max(0.0)
+            .powf(self.specular.min(MAX_SPECULAR_EXPONENT))
+    }
+
+    pub fn is_anisotropic(&self) -> bool {
+        self.roughness_u > 0.0 && self.roughness_v > 0.0
+    }
+
+    /// Si las coordenadas (u, v) de la cara caen en la franja del contorno
+    pub fn is_on_edge(&self, u: f32, v: f32) -> bool {
+        self.edge_width > 0.0 && u.min(1.0 - u).min(v).min(1.0 - v) < self.edge_width
+    }
+
+    /// Normal para sombrear un impacto visto desde `ray_direction`: los materiales de dos
+    /// caras la giran hacia el observador cuando el rayo llega por detrás
+    pub fn facing_normal(&self, normal: Vector3, ray_direction: Vector3) -> Vector3 {
+        if self.two_sided && ray_direction.dot(normal) > 0.0 {
+            -normal
+        } else {
+            normal
+        }
+    }
+
+    /// Coordenadas (u, v) con la rotación de la textura aplicada
+    pub fn rotate_uv(&self, u: f32, v: f32) -> (f32, f32) {
+        match self.uv_quarter_turns % 4 {
+            1 => (v, 1.0 - u),
+            2 => (1.0 - u, 1.0 - v),
+            3 => (1.0 - v, u),
+            _ => (u, v),
+        }
+    }
+
+    /// Color base sin textura en `point` (coordenadas del mundo): el ruido si lo hay o el difuso
+    pub fn base_color(&self, point: Vector3) -> Vector3 {
+        match &self.noise {
+            Some(noise) => noise.color(point),
+            None => self.diffuse,
+        }
+    }
+
+    /// Luz que emite la superficie en un punto cuyo color difuso es `diffuse_color`
+    pub fn emitted(&self, diffuse_color: Vector3) -> Vector3 {
+        if self.emission_strength <= 0.01 {
+            Vector3::zero()
+        } else if self.tint_emission {
+            diffuse_color * self.emission * self.emission_strength
+        } else {
+            self.emission * self.emission_strength
+        }
+    }
+
+    fn metal(&self) -> f32 {
+        self.metalness.map_or(0.0, |m| m.clamp(0.0, 1.0))
+    }
+
+    /// Reflectividad teniendo en cuenta la metalicidad, que mueve el peso difuso a la reflexión
+    pub fn effective_reflectivity(&self) -> f32 {
+        match self.metalness {
+            Some(_) => {
+                let m = self.metal();
+                self.reflectivity * (1.0 - m) + self.albedo[0] * m
+            }
+            None => self.reflectivity,
+        }
+    }
+
+    /// Tinte del brillo especular sobre una superficie de color `diffuse_color`
+    pub fn specular_tint(&self, diffuse_color: Vector3) -> Vector3 {
+        match self.metalness {
+            Some(_) => self.specular_color.lerp(diffuse_color, self.metal()),
+            None => self.specular_color,
         }
     }
 
+    /// Tinte del color reflejado: blanco en los dieléctricos, el de la superficie en los metales
+    pub fn reflection_tint(&self, diffuse_color: Vector3) -> Vector3 {
+        match self.metalness {
+            Some(_) => Vector3::one().lerp(diffuse_color, self.metal()),
+            None => Vector3::one(),
+        }
+    }
+
+    /// Pesos de los términos difuso, especular, reflejado y refractado. Con `conserve_energy`
+    /// se reducen en proporción cuando suman más de 1, para no devolver más luz de la que llega.
+    pub fn term_weights(&self, conserve_energy: bool) -> [f32; 4] {
+        let weights = [
+            self.albedo[0] * (1.0 - self.metal()),
+            self.albedo[1],
+            self.effective_reflectivity(),
+            self.transparency,
+        ];
+        let total: f32 = weights.iter().map(|w| w.max(0.0)).sum();
+        if !conserve_energy || total <= 1.0 {
+            return weights;
+        }
+        weights.map(|w| w.max(0.0) / total)
+    }
+
     pub fn black() -> Self {
         Material {
             diffuse: Vector3::zero(),
             albedo: [0.0, 0.0],
             specular: 0.0,
+            specular_color: Vector3::one(),
             reflectivity: 0.0,
             transparency: 0.0,
             refractive_index: 0.0,
+            metalness: None,
             texture: None,
             normal_map_id: None,
             emission: Vector3::zero(),
             emission_strength: 0.0,
+            tint_emission: true,
+            dispersion: 0.0,
+            bevel: 0.0,
+            roughness_u: 0.0,
+            roughness_v: 0.0,
+            edge_width: 0.0,
+            edge_color: Vector3::zero(),
+            two_sided: false,
+            uv_quarter_turns: 0,
+            noise: None,
+        }
+    }
+}
+
+/// Material difuso gris neutro, sin texturas ni emisión y con índice de refracción 1.0,
+/// pensado para `Material { diffuse: ..., ..Default::default() }`
+impl Default for Material {
+    fn default() -> Self {
+        Material {
+            diffuse: Vector3::new(0.5, 0.5, 0.5),
+            albedo: [1.0, 0.0],
+            refractive_index: 1.0,
+            ..Material::black()
+        }
+    }
+}
+
+/// Construye un `Material` partiendo de valores neutros: difuso gris, sin brillo,
+/// sin texturas y sin emisión. Solo hace falta indicar lo que cambia.
+#[derive(Debug, Clone)]
+pub struct MaterialBuilder {
+    material: Material,
+}
+
+impl MaterialBuilder {
+    pub fn new() -> Self {
+        MaterialBuilder {
+            material: Material::default(),
         }
     }
+
+    pub fn diffuse(mut self, diffuse: Vector3) -> Self {
+        self.material.diffuse = diffuse;
+        self
+    }
+
+    pub fn albedo(mut self, diffuse: f32, specular: f32) -> Self {
+        self.material.albedo = [diffuse, specular];
+        self
+    }
+
+    pub fn specular(mut self, exponent: f32) -> Self {
+        self.material.specular = exponent;
+        self
+    }
+
+    /// Tiñe el brillo especular, por ejemplo con el color de un metal
+    pub fn specular_color(mut self, color: Vector3) -> Self {
+        self.material.specular_color = color;
+        self
+    }
+
+    pub fn reflective(mut self, reflectivity: f32) -> Self {
+        self.material.reflectivity = reflectivity;
+        self
+    }
+
+    /// Metalicidad entre 0 (dieléctrico) y 1 (metal); ver `Material::metalness`
+    pub fn metalness(mut self, metalness: f32) -> Self {
+        self.material.metalness = Some(metalness);
+        self
+    }
+
+    /// Fracción de luz que atraviesa el material con el índice de refracción dado
+    pub fn transparent(mut self, transparency: f32, refractive_index: f32) -> Self {
+        self.material.transparency = transparency;
+        self.material.refractive_index = refractive_index;
+        self
+    }
+
+    pub fn texture(mut self, path: &str) -> Self {
+        self.material.texture = Some(TextureId::from_path(path));
+        self
+    }
+
+    pub fn normal_map(mut self, path: &str) -> Self {
+        self.material.normal_map_id = Some(TextureId::from_path(path));
+        self
+    }
+
+    pub fn emission(mut self, color: Vector3, strength: f32) -> Self {
+        self.material.emission = color;
+        self.material.emission_strength = strength;
+        self
+    }
+
+    /// Con `false` la emisión deja de teñirse con el color difuso o la textura
+    pub fn tint_emission(mut self, tint: bool) -> Self {
+        self.material.tint_emission = tint;
+        self
+    }
+
+    pub fn dispersion(mut self, dispersion: f32) -> Self {
+        self.material.dispersion = dispersion;
+        self
+    }
+
+    pub fn bevel(mut self, width: f32) -> Self {
+        self.material.bevel = width;
+        self
+    }
+
+    /// Brillo anisotrópico con rugosidad distinta a lo largo de U y de V
+    pub fn anisotropic(mut self, roughness_u: f32, roughness_v: f32) -> Self {
+        self.material.roughness_u = roughness_u;
+        self.material.roughness_v = roughness_v;
+        self
+    }
+
+    /// Contorno de `width` (en fracción de la cara) y color `color` en los bordes de cada cara
+    pub fn edges(mut self, width: f32, color: Vector3) -> Self {
+        self.material.edge_width = width;
+        self.material.edge_color = color;
+        self
+    }
+
+    pub fn two_sided(mut self) -> Self {
+        self.material.two_sided = true;
+        self
+    }
+
+    /// Gira la textura `quarter_turns` cuartos de vuelta en sentido horario
+    pub fn uv_rotation(mut self, quarter_turns: u8) -> Self {
+        self.material.uv_quarter_turns = quarter_turns % 4;
+        self
+    }
+
+    pub fn noise(mut self, pattern: NoisePattern) -> Self {
+        self.material.noise = Some(pattern);
+        self
+    }
+
+    pub fn build(self) -> Material {
+        self.material
+    }
+}
+
+impl Default for MaterialBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reduce en proporción los pesos reflejado y refractado de `term_weights` cuando suman más
+/// de 1, para que el vidrio no devuelva más luz de la que recibe. El difuso y el especular no
+/// cambian, ni tampoco los materiales que ya están dentro del presupuesto.
+pub fn clamp_bounce_weights(weights: [f32; 4]) -> [f32; 4] {
+    let [diffuse, specular, reflection, refraction] = weights;
+    let total = reflection.max(0.0) + refraction.max(0.0);
+    if total <= 1.0 {
+        return weights;
+    }
+    [
+        diffuse,
+        specular,
+        reflection.max(0.0) / total,
+        refraction.max(0.0) / total,
+    ]
 }
 
-pub fn vector3_to_color(v: Vector3) -> Color {
-    Color::new(
-        (v.x * 255.0).min(255.0) as u8,
-        (v.y * 255.0).min(255.0) as u8,
-        (v.z * 255.0).min(255.0) as u8,
-        255,
-    )
+/// Brillo anisotrópico de Ashikhmin-Shirley (sin el término de Fresnel) para vectores
+/// normalizados. La rugosidad de cada eje se convierte en un exponente de Blinn-Phong, así
+/// que con `roughness_u == roughness_v` el brillo es el isotrópico `(n·h)^e`.
+pub fn anisotropic_highlight(
+    normal: Vector3,
+    tangent: Vector3,
+    view: Vector3,
+    light: Vector3,
+    roughness_u: f32,
+    roughness_v: f32,
+) -> f32 {
+    let half = (view + light).normalized();
+    let n_dot_h = normal.dot(half).max(0.0);
+    let sin2 = 1.0 - n_dot_h * n_dot_h;
+    if n_dot_h <= 0.0 {
+        return 0.0;
+    }
+
+    let exponent_u = roughness_to_exponent(roughness_u);
+    let exponent_v = roughness_to_exponent(roughness_v);
+    let exponent = if sin2 <= 1e-6 {
+        // Con el medio vector sobre la normal ambos ejes dan el máximo
+        exponent_u.min(exponent_v)
+    } else {
+        let bitangent = normal.cross(tangent);
+        let h_u = half.dot(tangent);
+        let h_v = half.dot(bitangent);
+        (exponent_u * h_u * h_u + exponent_v * h_v * h_v) / sin2
+    };
+
+    n_dot_h.powf(exponent)
+}
+
+fn roughness_to_exponent(roughness: f32) -> f32 {
+    let r = roughness.clamp(0.01, 1.0);
+    2.0 / (r * r) - 2.0
 }
 
-pub fn color_to_vector3(color: Color) -> Vector3 {
-    Vector3::new(
-        color.r as f32 / 255.0,
-        color.g as f32 / 255.0,
-        color.b as f32 / 255.0,
-    )
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder_only_overrides_what_is_set() {
+        let material = MaterialBuilder::new()
+            .diffuse(Vector3::new(0.8, 0.2, 0.1))
+            .texture("assets/portal.png")
+            .reflective(0.1)
+            .build();
+
+        assert_eq!(material.diffuse, Vector3::new(0.8, 0.2, 0.1));
+        assert_eq!(
+            material.texture.map(TextureId::path).as_deref(),
+            Some("assets/portal.png")
+        );
+        assert_eq!(material.reflectivity, 0.1);
+        assert_eq!(material.albedo, [1.0, 0.0]);
+        assert_eq!(material.transparency, 0.0);
+        assert_eq!(material.emission_strength, 0.0);
+        assert!(material.normal_map_id.is_none());
+    }
+
+    #[test]
+    fn default_is_neutral_gray() {
+        let material = Material {
+            specular: 10.0,
+            ..Default::default()
+        };
+
+        assert_eq!(material.diffuse, Vector3::new(0.5, 0.5, 0.5));
+        assert_eq!(material.albedo, [1.0, 0.0]);
+        assert_eq!(material.refractive_index, 1.0);
+        assert_eq!(material.specular, 10.0);
+        assert!(material.texture.is_none());
+        assert_eq!(material.emission, Vector3::zero());
+        assert_eq!(material.specular_color, Vector3::one());
+    }
+
+    #[test]
+    fn equal_roughness_is_isotropic() {
+        let normal = Vector3::new(0.0, 1.0, 0.0);
+        let tangent = Vector3::new(1.0, 0.0, 0.0);
+        let view = Vector3::new(0.3, 1.0, 0.1).normalized();
+        let light = Vector3::new(-0.1, 1.0, 0.2).normalized();
+
+        let highlight = anisotropic_highlight(normal, tangent, view, light, 0.3, 0.3);
+        let half = (view + light).normalized();
+        let blinn = normal.dot(half).powf(roughness_to_exponent(0.3));
+        assert!((highlight - blinn).abs() < 1e-4);
+    }
+
+    #[test]
+    fn highlight_stretches_along_the_rough_axis() {
+        let normal = Vector3::new(0.0, 1.0, 0.0);
+        let tangent = Vector3::new(1.0, 0.0, 0.0);
+        let light = Vector3::new(0.0, 1.0, 0.0);
+        let along_u = Vector3::new(0.4, 1.0, 0.0).normalized();
+        let along_v = Vector3::new(0.0, 1.0, 0.4).normalized();
+
+        // Rugoso en U y pulido en V: el brillo se extiende a lo largo de U
+        let u = anisotropic_highlight(normal, tangent, along_u, light, 0.6, 0.1);
+        let v = anisotropic_highlight(normal, tangent, along_v, light, 0.6, 0.1);
+        assert!(u > v * 10.0);
+    }
+
+    #[test]
+    fn edge_band_follows_width() {
+        let material = MaterialBuilder::new().edges(0.05, Vector3::zero()).build();
+        assert!(material.is_on_edge(0.02, 0.5));
+        assert!(material.is_on_edge(0.5, 0.97));
+        assert!(!material.is_on_edge(0.5, 0.5));
+        assert!(!Material::default().is_on_edge(0.0, 0.0));
+    }
+
+    #[test]
+    fn conserving_weights_never_exceed_one() {
+        let material = MaterialBuilder::new()
+            .albedo(0.9, 0.3)
+            .reflective(0.4)
+            .transparent(0.4, 1.5)
+            .build();
+
+        assert_eq!(material.term_weights(false), [0.9, 0.3, 0.4, 0.4]);
+        let weights = material.term_weights(true);
+        assert!((weights.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+        assert!((weights[0] / weights[1] - 3.0).abs() < 1e-4);
+
+        // Los materiales que ya conservan la energía no cambian
+        let matte = MaterialBuilder::new().albedo(0.6, 0.2).build();
+        assert_eq!(matte.term_weights(true), [0.6, 0.2, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn bounce_clamp_only_touches_overbright_glass() {
+        let glass = MaterialBuilder::new()
+            .albedo(0.5, 0.2)
+            .reflective(0.6)
+            .transparent(0.9, 1.5)
+            .build();
+        let [diffuse, specular, reflection, refraction] =
+            clamp_bounce_weights(glass.term_weights(false));
+        assert_eq!([diffuse, specular], [0.5, 0.2]);
+        assert!((reflection + refraction - 1.0).abs() < 1e-6);
+        assert!((reflection / refraction - 0.6 / 0.9).abs() < 1e-5);
+
+        let window = MaterialBuilder::new()
+            .reflective(0.2)
+            .transparent(0.7, 1.5)
+            .build();
+        let weights = window.term_weights(false);
+        assert_eq!(clamp_bounce_weights(weights), weights);
+    }
+
+    #[test]
+    fn metals_reflect_their_own_color_instead_of_diffusing() {
+        let gold = Vector3::new(1.0, 0.8, 0.3);
+        let plain = MaterialBuilder::new()
+            .albedo(0.8, 0.2)
+            .reflective(0.1)
+            .build();
+        assert_eq!(plain.term_weights(false), [0.8, 0.2, 0.1, 0.0]);
+        assert_eq!(plain.specular_tint(gold), Vector3::one());
+        assert_eq!(plain.reflection_tint(gold), Vector3::one());
+
+        let metal = MaterialBuilder::new()
+            .albedo(0.8, 0.2)
+            .reflective(0.1)
+            .metalness(1.0)
+            .build();
+        assert_eq!(metal.term_weights(false), [0.0, 0.2, 0.8, 0.0]);
+        assert_eq!(metal.specular_tint(gold), gold);
+        assert_eq!(metal.reflection_tint(gold), gold);
+
+        // A medio camino el difuso cede la mitad de su peso a la reflexión
+        let half = MaterialBuilder::new()
+            .albedo(0.8, 0.2)
+            .reflective(0.1)
+            .metalness(0.5)
+            .build();
+        let [diffuse, _, reflection, _] = half.term_weights(false);
+        assert!((diffuse - 0.4).abs() < 1e-6 && (reflection - 0.45).abs() < 1e-6);
+    }
+
+    #[test]
+    fn two_sided_materials_face_the_viewer() {
+        let normal = Vector3::new(0.0, 0.0, 1.0);
+        let from_front = Vector3::new(0.0, 0.0, -1.0);
+        let from_behind = Vector3::new(0.0, 0.0, 1.0);
+
+        let one_sided = Material::default();
+        assert_eq!(one_sided.facing_normal(normal, from_behind), normal);
+
+        let leaf = MaterialBuilder::new().two_sided().build();
+        assert_eq!(leaf.facing_normal(normal, from_front), normal);
+        assert_eq!(leaf.facing_normal(normal, from_behind), -normal);
+    }
+
+    #[test]
+    fn untinted_emission_ignores_the_diffuse_color() {
+        let dark_texel = Vector3::new(0.1, 0.05, 0.0);
+        let glow = Vector3::new(1.0, 0.5, 0.2);
+
+        let tinted = MaterialBuilder::new().emission(glow, 2.0).build();
+        assert_eq!(tinted.emitted(dark_texel), dark_texel * glow * 2.0);
+
+        let pure = MaterialBuilder::new()
+            .emission(glow, 2.0)
+            .tint_emission(false)
+            .build();
+        assert_eq!(pure.emitted(dark_texel), glow * 2.0);
+
+        assert_eq!(Material::default().emitted(dark_texel), Vector3::zero());
+    }
+
+    #[test]
+    fn zero_specular_has_no_highlight() {
+        let matte = MaterialBuilder::new().specular(0.0).build();
+        for cosine in [1.0, 0.5, 0.0, -0.5] {
+            assert_eq!(matte.phong_highlight(cosine), 0.0);
+        }
+
+        let shiny = MaterialBuilder::new().specular(10.0).build();
+        assert_eq!(shiny.phong_highlight(1.0), 1.0);
+        assert!(shiny.phong_highlight(0.9) < 0.35);
+        assert_eq!(shiny.phong_highlight(-0.5), 0.0);
+
+        let huge = MaterialBuilder::new().specular(1e30).build();
+        assert_eq!(huge.phong_highlight(1.0), 1.0);
+        assert!(huge.phong_highlight(0.999).is_finite());
+        assert!(huge.validate().is_err());
+        assert!(
+            MaterialBuilder::new()
+                .specular(-1.0)
+                .build()
+                .validate()
+                .is_err()
+        );
+        assert!(
+            MaterialBuilder::new()
+                .specular(f32::NAN)
+                .build()
+                .validate()
+                .is_err()
+        );
+        assert!(matte.validate().is_ok());
+    }
 }
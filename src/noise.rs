@@ -0,0 +1,178 @@
+// noise.rs - Procedural Perlin noise pattern for organic surfaces without texture files
+use raylib::prelude::Vector3;
+
+// Más octavas ya no se distinguen a la resolución de un píxel y solo cuestan tiempo
+pub const MAX_OCTAVES: u32 = 8;
+
+/// Color procedural que mezcla dos colores según ruido de Perlin evaluado en el punto de
+/// impacto, en coordenadas del mundo: las caras vecinas continúan el mismo patrón
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "json-scene", derive(serde::Serialize, serde::Deserialize))]
+pub struct NoisePattern {
+    /// Frecuencia de la primera octava: manchas de unas `1 / scale` unidades del mundo
+    pub scale: f32,
+    /// Capas de ruido sumadas, cada una al doble de frecuencia y la mitad de peso
+    pub octaves: u32,
+    #[cfg_attr(feature = "json-scene", serde(with = "crate::json_scene::vec3"))]
+    pub low: Vector3,
+    #[cfg_attr(feature = "json-scene", serde(with = "crate::json_scene::vec3"))]
+    pub high: Vector3,
+}
+
+impl NoisePattern {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.scale > 0.0 && self.scale.is_finite()) {
+            return Err(format!("Noise scale must be positive, got {}", self.scale));
+        }
+        if !(1..=MAX_OCTAVES).contains(&self.octaves) {
+            return Err(format!(
+                "Noise octaves must be between 1 and {}, got {}",
+                MAX_OCTAVES, self.octaves
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn color(&self, point: Vector3) -> Vector3 {
+        let t = fractal_noise(point * self.scale, self.octaves);
+        self.low.lerp(self.high, t)
+    }
+}
+
+/// Suma de `octaves` capas de Perlin llevada a [0, 1]
+pub fn fractal_noise(point: Vector3, octaves: u32) -> f32 {
+    let mut sum = 0.0;
+    let mut amplitude = 1.0;
+    let mut total = 0.0;
+    let mut p = point;
+    for _ in 0..octaves.max(1) {
+        sum += perlin(p) * amplitude;
+        total += amplitude;
+        amplitude *= 0.5;
+        p *= 2.0;
+    }
+    (sum / total * 0.5 + 0.5).clamp(0.0, 1.0)
+}
+
+/// Ruido de gradientes de Perlin en 3D, aproximadamente en [-1, 1] y 0 en los puntos enteros.
+/// Los gradientes salen de un hash de la celda en lugar de una tabla de permutaciones.
+pub fn perlin(p: Vector3) -> f32 {
+    let (x0, y0, z0) = (p.x.floor(), p.y.floor(), p.z.floor());
+    let (fx, fy, fz) = (p.x - x0, p.y - y0, p.z - z0);
+    let (ix, iy, iz) = (x0 as i32, y0 as i32, z0 as i32);
+
+    let corner = |dx: i32, dy: i32, dz: i32| {
+        let gradient = gradient(ix + dx, iy + dy, iz + dz);
+        gradient.dot(Vector3::new(fx - dx as f32, fy - dy as f32, fz - dz as f32))
+    };
+
+    let (u, v, w) = (fade(fx), fade(fy), fade(fz));
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+    let x00 = lerp(corner(0, 0, 0), corner(1, 0, 0), u);
+    let x10 = lerp(corner(0, 1, 0), corner(1, 1, 0), u);
+    let x01 = lerp(corner(0, 0, 1), corner(1, 0, 1), u);
+    let x11 = lerp(corner(0, 1, 1), corner(1, 1, 1), u);
+    lerp(lerp(x00, x10, v), lerp(x01, x11, v), w)
+}
+
+/// Curva 6t⁵ - 15t⁴ + 10t³ de Perlin: derivadas primera y segunda nulas en los extremos
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+/// Uno de los 12 gradientes de Perlin, hacia el centro de una arista del cubo
+fn gradient(x: i32, y: i32, z: i32) -> Vector3 {
+    const GRADIENTS: [(f32, f32, f32); 12] = [
+        (1.0, 1.0, 0.0),
+        (-1.0, 1.0, 0.0),
+        (1.0, -1.0, 0.0),
+        (-1.0, -1.0, 0.0),
+        (1.0, 0.0, 1.0),
+        (-1.0, 0.0, 1.0),
+        (1.0, 0.0, -1.0),
+        (-1.0, 0.0, -1.0),
+        (0.0, 1.0, 1.0),
+        (0.0, -1.0, 1.0),
+        (0.0, 1.0, -1.0),
+        (0.0, -1.0, -1.0),
+    ];
+    let h = hash(x as u32 ^ hash(y as u32 ^ hash(z as u32)));
+    let (gx, gy, gz) = GRADIENTS[(h % 12) as usize];
+    Vector3::new(gx, gy, gz)
+}
+
+/// Mezcla de bits del final de MurmurHash3
+fn hash(value: u32) -> u32 {
+    let mut x = value.wrapping_mul(0x9e37_79b9);
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x85eb_ca6b);
+    x ^= x >> 13;
+    x = x.wrapping_mul(0xc2b2_ae35);
+    x ^ (x >> 16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn noise_is_deterministic_and_bounded() {
+        // En los puntos enteros todas las esquinas coinciden con el punto: el ruido es 0
+        for p in [Vector3::zero(), Vector3::new(3.0, -2.0, 7.0)] {
+            assert_eq!(perlin(p), 0.0);
+        }
+
+        let point = Vector3::new(1.37, -0.42, 5.9);
+        assert_eq!(perlin(point), perlin(point));
+        assert_eq!(fractal_noise(point, 4), fractal_noise(point, 4));
+
+        let mut values = Vec::new();
+        for i in 0..500 {
+            let t = i as f32 * 0.173;
+            let p = Vector3::new(t, t * 0.61 - 3.0, 10.0 - t * 1.3);
+            let n = perlin(p);
+            assert!((-1.0..=1.0).contains(&n), "{}", n);
+            assert!((0.0..=1.0).contains(&fractal_noise(p, 5)));
+            values.push(n);
+        }
+        // Varía de verdad: ni constante ni pegado a un extremo
+        let mean = values.iter().sum::<f32>() / values.len() as f32;
+        assert!(mean.abs() < 0.15, "{}", mean);
+        assert!(values.iter().any(|&n| n > 0.2) && values.iter().any(|&n| n < -0.2));
+    }
+
+    #[test]
+    fn pattern_blends_between_its_two_colors() {
+        let pattern = NoisePattern {
+            scale: 2.0,
+            octaves: 3,
+            low: Vector3::new(0.4, 0.0, 0.0),
+            high: Vector3::new(0.0, 0.0, 0.8),
+        };
+        assert!(pattern.validate().is_ok());
+        for i in 0..50 {
+            let color = pattern.color(Vector3::new(i as f32 * 0.31, 0.5, -0.2));
+            assert!((color.x / 0.4 + color.z / 0.8 - 1.0).abs() < 1e-5);
+        }
+        // Punto entero de todas las octavas: justo a mitad de camino
+        let middle = pattern.color(Vector3::zero());
+        assert!((middle - Vector3::new(0.2, 0.0, 0.4)).length() < 1e-6);
+
+        assert!(
+            NoisePattern {
+                scale: 0.0,
+                ..pattern
+            }
+            .validate()
+            .is_err()
+        );
+        assert!(
+            NoisePattern {
+                octaves: 0,
+                ..pattern
+            }
+            .validate()
+            .is_err()
+        );
+    }
+}
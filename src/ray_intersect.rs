@@ -1,7 +1,13 @@
 // ray_intersect.rs
+use crate::instance::InstanceHit;
 use crate::material::Material;
 use raylib::prelude::{Color, Vector3};
 
+// Separación de la superficie para los rayos de sombra: a lo largo de la normal y, además,
+// hacia la luz, que es lo que evita el acné cuando la luz llega casi rasante a la cara
+pub const SHADOW_NORMAL_BIAS: f32 = 1e-4;
+pub const SHADOW_LIGHT_BIAS: f32 = 5e-4;
+
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
 
@@ -13,6 +19,11 @@ pub struct Intersect {
     pub point: Vector3,
     pub u: f32,
     pub v: f32,
+    pub object_idx: Option<usize>,
+    /// El rayo partió dentro del objeto; `normal` apunta entonces hacia dentro, contra el rayo
+    pub inside: bool,
+    /// Impacto con una instancia: el cubo está en su prototipo, desplazado
+    pub instance: Option<InstanceHit>,
 }
 
 impl Intersect {
@@ -32,29 +43,40 @@ impl Intersect {
             point,
             u,
             v,
+            object_idx: None,
+            inside: false,
+            instance: None,
+        }
+    }
+
+    /// Normal hacia fuera del objeto, sin importar de qué lado llegó el rayo
+    pub fn outward_normal(&self) -> Vector3 {
+        if self.inside {
+            -self.normal
+        } else {
+            self.normal
         }
     }
 
+    /// Origen del rayo de sombra hacia `light_direction`. Con luz rasante la normal apenas
+    /// aleja el rayo de la cara, así que también avanza `SHADOW_LIGHT_BIAS` hacia la luz;
+    /// quien lo use debe acortar la distancia a la luz en lo mismo.
+    pub fn shadow_origin(&self, light_direction: Vector3) -> Vector3 {
+        self.point + self.normal * SHADOW_NORMAL_BIAS + light_direction * SHADOW_LIGHT_BIAS
+    }
+
     pub fn empty() -> Self {
         Intersect {
-            material: Material {
-                diffuse: Vector3::zero(),
-                albedo: [0.0, 0.0],
-                specular: 0.0,
-                reflectivity: 0.0,
-                transparency: 0.0,
-                refractive_index: 0.0,
-                texture: None,
-                normal_map_id: None,
-                emission: Vector3::zero(),
-                emission_strength: 0.0,
-            },
+            material: Material::black(),
             distance: 0.0,
             is_intersecting: false,
             normal: Vector3::zero(),
             point: Vector3::zero(),
             u: 0.0,
             v: 0.0,
+            object_idx: None,
+            inside: false,
+            instance: None,
         }
     }
 }
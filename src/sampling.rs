@@ -0,0 +1,114 @@
+// sampling.rs - Sample placement inside a pixel: golden-ratio lattice or jittered strata
+
+/// Cómo se reparten las muestras de un píxel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SamplePattern {
+    /// Retícula fija con la proporción áurea, igual en todos los píxeles
+    Lattice,
+    /// Una muestra al azar dentro de cada celda de una rejilla que cubre el píxel; el azar
+    /// cambia de un píxel a otro para que el error no forme patrones
+    Stratified,
+}
+
+impl SamplePattern {
+    pub fn name(self) -> &'static str {
+        match self {
+            SamplePattern::Lattice => "lattice",
+            SamplePattern::Stratified => "stratified",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "lattice" => Some(SamplePattern::Lattice),
+            "stratified" => Some(SamplePattern::Stratified),
+            _ => None,
+        }
+    }
+}
+
+/// Columnas y filas de la rejilla para `n` muestras: lo más cuadrada posible, pero siempre
+/// con exactamente `n` celdas para que cada una reciba una muestra
+pub fn strata(n: u32) -> (u32, u32) {
+    let n = n.max(1);
+    let columns = (1..=n.isqrt())
+        .rev()
+        .find(|c| n.is_multiple_of(*c))
+        .unwrap_or(1);
+    (columns, n / columns)
+}
+
+/// Muestra `i` de `n` en [0, 1)², dentro de su celda y desplazada según `seed`
+pub fn stratified(i: u32, n: u32, seed: u32) -> (f32, f32) {
+    let (columns, rows) = strata(n);
+    let i = i % (columns * rows);
+    let (column, row) = (i % columns, i / columns);
+
+    let jitter_x = unit_random(hash(seed ^ hash(2 * i)));
+    let jitter_y = unit_random(hash(seed ^ hash(2 * i + 1)));
+    (
+        (column as f32 + jitter_x) / columns as f32,
+        (row as f32 + jitter_y) / rows as f32,
+    )
+}
+
+/// Semilla propia de cada píxel, combinada con la semilla global del render
+pub fn pixel_seed(x: i32, y: i32, seed: u32) -> u32 {
+    hash(hash(x as u32 ^ hash(y as u32)) ^ seed)
+}
+
+/// Mezcla de bits del final de MurmurHash3
+fn hash(value: u32) -> u32 {
+    let mut x = value.wrapping_mul(0x9e37_79b9);
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x85eb_ca6b);
+    x ^= x >> 13;
+    x = x.wrapping_mul(0xc2b2_ae35);
+    x ^ (x >> 16)
+}
+
+/// Valor en [0, 1) a partir de los 24 bits altos, que caben exactos en un f32
+fn unit_random(bits: u32) -> f32 {
+    (bits >> 8) as f32 / (1u32 << 24) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strata_use_every_sample() {
+        assert_eq!(strata(1), (1, 1));
+        assert_eq!(strata(4), (2, 2));
+        assert_eq!(strata(6), (2, 3));
+        assert_eq!(strata(16), (4, 4));
+        assert_eq!(strata(7), (1, 7));
+    }
+
+    #[test]
+    fn stratified_samples_cover_all_strata() {
+        for n in [1, 4, 6, 9, 16, 7] {
+            let (columns, rows) = strata(n);
+            for seed in [0, 17, 0xdead_beef] {
+                let mut hit = vec![0; n as usize];
+                for i in 0..n {
+                    let (u, v) = stratified(i, n, seed);
+                    assert!((0.0..1.0).contains(&u) && (0.0..1.0).contains(&v));
+                    let cell = (v * rows as f32) as u32 * columns + (u * columns as f32) as u32;
+                    hit[cell as usize] += 1;
+                }
+                assert!(hit.iter().all(|&count| count == 1), "{} {:?}", n, hit);
+            }
+        }
+    }
+
+    #[test]
+    fn pixels_get_different_jitter() {
+        let a = stratified(0, 4, pixel_seed(10, 20, 0));
+        let b = stratified(0, 4, pixel_seed(11, 20, 0));
+        let c = stratified(0, 4, pixel_seed(10, 20, 1));
+        assert_ne!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(a, stratified(0, 4, pixel_seed(10, 20, 0)));
+    }
+}
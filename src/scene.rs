@@ -0,0 +1,1361 @@
+// scene.rs - Structured scene files: materials, objects, lights, camera and skybox
+use crate::animation::{Animation, SkyBlend};
+use crate::axis::UpAxis;
+use crate::camera::Camera;
+use crate::cube::Cube;
+use crate::instance::{Instance, Prototype};
+use crate::light::{Flicker, FlickerShape, Light, kelvin_to_rgb};
+use crate::material::Material;
+use crate::noise::NoisePattern;
+use crate::textures::TextureId;
+use raylib::prelude::*;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "json-scene", derive(serde::Serialize, serde::Deserialize))]
+pub struct ObjectDef {
+    #[cfg_attr(feature = "json-scene", serde(with = "crate::json_scene::vec3"))]
+    pub center: Vector3,
+    #[cfg_attr(feature = "json-scene", serde(with = "crate::json_scene::vec3"))]
+    pub size: Vector3,
+    pub material: String,
+    /// Propiedades del material cambiadas solo para este objeto, como pares clave/valor
+    /// con la sintaxis de las secciones `[material]`
+    #[cfg_attr(feature = "json-scene", serde(default))]
+    pub overrides: Vec<(String, String)>,
+    /// Declarado en `[subtract]`: vacía el volumen que comparte con los demás objetos
+    #[cfg_attr(feature = "json-scene", serde(default))]
+    pub subtractive: bool,
+}
+
+impl ObjectDef {
+    /// Un cubo con alguna dimensión nula, negativa o no finita tiene una caja sin volumen
+    /// que rompe el reparto del BVH y la prueba de las placas
+    pub fn is_degenerate(&self) -> bool {
+        let [x, y, z] = [self.size.x, self.size.y, self.size.z];
+        let [cx, cy, cz] = [self.center.x, self.center.y, self.center.z];
+        [x, y, z].iter().any(|s| !(s.is_finite() && *s > 0.0))
+            || [cx, cy, cz].iter().any(|c| !c.is_finite())
+    }
+
+    /// Copia de `base` con los cambios propios del objeto aplicados
+    pub fn resolve_material(&self, base: &Material) -> Result<Material, String> {
+        let mut material = base.clone();
+        for (key, value) in &self.overrides {
+            set_material_property(&mut material, key, value)?;
+        }
+        Ok(material)
+    }
+
+    pub fn to_cube(&self, material: &Material) -> Cube {
+        // Los cambios se validan al cargar la escena, así que aquí no fallan
+        let material = self
+            .resolve_material(material)
+            .unwrap_or_else(|_| material.clone());
+        let mut cube = Cube::new_rect(self.center, self.size.x, self.size.y, self.size.z, material);
+        cube.subtractive = self.subtractive;
+        cube
+    }
+}
+
+/// Copia de un `[prototype]` colocada en la escena
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "json-scene", derive(serde::Serialize, serde::Deserialize))]
+pub struct InstanceDef {
+    pub prototype: String,
+    #[cfg_attr(feature = "json-scene", serde(with = "crate::json_scene::vec3"))]
+    pub offset: Vector3,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "json-scene", derive(serde::Serialize, serde::Deserialize))]
+pub struct CameraSetup {
+    #[cfg_attr(feature = "json-scene", serde(with = "crate::json_scene::vec3"))]
+    pub eye: Vector3,
+    #[cfg_attr(feature = "json-scene", serde(with = "crate::json_scene::vec3"))]
+    pub center: Vector3,
+    #[cfg_attr(feature = "json-scene", serde(with = "crate::json_scene::vec3"))]
+    pub up: Vector3,
+}
+
+impl CameraSetup {
+    pub fn from_camera(camera: &Camera) -> Self {
+        CameraSetup {
+            eye: camera.eye,
+            center: camera.center,
+            up: camera.up,
+        }
+    }
+
+    pub fn to_camera(&self) -> Camera {
+        Camera::new(self.eye, self.center, self.up)
+    }
+}
+
+/// Resolución y campo de visión con los que se pensó la escena. Los que faltan toman los
+/// valores por defecto, y `--width`/`--height` tienen prioridad sobre los de la escena.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(
+    feature = "json-scene",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct RenderSetup {
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    /// Campo de visión vertical en grados
+    pub fov: Option<f32>,
+    /// Eje vertical de la escena; `--up-axis` tiene prioridad
+    pub up_axis: Option<UpAxis>,
+}
+
+impl RenderSetup {
+    pub fn is_empty(&self) -> bool {
+        *self == RenderSetup::default()
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for size in [self.width, self.height].into_iter().flatten() {
+            if size <= 0 {
+                return Err(format!("Render size must be positive, got {}", size));
+            }
+        }
+        if let Some(fov) = self.fov
+            && !(fov > 0.0 && fov < 180.0)
+        {
+            return Err(format!("Render fov must be between 0 and 180, got {}", fov));
+        }
+        Ok(())
+    }
+}
+
+#[cfg_attr(feature = "json-scene", derive(serde::Serialize, serde::Deserialize))]
+pub struct Scene {
+    pub materials: BTreeMap<String, Material>,
+    pub objects: Vec<ObjectDef>,
+    pub lights: Vec<Light>,
+    pub camera: CameraSetup,
+    pub skybox: Option<String>,
+    /// Segundo skybox mezclado con el principal
+    #[cfg_attr(feature = "json-scene", serde(default))]
+    pub sky_blend: Option<SkyBlend>,
+    #[cfg_attr(feature = "json-scene", serde(default))]
+    pub animations: Vec<Animation>,
+    #[cfg_attr(feature = "json-scene", serde(default))]
+    pub render: RenderSetup,
+    /// Grupos de objetos con coordenadas relativas que se colocan con `instances`
+    #[cfg_attr(feature = "json-scene", serde(default))]
+    pub prototypes: BTreeMap<String, Vec<ObjectDef>>,
+    #[cfg_attr(feature = "json-scene", serde(default))]
+    pub instances: Vec<InstanceDef>,
+}
+
+impl Scene {
+    /// Carga una escena; los archivos `.json` requieren la feature `json-scene`
+    pub fn load(filepath: &str) -> Result<Scene, String> {
+        let contents = std::fs::read_to_string(filepath)
+            .map_err(|e| format!("Failed to read scene file '{}': {}", filepath, e))?;
+
+        if is_json_path(filepath) {
+            return Scene::parse_json(&contents);
+        }
+
+        Scene::parse(&contents)
+    }
+
+    pub fn save(&self, filepath: &str) -> Result<(), String> {
+        let contents = if is_json_path(filepath) {
+            self.to_json()?
+        } else {
+            self.to_text()
+        };
+
+        std::fs::write(filepath, contents)
+            .map_err(|e| format!("Failed to write scene file '{}': {}", filepath, e))
+    }
+
+    /// Verifica que cada objeto tenga volumen y haga referencia a un material definido,
+    /// que cada animación apunte a un objeto existente, que cada instancia use un prototipo
+    /// con objetos y que la resolución tenga sentido
+    pub fn validate(&self) -> Result<(), String> {
+        self.render.validate()?;
+        if let Some(blend) = &self.sky_blend {
+            if blend.texture.is_empty() {
+                return Err("Sky blend needs a texture".to_string());
+            }
+            if !(blend.frequency >= 0.0 && blend.frequency.is_finite()) {
+                return Err(format!(
+                    "Sky blend frequency must be zero or positive, got {}",
+                    blend.frequency
+                ));
+            }
+            if !(0.0..=1.0).contains(&blend.amount) {
+                return Err(format!(
+                    "Sky blend amount must be between 0 and 1, got {}",
+                    blend.amount
+                ));
+            }
+        }
+        for (name, material) in &self.materials {
+            material
+                .validate()
+                .map_err(|e| format!("Material '{}': {}", name, e))?;
+        }
+        for (i, object) in self.objects.iter().enumerate() {
+            self.validate_object(object)
+                .map_err(|e| format!("Object {}: {}", i, e))?;
+        }
+        for (name, objects) in &self.prototypes {
+            if objects.is_empty() {
+                return Err(format!("Prototype '{}' has no objects", name));
+            }
+            for (i, object) in objects.iter().enumerate() {
+                self.validate_object(object)
+                    .map_err(|e| format!("Prototype '{}', object {}: {}", name, i, e))?;
+            }
+        }
+        for (i, instance) in self.instances.iter().enumerate() {
+            if !self.prototypes.contains_key(&instance.prototype) {
+                return Err(format!(
+                    "Instance {}: Unknown prototype '{}'",
+                    i, instance.prototype
+                ));
+            }
+        }
+        for (i, animation) in self.animations.iter().enumerate() {
+            if animation.object >= self.objects.len() {
+                return Err(format!(
+                    "Animation {}: Unknown object {}",
+                    i, animation.object
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_object(&self, object: &ObjectDef) -> Result<(), String> {
+        if object.is_degenerate() {
+            return Err(format!(
+                "Degenerate cube at {} with size {}",
+                format_vector(object.center),
+                format_vector(object.size)
+            ));
+        }
+        let Some(material) = self.materials.get(&object.material) else {
+            return Err(format!("Unknown material '{}'", object.material));
+        };
+        object.resolve_material(material).map(|_| ())
+    }
+
+    /// Avisos que no impiden renderizar, como luces que no iluminan nada o materiales que
+    /// reflejan y refractan más luz de la que reciben
+    pub fn warnings(&self) -> Vec<String> {
+        let lights = self.lights.iter().enumerate().filter_map(|(i, light)| {
+            dark_light_warning(light).map(|w| format!("Light {}: {}", i, w))
+        });
+        let materials = self.materials.iter().filter_map(|(name, material)| {
+            bounce_energy_warning(material).map(|w| format!("Material '{}': {}", name, w))
+        });
+        lights.chain(materials).collect()
+    }
+
+    pub fn parse(contents: &str) -> Result<Scene, String> {
+        let mut scene = Scene {
+            materials: BTreeMap::new(),
+            objects: Vec::new(),
+            lights: Vec::new(),
+            camera: CameraSetup {
+                eye: Vector3::new(0.0, 2.0, 8.0),
+                center: Vector3::zero(),
+                up: Vector3::new(0.0, 1.0, 0.0),
+            },
+            skybox: None,
+            sky_blend: None,
+            animations: Vec::new(),
+            render: RenderSetup::default(),
+            prototypes: BTreeMap::new(),
+            instances: Vec::new(),
+        };
+
+        let mut section = Section::None;
+        // Los objetos se resuelven al final para permitir materiales definidos después
+        let mut object_lines = Vec::new();
+        let mut prototype_lines = Vec::new();
+        // Línea de la cabecera de cada luz, para situar los avisos
+        let mut light_lines = Vec::new();
+        // Sin `up` en [camera], la cámara sigue el eje vertical de la escena
+        let mut camera_up = None;
+
+        for (line_num, line) in contents.lines().enumerate() {
+            let trimmed = line.trim();
+
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+
+            if trimmed.starts_with('[') {
+                section = parse_section_header(line_num, trimmed)?;
+                match &section {
+                    Section::Material(name) => {
+                        scene.materials.insert(name.clone(), Material::black());
+                    }
+                    Section::Light => {
+                        scene
+                            .lights
+                            .push(Light::new(Vector3::zero(), Vector3::one(), 1.0));
+                        light_lines.push(line_num);
+                    }
+                    Section::Animation => {
+                        scene.animations.push(Animation {
+                            object: 0,
+                            amplitude: Vector3::zero(),
+                            frequency: 1.0,
+                            phase: 0.0,
+                        });
+                    }
+                    Section::Prototype(name) => {
+                        scene.prototypes.entry(name.clone()).or_default();
+                    }
+                    Section::SkyBlend => {
+                        scene.sky_blend = Some(SkyBlend {
+                            texture: String::new(),
+                            frequency: 0.0,
+                            phase: 0.0,
+                            amount: 0.5,
+                        });
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+
+            let (key, value) = split_key_value(trimmed);
+
+            match &section {
+                Section::None => {
+                    return Err(format!(
+                        "Line {}: Expected a section header before '{}'",
+                        line_num + 1,
+                        trimmed
+                    ));
+                }
+                Section::Skybox => {
+                    scene.skybox = Some(trimmed.to_string());
+                }
+                Section::SkyBlend => {
+                    let blend = scene.sky_blend.as_mut().unwrap();
+                    match key {
+                        "texture" => blend.texture = value.to_string(),
+                        "frequency" => blend.frequency = parse_float(line_num, key, value)?,
+                        "phase" => blend.phase = parse_float(line_num, key, value)?,
+                        "amount" => blend.amount = parse_float(line_num, key, value)?,
+                        _ => return Err(unknown_key(line_num, "sky_blend", key)),
+                    }
+                }
+                Section::Camera => {
+                    let v = parse_vector(line_num, key, value)?;
+                    match key {
+                        "eye" => scene.camera.eye = v,
+                        "center" => scene.camera.center = v,
+                        "up" => camera_up = Some(v),
+                        _ => return Err(unknown_key(line_num, "camera", key)),
+                    }
+                }
+                Section::Render => match key {
+                    "width" => scene.render.width = Some(parse_size(line_num, key, value)?),
+                    "height" => scene.render.height = Some(parse_size(line_num, key, value)?),
+                    "fov" => {
+                        let fov = parse_float(line_num, key, value)?;
+                        if !(fov > 0.0 && fov < 180.0) {
+                            return Err(format!(
+                                "Line {}: fov must be between 0 and 180, got {}",
+                                line_num + 1,
+                                fov
+                            ));
+                        }
+                        scene.render.fov = Some(fov);
+                    }
+                    "up_axis" => {
+                        scene.render.up_axis = Some(UpAxis::from_name(value).ok_or_else(|| {
+                            format!(
+                                "Line {}: up_axis must be y or z, got '{}'",
+                                line_num + 1,
+                                value
+                            )
+                        })?)
+                    }
+                    _ => return Err(unknown_key(line_num, "render", key)),
+                },
+                Section::Material(name) => {
+                    let material = scene.materials.get_mut(name).unwrap();
+                    set_material_property(material, key, value)
+                        .map_err(|e| format!("Line {}: {}", line_num + 1, e))?;
+                }
+                Section::Light => {
+                    let light = scene.lights.last_mut().unwrap();
+                    match key {
+                        "position" => light.position = parse_vector(line_num, key, value)?,
+                        "color" => light.color = parse_vector(line_num, key, value)?,
+                        "temperature" => {
+                            light.color = kelvin_to_rgb(parse_float(line_num, key, value)?)
+                        }
+                        "intensity" => light.intensity = parse_float(line_num, key, value)?,
+                        "radius" => light.radius = parse_float(line_num, key, value)?,
+                        "casts_shadows" => light.casts_shadows = parse_bool(line_num, key, value)?,
+                        "flicker" => light.flicker = Some(parse_flicker(line_num, value)?),
+                        _ => return Err(unknown_key(line_num, "light", key)),
+                    }
+                }
+                Section::Animation => {
+                    let animation = scene.animations.last_mut().unwrap();
+                    match key {
+                        "object" => {
+                            animation.object = value.parse::<usize>().map_err(|_| {
+                                format!("Line {}: Invalid {} '{}'", line_num + 1, key, value)
+                            })?
+                        }
+                        "amplitude" => animation.amplitude = parse_vector(line_num, key, value)?,
+                        "frequency" => animation.frequency = parse_float(line_num, key, value)?,
+                        "phase" => animation.phase = parse_float(line_num, key, value)?,
+                        _ => return Err(unknown_key(line_num, "animation", key)),
+                    }
+                }
+                Section::Objects | Section::Subtract => {
+                    let subtractive = matches!(section, Section::Subtract);
+                    object_lines.push((line_num, trimmed, subtractive));
+                }
+                Section::Prototype(name) => {
+                    prototype_lines.push((line_num, trimmed, name.clone()));
+                }
+                Section::Instances(name) => {
+                    scene.instances.push(InstanceDef {
+                        prototype: name.clone(),
+                        offset: parse_vector(line_num, "instance offset", trimmed)?,
+                    });
+                }
+            }
+        }
+
+        scene.camera.up =
+            camera_up.unwrap_or_else(|| scene.render.up_axis.unwrap_or_default().up());
+
+        for (line_num, line, subtractive) in object_lines {
+            let mut object = parse_object_line(line_num, line, &scene.materials)?;
+            object.subtractive = subtractive;
+            scene.objects.push(object);
+        }
+        for (line_num, line, name) in prototype_lines {
+            let object = parse_object_line(line_num, line, &scene.materials)?;
+            scene.prototypes.get_mut(&name).unwrap().push(object);
+        }
+
+        scene.validate()?;
+
+        for (light, line_num) in scene.lights.iter().zip(light_lines) {
+            if let Some(warning) = dark_light_warning(light) {
+                eprintln!("Warning: Line {}: {}", line_num + 1, warning);
+            }
+        }
+        for (name, material) in &scene.materials {
+            if let Some(warning) = bounce_energy_warning(material) {
+                eprintln!("Warning: Material '{}': {}", name, warning);
+            }
+        }
+
+        Ok(scene)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+
+        if let Some(skybox) = &self.skybox {
+            out.push_str("[skybox]\n");
+            out.push_str(&format!("{}\n\n", skybox));
+        }
+        if let Some(blend) = &self.sky_blend {
+            out.push_str("[sky_blend]\n");
+            out.push_str(&format!("texture {}\n", blend.texture));
+            out.push_str(&format!("frequency {}\n", blend.frequency));
+            out.push_str(&format!("phase {}\n", blend.phase));
+            out.push_str(&format!("amount {}\n\n", blend.amount));
+        }
+
+        out.push_str("[camera]\n");
+        out.push_str(&format!("eye {}\n", format_vector(self.camera.eye)));
+        out.push_str(&format!("center {}\n", format_vector(self.camera.center)));
+        out.push_str(&format!("up {}\n\n", format_vector(self.camera.up)));
+
+        if !self.render.is_empty() {
+            out.push_str("[render]\n");
+            if let Some(width) = self.render.width {
+                out.push_str(&format!("width {}\n", width));
+            }
+            if let Some(height) = self.render.height {
+                out.push_str(&format!("height {}\n", height));
+            }
+            if let Some(fov) = self.render.fov {
+                out.push_str(&format!("fov {}\n", fov));
+            }
+            if let Some(up_axis) = self.render.up_axis {
+                out.push_str(&format!("up_axis {}\n", up_axis.name()));
+            }
+            out.push('\n');
+        }
+
+        for (name, material) in &self.materials {
+            out.push_str(&format!("[material {}]\n", name));
+            out.push_str(&format_material(material));
+            out.push('\n');
+        }
+
+        for light in &self.lights {
+            out.push_str("[light]\n");
+            out.push_str(&format!("position {}\n", format_vector(light.position)));
+            out.push_str(&format!("color {}\n", format_vector(light.color)));
+            out.push_str(&format!("intensity {}\n", light.intensity));
+            if light.radius > 0.0 {
+                out.push_str(&format!("radius {}\n", light.radius));
+            }
+            if !light.casts_shadows {
+                out.push_str("casts_shadows false\n");
+            }
+            if let Some(flicker) = &light.flicker {
+                out.push_str(&format!(
+                    "flicker {} {} {}\n",
+                    flicker.shape.name(),
+                    flicker.amount,
+                    flicker.frequency
+                ));
+            }
+            out.push('\n');
+        }
+
+        for animation in &self.animations {
+            out.push_str("[animation]\n");
+            out.push_str(&format!("object {}\n", animation.object));
+            out.push_str(&format!(
+                "amplitude {}\n",
+                format_vector(animation.amplitude)
+            ));
+            out.push_str(&format!("frequency {}\n", animation.frequency));
+            out.push_str(&format!("phase {}\n\n", animation.phase));
+        }
+
+        out.push_str("[objects]\n");
+        out.push_str("# x y z size material  |  x y z width height depth material\n");
+        // Se abre una sección nueva en cada cambio entre objetos y restas para conservar
+        // el orden, al que se refieren las animaciones
+        let mut subtracting = false;
+        for object in &self.objects {
+            if object.subtractive != subtracting {
+                subtracting = object.subtractive;
+                out.push_str(if subtracting {
+                    "\n[subtract]\n"
+                } else {
+                    "\n[objects]\n"
+                });
+            }
+            out.push_str(&format_object(object));
+            out.push('\n');
+        }
+
+        for (name, objects) in &self.prototypes {
+            out.push_str(&format!("\n[prototype {}]\n", name));
+            for object in objects {
+                out.push_str(&format_object(object));
+                out.push('\n');
+            }
+        }
+
+        // Una sección por cada tramo de instancias seguidas del mismo prototipo
+        let mut current: Option<&str> = None;
+        for instance in &self.instances {
+            if current != Some(instance.prototype.as_str()) {
+                current = Some(instance.prototype.as_str());
+                out.push_str(&format!("\n[instances {}]\n", instance.prototype));
+            }
+            out.push_str(&format!("{}\n", format_vector(instance.offset)));
+        }
+
+        out
+    }
+
+    /// Texturas de los materiales, incluidas las que un objeto cambia con `texture=`
+    pub fn texture_ids(&self) -> Vec<TextureId> {
+        let overridden = self
+            .objects
+            .iter()
+            .chain(self.prototypes.values().flatten())
+            .filter(|object| !object.overrides.is_empty())
+            .filter_map(|object| {
+                object
+                    .resolve_material(&self.materials[&object.material])
+                    .ok()
+            });
+        self.materials
+            .values()
+            .cloned()
+            .chain(overridden)
+            .filter_map(|material| material.texture)
+            .collect()
+    }
+
+    pub fn build_objects(&self) -> Vec<Cube> {
+        self.objects
+            .iter()
+            .map(|object| object.to_cube(&self.materials[&object.material]))
+            .collect()
+    }
+
+    /// Construye cada prototipo usado una sola vez, con su BVH, y lo comparte entre todas
+    /// sus instancias
+    pub fn build_instances(&self, max_leaf_size: usize) -> Vec<Instance> {
+        let mut prototypes: BTreeMap<&str, Arc<Prototype>> = BTreeMap::new();
+        self.instances
+            .iter()
+            .map(|def| {
+                let prototype = prototypes.entry(&def.prototype).or_insert_with(|| {
+                    let cubes = self.prototypes[&def.prototype]
+                        .iter()
+                        .map(|object| object.to_cube(&self.materials[&object.material]))
+                        .collect();
+                    Arc::new(Prototype::new(&def.prototype, cubes, max_leaf_size))
+                });
+                Instance {
+                    prototype: Arc::clone(prototype),
+                    offset: def.offset,
+                }
+            })
+            .collect()
+    }
+}
+
+fn is_json_path(filepath: &str) -> bool {
+    std::path::Path::new(filepath)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+}
+
+/// Sin la feature `json-scene` los archivos `.json` se rechazan con un error claro
+#[cfg(not(feature = "json-scene"))]
+impl Scene {
+    pub fn parse_json(_contents: &str) -> Result<Scene, String> {
+        Err("JSON scenes require the `json-scene` feature".to_string())
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        Err("JSON scenes require the `json-scene` feature".to_string())
+    }
+}
+
+enum Section {
+    None,
+    Skybox,
+    SkyBlend,
+    Camera,
+    Render,
+    Material(String),
+    Light,
+    Animation,
+    Objects,
+    Subtract,
+    Prototype(String),
+    Instances(String),
+}
+
+fn parse_section_header(line_num: usize, trimmed: &str) -> Result<Section, String> {
+    let inner = trimmed
+        .strip_prefix('[')
+        .and_then(|s| s.strip_suffix(']'))
+        .ok_or_else(|| {
+            format!(
+                "Line {}: Malformed section header '{}'",
+                line_num + 1,
+                trimmed
+            )
+        })?
+        .trim();
+
+    let (kind, arg) = split_key_value(inner);
+
+    match (kind, arg) {
+        ("skybox", "") => Ok(Section::Skybox),
+        ("sky_blend", "") => Ok(Section::SkyBlend),
+        ("camera", "") => Ok(Section::Camera),
+        ("render", "") => Ok(Section::Render),
+        ("light", "") => Ok(Section::Light),
+        ("animation", "") => Ok(Section::Animation),
+        ("objects", "") => Ok(Section::Objects),
+        ("subtract", "") => Ok(Section::Subtract),
+        ("material", name) if !name.is_empty() => Ok(Section::Material(name.to_string())),
+        ("prototype", name) if !name.is_empty() => Ok(Section::Prototype(name.to_string())),
+        ("instances", name) if !name.is_empty() => Ok(Section::Instances(name.to_string())),
+        _ => Err(format!(
+            "Line {}: Unknown section '{}'",
+            line_num + 1,
+            trimmed
+        )),
+    }
+}
+
+fn dark_light_warning(light: &Light) -> Option<String> {
+    let dark = light.intensity.is_nan() || light.intensity <= 0.0 || light.color == Vector3::zero();
+    dark.then(|| {
+        format!(
+            "Light with intensity {} and color {} illuminates nothing",
+            light.intensity,
+            format_vector(light.color)
+        )
+    })
+}
+
+fn bounce_energy_warning(material: &Material) -> Option<String> {
+    let total = material.effective_reflectivity() + material.transparency;
+    (total > 1.0).then(|| {
+        format!(
+            "reflectivity + transparency is {}, more light than it receives (see --clamp-bounces)",
+            total
+        )
+    })
+}
+
+fn split_key_value(line: &str) -> (&str, &str) {
+    match line.split_once(char::is_whitespace) {
+        Some((key, value)) => (key, value.trim()),
+        None => (line, ""),
+    }
+}
+
+fn unknown_key(line_num: usize, section: &str, key: &str) -> String {
+    format!(
+        "Line {}: Unknown {} property '{}'",
+        line_num + 1,
+        section,
+        key
+    )
+}
+
+/// Entero positivo, como el ancho o el alto en píxeles
+fn parse_size(line_num: usize, key: &str, value: &str) -> Result<i32, String> {
+    value
+        .parse::<i32>()
+        .ok()
+        .filter(|size| *size > 0)
+        .ok_or_else(|| format!("Line {}: Invalid {} '{}'", line_num + 1, key, value))
+}
+
+fn parse_float(line_num: usize, key: &str, value: &str) -> Result<f32, String> {
+    value
+        .parse::<f32>()
+        .map_err(|_| format!("Line {}: Invalid {} '{}'", line_num + 1, key, value))
+}
+
+/// `flicker <sine|noise> <cantidad> <frecuencia>`
+fn parse_flicker(line_num: usize, value: &str) -> Result<Flicker, String> {
+    let invalid = || format!("Line {}: Invalid flicker '{}'", line_num + 1, value);
+    let parts: Vec<&str> = value.split_whitespace().collect();
+    let [shape, amount, frequency] = parts[..] else {
+        return Err(invalid());
+    };
+
+    Ok(Flicker {
+        shape: FlickerShape::from_name(shape).ok_or_else(invalid)?,
+        amount: amount.parse().map_err(|_| invalid())?,
+        frequency: frequency.parse().map_err(|_| invalid())?,
+    })
+}
+
+fn parse_bool(line_num: usize, key: &str, value: &str) -> Result<bool, String> {
+    match value {
+        "true" | "yes" | "1" => Ok(true),
+        "false" | "no" | "0" => Ok(false),
+        _ => Err(format!(
+            "Line {}: Invalid {} '{}'",
+            line_num + 1,
+            key,
+            value
+        )),
+    }
+}
+
+fn parse_vector(line_num: usize, key: &str, value: &str) -> Result<Vector3, String> {
+    parse_floats::<3>(value)
+        .map(|[x, y, z]| Vector3::new(x, y, z))
+        .ok_or_else(|| format!("Line {}: Invalid {} '{}'", line_num + 1, key, value))
+}
+
+fn parse_floats<const N: usize>(value: &str) -> Option<[f32; N]> {
+    let parts: Vec<&str> = value.split_whitespace().collect();
+    if parts.len() != N {
+        return None;
+    }
+
+    let mut out = [0.0; N];
+    for (slot, part) in out.iter_mut().zip(parts) {
+        *slot = part.parse::<f32>().ok()?;
+    }
+    Some(out)
+}
+
+fn format_vector(v: Vector3) -> String {
+    format!("{} {} {}", v.x, v.y, v.z)
+}
+
+/// Asigna una propiedad de material a partir de su nombre en el archivo de escena
+pub fn set_material_property(
+    material: &mut Material,
+    key: &str,
+    value: &str,
+) -> Result<(), String> {
+    let invalid = || format!("Invalid {} '{}'", key, value);
+    let float = || value.parse::<f32>().map_err(|_| invalid());
+    let boolean = || match value {
+        "true" | "yes" | "1" => Ok(true),
+        "false" | "no" | "0" => Ok(false),
+        _ => Err(invalid()),
+    };
+    let vector = || {
+        parse_floats::<3>(value)
+            .map(|[x, y, z]| Vector3::new(x, y, z))
+            .ok_or_else(invalid)
+    };
+
+    match key {
+        "diffuse" => material.diffuse = vector()?,
+        "albedo" => material.albedo = parse_floats::<2>(value).ok_or_else(invalid)?,
+        "specular" => {
+            material.specular = float()?;
+            material.validate()?;
+        }
+        "specular_color" => material.specular_color = vector()?,
+        "reflectivity" => material.reflectivity = float()?,
+        "transparency" => material.transparency = float()?,
+        "refractive_index" => material.refractive_index = float()?,
+        "metalness" => material.metalness = Some(float()?),
+        "dispersion" => material.dispersion = float()?,
+        "bevel" => material.bevel = float()?,
+        "roughness" => {
+            [material.roughness_u, material.roughness_v] =
+                parse_floats::<2>(value).ok_or_else(invalid)?
+        }
+        "edge_width" => material.edge_width = float()?,
+        "edge_color" => material.edge_color = vector()?,
+        "two_sided" => material.two_sided = boolean()?,
+        "uv_rotation" => {
+            let degrees = value.parse::<i32>().map_err(|_| invalid())?;
+            if degrees % 90 != 0 {
+                return Err(invalid());
+            }
+            material.uv_quarter_turns = (degrees / 90).rem_euclid(4) as u8;
+        }
+        "texture" => material.texture = Some(TextureId::from_path(value)),
+        "normal_map" => material.normal_map_id = Some(TextureId::from_path(value)),
+        "emission" => material.emission = vector()?,
+        "emission_strength" => material.emission_strength = float()?,
+        "tint_emission" => material.tint_emission = boolean()?,
+        "noise" => {
+            let [scale, octaves, lr, lg, lb, hr, hg, hb] =
+                parse_floats::<8>(value).ok_or_else(invalid)?;
+            if octaves.fract() != 0.0 || octaves < 0.0 {
+                return Err(invalid());
+            }
+            let noise = NoisePattern {
+                scale,
+                octaves: octaves as u32,
+                low: Vector3::new(lr, lg, lb),
+                high: Vector3::new(hr, hg, hb),
+            };
+            noise.validate()?;
+            material.noise = Some(noise);
+        }
+        _ => return Err(format!("Unknown material property '{}'", key)),
+    }
+
+    Ok(())
+}
+
+fn format_material(material: &Material) -> String {
+    let mut out = String::new();
+    out.push_str(&format!("diffuse {}\n", format_vector(material.diffuse)));
+    out.push_str(&format!(
+        "albedo {} {}\n",
+        material.albedo[0], material.albedo[1]
+    ));
+    out.push_str(&format!("specular {}\n", material.specular));
+    if material.specular_color != Vector3::one() {
+        out.push_str(&format!(
+            "specular_color {}\n",
+            format_vector(material.specular_color)
+        ));
+    }
+    out.push_str(&format!("reflectivity {}\n", material.reflectivity));
+    out.push_str(&format!("transparency {}\n", material.transparency));
+    out.push_str(&format!("refractive_index {}\n", material.refractive_index));
+    if let Some(metalness) = material.metalness {
+        out.push_str(&format!("metalness {}\n", metalness));
+    }
+    if material.dispersion > 0.0 {
+        out.push_str(&format!("dispersion {}\n", material.dispersion));
+    }
+    if material.bevel > 0.0 {
+        out.push_str(&format!("bevel {}\n", material.bevel));
+    }
+    if material.is_anisotropic() {
+        out.push_str(&format!(
+            "roughness {} {}\n",
+            material.roughness_u, material.roughness_v
+        ));
+    }
+    if material.edge_width > 0.0 {
+        out.push_str(&format!("edge_width {}\n", material.edge_width));
+        out.push_str(&format!(
+            "edge_color {}\n",
+            format_vector(material.edge_color)
+        ));
+    }
+    if material.two_sided {
+        out.push_str("two_sided true\n");
+    }
+    if material.uv_quarter_turns != 0 {
+        out.push_str(&format!(
+            "uv_rotation {}\n",
+            material.uv_quarter_turns as u32 * 90
+        ));
+    }
+    if let Some(noise) = &material.noise {
+        out.push_str(&format!(
+            "noise {} {} {} {}\n",
+            noise.scale,
+            noise.octaves,
+            format_vector(noise.low),
+            format_vector(noise.high)
+        ));
+    }
+    if let Some(texture) = &material.texture {
+        out.push_str(&format!("texture {}\n", texture.path()));
+    }
+    if let Some(normal_map) = &material.normal_map_id {
+        out.push_str(&format!("normal_map {}\n", normal_map.path()));
+    }
+    out.push_str(&format!("emission {}\n", format_vector(material.emission)));
+    out.push_str(&format!(
+        "emission_strength {}\n",
+        material.emission_strength
+    ));
+    if !material.tint_emission {
+        out.push_str("tint_emission false\n");
+    }
+    out
+}
+
+fn format_object(object: &ObjectDef) -> String {
+    let mut line = format_object_placement(object);
+    for (key, value) in &object.overrides {
+        line.push_str(&format!(
+            " {}={}",
+            key,
+            value.split_whitespace().collect::<Vec<_>>().join(",")
+        ));
+    }
+    line
+}
+
+fn format_object_placement(object: &ObjectDef) -> String {
+    let s = object.size;
+    if s.x == s.y && s.y == s.z {
+        format!(
+            "{} {} {}",
+            format_vector(object.center),
+            s.x,
+            object.material
+        )
+    } else {
+        format!(
+            "{} {} {} {} {}",
+            format_vector(object.center),
+            s.x,
+            s.y,
+            s.z,
+            object.material
+        )
+    }
+}
+
+/// Interpreta una línea de objeto: `x y z size material` o `x y z width height depth material`,
+/// seguida opcionalmente de cambios al material solo para ese objeto (`reflectivity=0.5`).
+/// Los valores con varios números los separan con comas: `diffuse=1,0.2,0.1`.
+pub fn parse_object_line(
+    line_num: usize,
+    line: &str,
+    materials: &BTreeMap<String, Material>,
+) -> Result<ObjectDef, String> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    let split = tokens
+        .iter()
+        .position(|token| token.contains('='))
+        .unwrap_or(tokens.len());
+    let (parts, override_tokens) = tokens.split_at(split);
+
+    if parts.len() != 5 && parts.len() != 7 {
+        return Err(format!(
+            "Line {}: Expected 5 or 7 parameters (x y z size material), got {}",
+            line_num + 1,
+            parts.len()
+        ));
+    }
+
+    let x = parts[0]
+        .parse::<f32>()
+        .map_err(|_| format!("Line {}: Invalid x coordinate '{}'", line_num + 1, parts[0]))?;
+    let y = parts[1]
+        .parse::<f32>()
+        .map_err(|_| format!("Line {}: Invalid y coordinate '{}'", line_num + 1, parts[1]))?;
+    let z = parts[2]
+        .parse::<f32>()
+        .map_err(|_| format!("Line {}: Invalid z coordinate '{}'", line_num + 1, parts[2]))?;
+
+    let size_parts = &parts[3..parts.len() - 1];
+    let mut dims = [0.0; 3];
+    for (i, part) in size_parts.iter().enumerate() {
+        dims[i] = part
+            .parse::<f32>()
+            .map_err(|_| format!("Line {}: Invalid size '{}'", line_num + 1, part))?;
+    }
+    let size = if size_parts.len() == 1 {
+        Vector3::new(dims[0], dims[0], dims[0])
+    } else {
+        Vector3::new(dims[0], dims[1], dims[2])
+    };
+    if dims[..size_parts.len()]
+        .iter()
+        .any(|s| !(s.is_finite() && *s > 0.0))
+    {
+        return Err(format!(
+            "Line {}: Degenerate size {}, every dimension must be positive",
+            line_num + 1,
+            size_parts.join(" ")
+        ));
+    }
+
+    let material_name = parts[parts.len() - 1];
+
+    let Some(material) = materials.get(material_name) else {
+        return Err(format!(
+            "Line {}: Unknown material '{}'. Available: {}",
+            line_num + 1,
+            material_name,
+            materials
+                .keys()
+                .map(|s| s.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    };
+
+    let mut overrides = Vec::new();
+    for token in override_tokens {
+        let invalid = |reason: String| {
+            format!(
+                "Line {}: Invalid material override '{}': {}",
+                line_num + 1,
+                token,
+                reason
+            )
+        };
+        let Some((key, value)) = token.split_once('=') else {
+            return Err(invalid(
+                "overrides must come after the material, as key=value".to_string(),
+            ));
+        };
+        // Solo los valores numéricos separan con comas; una ruta puede contenerlas
+        let value = match key {
+            "texture" | "normal_map" => value.to_string(),
+            _ => value.replace(',', " "),
+        };
+        set_material_property(&mut material.clone(), key, &value).map_err(invalid)?;
+        overrides.push((key.to_string(), value));
+    }
+
+    Ok(ObjectDef {
+        center: Vector3::new(x, y, z),
+        size,
+        material: material_name.to_string(),
+        overrides,
+        subtractive: false,
+    })
+}
+
+/// Carga un archivo con solo la lista de cubos (formato original de `scene.txt`)
+pub fn load_scene_from_file(
+    filepath: &str,
+    materials: &BTreeMap<String, Material>,
+) -> Result<Vec<ObjectDef>, String> {
+    let contents = std::fs::read_to_string(filepath)
+        .map_err(|e| format!("Failed to read scene file '{}': {}", filepath, e))?;
+
+    parse_object_list(&contents, materials)
+}
+
+/// Carga la lista de cubos de una escena repartida en varios archivos. `path` puede ser un
+/// directorio (se leen sus `.txt` en orden alfabético), un índice `.list` con una ruta por
+/// línea relativa al propio índice, o un archivo suelto como en `load_scene_from_file`.
+/// Los errores indican el archivo además de la línea.
+pub fn load_scene_files(
+    path: &str,
+    materials: &BTreeMap<String, Material>,
+) -> Result<Vec<ObjectDef>, String> {
+    let root = Path::new(path);
+    let files: Vec<PathBuf> = if root.is_dir() {
+        let mut files: Vec<PathBuf> = std::fs::read_dir(root)
+            .map_err(|e| format!("Failed to read scene directory '{}': {}", path, e))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|file| file.extension().is_some_and(|ext| ext == "txt"))
+            .collect();
+        files.sort();
+        files
+    } else if path.ends_with(".list") {
+        let contents = std::fs::read_to_string(root)
+            .map_err(|e| format!("Failed to read scene index '{}': {}", path, e))?;
+        let base = root.parent().unwrap_or(Path::new(""));
+        contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| base.join(line))
+            .collect()
+    } else {
+        return load_scene_from_file(path, materials);
+    };
+
+    if files.is_empty() {
+        return Err(format!("No scene files found in '{}'", path));
+    }
+
+    let mut objects = Vec::new();
+    for file in files {
+        let file = file.display().to_string();
+        let contents = std::fs::read_to_string(&file)
+            .map_err(|e| format!("Failed to read scene file '{}': {}", file, e))?;
+        let part =
+            parse_object_list(&contents, materials).map_err(|e| format!("{}: {}", file, e))?;
+        objects.extend(part);
+    }
+
+    Ok(objects)
+}
+
+fn parse_object_list(
+    contents: &str,
+    materials: &BTreeMap<String, Material>,
+) -> Result<Vec<ObjectDef>, String> {
+    let mut objects = Vec::new();
+
+    for (line_num, line) in contents.lines().enumerate() {
+        let trimmed = line.trim();
+
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        objects.push(parse_object_line(line_num, trimmed, materials)?);
+    }
+
+    Ok(objects)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCENE: &str = "
+[render]
+width 320
+height 200
+fov 50
+up_axis z
+
+[camera]
+eye 0 -8 2
+center 0 0 0
+
+[material stone]
+diffuse 0.5 0.4 0.3
+reflectivity 0.25
+
+[light]
+position 1 2 3
+color 1 0.5 0.25
+intensity 2
+radius 0.5
+flicker noise 0.2 3
+
+[light]
+position -1 4 0
+intensity 0.5
+casts_shadows false
+
+[objects]
+0 0 0 1 stone
+2 0 0 1 2 3 stone reflectivity=0.5
+
+[subtract]
+2 0 0.5 0.5 stone
+
+[objects]
+4 0 0 1 stone
+
+[prototype stem]
+0 0 0 0.5 stone
+0 0 0.5 0.5 stone
+
+[instances stem]
+-3 0 0
+3 0 0
+";
+
+    #[test]
+    fn text_round_trips() {
+        let scene = Scene::parse(SCENE).unwrap();
+        let text = scene.to_text();
+        let reloaded = Scene::parse(&text).unwrap();
+        // Escribir la escena recargada da exactamente el mismo archivo
+        assert_eq!(reloaded.to_text(), text);
+
+        assert_eq!(reloaded.render, scene.render);
+        assert_eq!(reloaded.render.up_axis, Some(UpAxis::Z));
+        assert_eq!(reloaded.camera.up, Vector3::new(0.0, 0.0, 1.0));
+
+        let light = &reloaded.lights[0];
+        assert_eq!(light.radius, 0.5);
+        assert_eq!(light.flicker, scene.lights[0].flicker);
+        assert_eq!(light.flicker.unwrap().shape, FlickerShape::Noise);
+        assert!(!reloaded.lights[1].casts_shadows);
+
+        let subtractive: Vec<bool> = reloaded.objects.iter().map(|o| o.subtractive).collect();
+        assert_eq!(subtractive, [false, false, true, false]);
+        assert_eq!(reloaded.objects[1].size, Vector3::new(1.0, 2.0, 3.0));
+        assert_eq!(reloaded.objects[1].overrides, scene.objects[1].overrides);
+
+        assert_eq!(reloaded.prototypes["stem"].len(), 2);
+        let offsets: Vec<Vector3> = reloaded.instances.iter().map(|i| i.offset).collect();
+        assert_eq!(
+            offsets,
+            [Vector3::new(-3.0, 0.0, 0.0), Vector3::new(3.0, 0.0, 0.0)]
+        );
+    }
+    #[test]
+    fn invalid_override_reports_its_line() {
+        let text = "[material stone]\ndiffuse 1 1 1\n\n[objects]\n0 0 0 1 stone\n1 0 0 1 stone shininess=2\n";
+        let err = Scene::parse(text).err().unwrap();
+        assert!(err.starts_with("Line 6: Invalid material override 'shininess=2'"));
+    }
+
+    #[test]
+    fn override_textures_are_collected_with_commas_kept() {
+        let text = format!(
+            "{}\n[objects]\n6 0 0 1 stone texture=assets/a,b.png diffuse=1,0,0\n",
+            SCENE
+        );
+        let scene = Scene::parse(&text).unwrap();
+        let object = scene.objects.last().unwrap();
+        assert_eq!(object.overrides[0].1, "assets/a,b.png");
+        assert_eq!(object.overrides[1].1, "1 0 0");
+        assert_eq!(
+            scene.texture_ids(),
+            [TextureId::from_path("assets/a,b.png")]
+        );
+    }
+    #[test]
+    fn degenerate_cube_is_rejected_with_its_line() {
+        let text =
+            "[material stone]\ndiffuse 1 1 1\n\n[objects]\n0 0 0 1 stone\n1 0 0 1 0 1 stone\n";
+        let err = Scene::parse(text).err().unwrap();
+        assert_eq!(
+            err,
+            "Line 6: Degenerate size 1 0 1, every dimension must be positive"
+        );
+    }
+
+    #[test]
+    fn valid_scene_has_no_warnings() {
+        let scene = Scene::parse(SCENE).unwrap();
+        assert!(scene.validate().is_ok());
+        assert_eq!(scene.warnings(), Vec::<String>::new());
+
+        let dark = Scene::parse(&format!("{}\n[light]\nintensity 0\n", SCENE)).unwrap();
+        assert_eq!(dark.warnings().len(), 1);
+        assert!(dark.warnings()[0].starts_with("Light 2: "));
+    }
+
+    /// Directorio temporal vacío y propio de cada prueba
+    fn scene_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("scene-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn stone() -> BTreeMap<String, Material> {
+        BTreeMap::from([("stone".to_string(), Material::black())])
+    }
+
+    fn xs(objects: &[ObjectDef]) -> Vec<f32> {
+        objects.iter().map(|object| object.center.x).collect()
+    }
+
+    #[test]
+    fn directory_files_load_in_alphabetical_order() {
+        let dir = scene_dir("order");
+        std::fs::write(dir.join("b.txt"), "2 0 0 1 stone\n").unwrap();
+        std::fs::write(dir.join("a.txt"), "# suelo\n\n1 0 0 1 stone\n").unwrap();
+        std::fs::write(dir.join("notes.md"), "not a scene\n").unwrap();
+
+        let objects = load_scene_files(dir.to_str().unwrap(), &stone()).unwrap();
+        assert_eq!(xs(&objects), [1.0, 2.0]);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn list_paths_are_relative_to_the_index() {
+        let dir = scene_dir("list");
+        std::fs::create_dir(dir.join("parts")).unwrap();
+        std::fs::write(dir.join("parts/z.txt"), "3 0 0 1 stone\n").unwrap();
+        std::fs::write(dir.join("parts/a.txt"), "4 0 0 1 stone\n").unwrap();
+        // El índice manda sobre el orden alfabético y admite comentarios y líneas vacías
+        std::fs::write(
+            dir.join("scene.list"),
+            "# partes\nparts/z.txt\n\n  parts/a.txt\n",
+        )
+        .unwrap();
+
+        let index = dir.join("scene.list");
+        let objects = load_scene_files(index.to_str().unwrap(), &stone()).unwrap();
+        assert_eq!(xs(&objects), [3.0, 4.0]);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn empty_directory_is_rejected() {
+        let dir = scene_dir("empty");
+        let path = dir.to_str().unwrap();
+        let err = load_scene_files(path, &stone()).err().unwrap();
+        assert_eq!(err, format!("No scene files found in '{}'", path));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn errors_name_the_file_and_line() {
+        let dir = scene_dir("error");
+        let file = dir.join("bad.txt");
+        std::fs::write(&file, "0 0 0 1 stone\n# comentario\n0 0 zero 1 stone\n").unwrap();
+
+        let err = load_scene_files(dir.to_str().unwrap(), &stone())
+            .err()
+            .unwrap();
+        let prefix = format!("{}: Line 3: Invalid z coordinate 'zero'", file.display());
+        assert!(err.starts_with(&prefix), "{}", err);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
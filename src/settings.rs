@@ -0,0 +1,130 @@
+// settings.rs - Per-user view preferences kept between interactive sessions
+use crate::ToneMap;
+
+pub const SETTINGS_FILE: &str = "settings.txt";
+
+/// Preferencias de visualización de la ventana interactiva. No forman parte de la escena:
+/// se guardan al cerrar y se recuperan al abrir, sea cual sea la escena cargada.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ViewSettings {
+    pub exposure: f32,
+    pub tone_map: ToneMap,
+    /// Campo de visión vertical en grados
+    pub fov_degrees: f32,
+    /// Ruta del skybox elegido o `procedural` para el degradado; `None` usa el de la escena
+    pub sky: Option<String>,
+}
+
+/// Valor de `sky` para el degradado procedural
+pub const PROCEDURAL_SKY: &str = "procedural";
+
+impl Default for ViewSettings {
+    fn default() -> Self {
+        ViewSettings {
+            exposure: 1.0,
+            tone_map: ToneMap::None,
+            fov_degrees: 60.0,
+            sky: None,
+        }
+    }
+}
+
+impl ViewSettings {
+    /// Lee `path`; si falta o tiene errores se usan los valores por defecto sin abortar
+    pub fn load(path: &str) -> Self {
+        let Ok(contents) = std::fs::read_to_string(path) else {
+            return ViewSettings::default();
+        };
+
+        ViewSettings::parse(&contents).unwrap_or_else(|e| {
+            eprintln!("Warning: ignoring {}: {}", path, e);
+            ViewSettings::default()
+        })
+    }
+
+    pub fn parse(contents: &str) -> Result<ViewSettings, String> {
+        let mut settings = ViewSettings::default();
+
+        for (line_num, line) in contents.lines().enumerate() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = trimmed
+                .split_once(char::is_whitespace)
+                .unwrap_or((trimmed, ""));
+            let value = value.trim();
+            let invalid = || format!("Line {}: Invalid {} '{}'", line_num + 1, key, value);
+            let positive = || {
+                value
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|v| v.is_finite() && *v > 0.0)
+                    .ok_or_else(invalid)
+            };
+
+            match key {
+                "exposure" => settings.exposure = positive()?,
+                "tonemap" => settings.tone_map = ToneMap::from_name(value).ok_or_else(invalid)?,
+                "fov" => settings.fov_degrees = positive()?.min(179.0),
+                "sky" if !value.is_empty() => settings.sky = Some(value.to_string()),
+                "sky" => return Err(invalid()),
+                _ => {
+                    return Err(format!("Line {}: Unknown setting '{}'", line_num + 1, key));
+                }
+            }
+        }
+
+        Ok(settings)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut out = String::from("# Preferencias de la ventana, se reescriben al cerrar\n");
+        out.push_str(&format!("exposure {}\n", self.exposure));
+        out.push_str(&format!("tonemap {}\n", self.tone_map.name()));
+        out.push_str(&format!("fov {}\n", self.fov_degrees));
+        if let Some(sky) = &self.sky {
+            out.push_str(&format!("sky {}\n", sky));
+        }
+        out
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        std::fs::write(path, self.to_text()).map_err(|e| format!("Failed to write {}: {}", path, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_round_trip() {
+        let settings = ViewSettings {
+            exposure: 1.5,
+            tone_map: ToneMap::Aces,
+            fov_degrees: 45.0,
+            sky: Some(PROCEDURAL_SKY.to_string()),
+        };
+        assert_eq!(ViewSettings::parse(&settings.to_text()).unwrap(), settings);
+
+        // Las claves que faltan conservan su valor por defecto
+        let partial = ViewSettings::parse("fov 75\n").unwrap();
+        assert_eq!(partial.fov_degrees, 75.0);
+        assert_eq!(partial.exposure, 1.0);
+        assert_eq!(partial.sky, None);
+    }
+
+    #[test]
+    fn malformed_settings_are_rejected() {
+        assert!(ViewSettings::parse("exposure bright\n").is_err());
+        assert!(ViewSettings::parse("exposure -1\n").is_err());
+        assert!(ViewSettings::parse("tonemap filmic\n").is_err());
+        assert!(ViewSettings::parse("gamma 2.2\n").is_err());
+        assert_eq!(
+            ViewSettings::load("does/not/exist/settings.txt"),
+            ViewSettings::default()
+        );
+    }
+}
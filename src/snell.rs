@@ -44,4 +44,141 @@ pub fn refract(incident: &Vector3, normal: &Vector3, refractive_index: f32) -> V
         // If k is non-negative, we can calculate the direction of the refracted ray.
         *incident * eta + n * (eta * cosi - k.sqrt())
     }
-}
\ No newline at end of file
+}
+
+/// Índice de refracción del aire, el medio en el que empiezan los rayos de cámara
+pub const AIR_INDEX: f32 = 1.0;
+
+/// Refracción al pasar de un medio con índice `from_index` a otro con `to_index`.
+/// `normal` debe apuntar hacia el lado del que llega el rayo. Devuelve `None` si hay
+/// reflexión total interna.
+pub fn refract_between(
+    incident: &Vector3,
+    normal: &Vector3,
+    from_index: f32,
+    to_index: f32,
+) -> Option<Vector3> {
+    let cosi = (-incident.dot(*normal)).clamp(-1.0, 1.0);
+    let eta = from_index / to_index;
+    let k = 1.0 - eta * eta * (1.0 - cosi * cosi);
+
+    if k < 0.0 {
+        None
+    } else {
+        Some(*incident * eta + *normal * (eta * cosi - k.sqrt()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cube::Cube;
+    use crate::material::Material;
+    use crate::ray_intersect::RayIntersect;
+
+    const EPS: f32 = 1e-5;
+
+    fn assert_vec_eq(a: Vector3, b: Vector3) {
+        assert!(
+            (a.x - b.x).abs() < EPS && (a.y - b.y).abs() < EPS && (a.z - b.z).abs() < EPS,
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    /// Dirección que baja hacia una superficie con normal +Y formando `angle` con la normal
+    fn incoming(angle: f32) -> Vector3 {
+        Vector3::new(angle.sin(), -angle.cos(), 0.0)
+    }
+
+    #[test]
+    fn reflect_mirrors_about_normal() {
+        let normal = Vector3::new(0.0, 1.0, 0.0);
+        let reflected = reflect(&incoming(0.3), &normal);
+        assert_vec_eq(reflected, Vector3::new(0.3_f32.sin(), 0.3_f32.cos(), 0.0));
+    }
+
+    #[test]
+    fn normal_incidence_passes_straight_through() {
+        let normal = Vector3::new(0.0, 1.0, 0.0);
+        let refracted = refract(&incoming(0.0), &normal, 1.5);
+        assert_vec_eq(refracted, Vector3::new(0.0, -1.0, 0.0));
+    }
+
+    #[test]
+    fn entering_denser_medium_at_45_degrees() {
+        let normal = Vector3::new(0.0, 1.0, 0.0);
+        let theta_i = std::f32::consts::FRAC_PI_4;
+
+        // Snell: sin(θt) = (n1 / n2) * sin(θi)
+        let theta_t = (theta_i.sin() / 1.5).asin();
+        let refracted = refract(&incoming(theta_i), &normal, 1.5);
+
+        assert_vec_eq(refracted, incoming(theta_t));
+        assert!((refracted.length() - 1.0).abs() < EPS);
+    }
+
+    #[test]
+    fn leaving_denser_medium_bends_away_from_normal() {
+        // El rayo viaja dentro del vidrio hacia arriba y sale por la cara con normal +Y
+        let normal = Vector3::new(0.0, 1.0, 0.0);
+        let theta_i = 20.0_f32.to_radians();
+        let inside = Vector3::new(theta_i.sin(), theta_i.cos(), 0.0);
+
+        let theta_t = (theta_i.sin() * 1.5).asin();
+        let refracted = refract(&inside, &normal, 1.5);
+
+        assert_vec_eq(refracted, Vector3::new(theta_t.sin(), theta_t.cos(), 0.0));
+    }
+
+    #[test]
+    fn entering_then_leaving_restores_direction() {
+        let normal = Vector3::new(0.0, 1.0, 0.0);
+        let original = incoming(0.6);
+
+        let inside = refract(&original, &normal, 1.5);
+        // Cara inferior de una placa paralela: su normal exterior apunta hacia -Y
+        let exit_normal = Vector3::new(0.0, -1.0, 0.0);
+        let outside = refract(&inside, &exit_normal, 1.5);
+
+        assert_vec_eq(outside, original);
+    }
+
+    #[test]
+    fn total_internal_reflection_returns_zero() {
+        // Ángulo crítico para n = 1.5 es ~41.8°; a 60° no hay rayo refractado
+        let normal = Vector3::new(0.0, 1.0, 0.0);
+        let theta_i = 60.0_f32.to_radians();
+        let inside = Vector3::new(theta_i.sin(), theta_i.cos(), 0.0);
+
+        assert_vec_eq(refract(&inside, &normal, 1.5), Vector3::zero());
+        assert!(refract_between(&inside, &-normal, 1.5, AIR_INDEX).is_none());
+    }
+
+    #[test]
+    fn glass_slab_shifts_the_ray_without_bending_it() {
+        // Placa de vidrio de 1 de grosor en z; el rayo entra por +Z y sale por -Z
+        let slab = Cube::new_rect(Vector3::zero(), 10.0, 10.0, 1.0, Material::black());
+        let original = Vector3::new(0.5, 0.0, -1.0).normalized();
+        let origin = Vector3::new(-2.0, 0.0, 4.0);
+
+        let entry = slab.ray_intersect(&origin, &original);
+        assert!(entry.is_intersecting && !entry.inside);
+        let inside = refract_between(&original, &entry.normal, AIR_INDEX, 1.5).unwrap();
+        assert!(
+            inside.x < original.x,
+            "el rayo se acerca a la normal al entrar"
+        );
+
+        let exit = slab.ray_intersect(&(entry.point + inside * 1e-3), &inside);
+        assert!(exit.is_intersecting && exit.inside);
+        // Desde dentro la normal del impacto ya apunta hacia el rayo
+        let outside = refract_between(&inside, &exit.normal, 1.5, AIR_INDEX).unwrap();
+        assert_vec_eq(outside, original);
+
+        // Sale paralelo pero desplazado respecto a la recta sin vidrio
+        let straight = entry.point + original * (1.0 / -original.z);
+        assert!((exit.point.x - straight.x).abs() > 0.05);
+    }
+}
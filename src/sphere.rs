@@ -8,6 +8,8 @@ pub struct Sphere {
     pub center: Vector3,
     pub radius: f32,
     pub material: Material,
+    /// Invierte la normal hacia adentro, para usar una esfera grande como domo de cielo
+    pub inverted: bool,
 }
 
 impl Sphere {
@@ -30,9 +32,14 @@ impl RayIntersect for Sphere {
         let discriminant = b * b - 4.0 * a * c;
 
         if discriminant > 0.0 {
-            let t = (-b - discriminant.sqrt()) / (2.0 * a);
+            let mut t = (-b - discriminant.sqrt()) / (2.0 * a);
+            // Desde dentro de un domo invertido el impacto útil es la raíz lejana
+            if self.inverted && t <= 0.0 {
+                t = (-b + discriminant.sqrt()) / (2.0 * a);
+            }
             let point = *ray_origin + *ray_direction * t;
-            let normal = (point - self.center).normalized();
+            let outward = (point - self.center).normalized();
+            let normal = if self.inverted { -outward } else { outward };
             let (u, v) = self.get_uv(&point);
             if t > 0.0 {
                 return Intersect::new(
@@ -48,4 +55,38 @@ impl RayIntersect for Sphere {
 
         Intersect::empty()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dome(inverted: bool) -> Sphere {
+        Sphere {
+            center: Vector3::zero(),
+            radius: 10.0,
+            material: Material::black(),
+            inverted,
+        }
+    }
+
+    #[test]
+    fn inverted_sphere_is_hit_from_inside_with_inward_normal() {
+        let direction = Vector3::new(0.0, 0.0, 1.0);
+        let hit = dome(true).ray_intersect(&Vector3::zero(), &direction);
+        assert!(hit.is_intersecting);
+        // Desde el centro la raíz cercana queda detrás del ojo; se toma la lejana
+        assert!((hit.distance - 10.0).abs() < 1e-4);
+        assert!((hit.normal - (-direction)).length() < 1e-4);
+    }
+
+    #[test]
+    fn default_sphere_keeps_outward_normal() {
+        let origin = Vector3::new(0.0, 0.0, -20.0);
+        let direction = Vector3::new(0.0, 0.0, 1.0);
+        let hit = dome(false).ray_intersect(&origin, &direction);
+        assert!(hit.is_intersecting);
+        assert!((hit.distance - 10.0).abs() < 1e-4);
+        assert!((hit.normal - Vector3::new(0.0, 0.0, -1.0)).length() < 1e-4);
+    }
+}
@@ -0,0 +1,116 @@
+// stats.rs - Scene complexity summary printed once the scene and its BVH are built
+use crate::bvh::{AABB, BVHNode, BVHStats};
+use crate::cube::Cube;
+use crate::instance::Instance;
+use std::fmt;
+
+/// Cuánto contiene la escena cargada: sirve para entender por qué un render tarda
+pub struct SceneStats {
+    pub objects: usize,
+    pub subtractive: usize,
+    pub instances: usize,
+    /// Todas las luces, incluidas las que salen de objetos emisivos
+    pub lights: usize,
+    pub emissive_lights: usize,
+    pub bvh: BVHStats,
+    pub bounds: AABB,
+}
+
+impl SceneStats {
+    pub fn new(
+        objects: &[Cube],
+        instances: &[Instance],
+        bvh: &BVHNode,
+        lights: usize,
+        emissive_lights: usize,
+    ) -> Self {
+        let bounds = bvh.bounds();
+        SceneStats {
+            objects: objects.len(),
+            subtractive: objects.iter().filter(|cube| cube.subtractive).count(),
+            instances: instances.len(),
+            lights,
+            emissive_lights,
+            bvh: bvh.stats(),
+            bounds: AABB {
+                min: bounds.min,
+                max: bounds.max,
+            },
+        }
+    }
+}
+
+impl fmt::Display for SceneStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Scene: {} objects ({} subtractive), {} instances, {} lights ({} emissive)",
+            self.objects, self.subtractive, self.instances, self.lights, self.emissive_lights
+        )?;
+        writeln!(
+            f,
+            "BVH: {} nodes, {} leaves, depth {}, {} primitives",
+            self.bvh.nodes, self.bvh.leaves, self.bvh.depth, self.bvh.primitives
+        )?;
+        let (min, max) = (self.bounds.min, self.bounds.max);
+        write!(
+            f,
+            "Bounds: [{:.2} {:.2} {:.2}]..[{:.2} {:.2} {:.2}]",
+            min.x, min.y, min.z, max.x, max.y, max.z
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bvh::DEFAULT_LEAF_SIZE;
+    use crate::instance::Prototype;
+    use crate::material::Material;
+    use raylib::prelude::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn counts_objects_instances_and_tree() {
+        let objects: Vec<Cube> = (0..4)
+            .map(|i| {
+                Cube::new(
+                    Vector3::new(i as f32 * 2.0, 0.0, 0.0),
+                    1.0,
+                    Material::black(),
+                )
+            })
+            .collect();
+        let prototype = Arc::new(Prototype::new(
+            "pair",
+            vec![
+                Cube::new(Vector3::zero(), 1.0, Material::black()),
+                Cube::new(Vector3::new(0.0, 1.0, 0.0), 1.0, Material::black()),
+            ],
+            DEFAULT_LEAF_SIZE,
+        ));
+        let instances: Vec<Instance> = (0..2)
+            .map(|i| Instance {
+                prototype: Arc::clone(&prototype),
+                offset: Vector3::new(0.0, 0.0, -3.0 * (i + 1) as f32),
+            })
+            .collect();
+        let mut indices: Vec<usize> = (0..objects.len()).collect();
+        let bvh = BVHNode::build_with_instances(&objects, &mut indices, &instances, 1);
+
+        let stats = SceneStats::new(&objects, &instances, &bvh, 3, 1);
+        assert_eq!(stats.objects, 4);
+        assert_eq!(stats.instances, 2);
+        // 4 hojas de cubos y 2 de instancias unidas por 5 nodos internos
+        assert_eq!(stats.bvh.leaves, 6);
+        assert_eq!(stats.bvh.nodes, 11);
+        assert_eq!(stats.bvh.depth, 3);
+        assert_eq!(stats.bvh.primitives, 8);
+        assert_eq!(stats.bounds.min, Vector3::new(-0.5, -0.5, -6.5));
+        assert_eq!(stats.bounds.max, Vector3::new(6.5, 1.5, 0.5));
+
+        let text = stats.to_string();
+        assert!(text.contains("4 objects"), "{}", text);
+        assert!(text.contains("3 lights (1 emissive)"), "{}", text);
+    }
+}
@@ -1,6 +1,57 @@
 // textures.rs
 use raylib::prelude::*;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+
+/// Identificador compacto de una textura. Cada ruta recibe el suyo una sola vez, al leer
+/// la escena, y el render indexa con él en lugar de calcular el hash de la ruta por píxel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TextureId(u32);
+
+// Rutas registradas, en el orden de sus ids
+static TEXTURE_PATHS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+impl TextureId {
+    /// Id de la ruta, registrándola si es la primera vez que aparece
+    pub fn from_path(path: &str) -> Self {
+        let mut paths = TEXTURE_PATHS.lock().unwrap();
+        let index = match paths.iter().position(|p| p == path) {
+            Some(index) => index,
+            None => {
+                paths.push(path.to_string());
+                paths.len() - 1
+            }
+        };
+        TextureId(index as u32)
+    }
+
+    /// Ruta con la que se registró, para cargar la imagen o escribir la escena
+    pub fn path(self) -> String {
+        TEXTURE_PATHS.lock().unwrap()[self.0 as usize].clone()
+    }
+
+    fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+// En JSON la textura se guarda por su ruta, igual que en los archivos de escena
+#[cfg(feature = "json-scene")]
+impl serde::Serialize for TextureId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.path())
+    }
+}
+
+#[cfg(feature = "json-scene")]
+impl<'de> serde::Deserialize<'de> for TextureId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let path = <String as serde::Deserialize>::deserialize(deserializer)?;
+        Ok(TextureId::from_path(&path))
+    }
+}
 
 struct CpuTexture {
     width: i32,
@@ -29,11 +80,75 @@ impl CpuTexture {
             pixels,
         }
     }
+
+    /// Memoria aproximada que ocupan los píxeles decodificados
+    fn bytes(&self) -> usize {
+        self.pixels.len() * std::mem::size_of::<Vector3>()
+    }
+
+    /// Tablero magenta y negro que delata a simple vista una textura que falta
+    fn checkerboard() -> Self {
+        let magenta = Vector3::new(1.0, 0.0, 1.0);
+        let pixels = (0..PLACEHOLDER_SIZE * PLACEHOLDER_SIZE)
+            .map(|i| {
+                let (x, y) = (i % PLACEHOLDER_SIZE, i / PLACEHOLDER_SIZE);
+                if (x + y) % 2 == 0 {
+                    magenta
+                } else {
+                    Vector3::zero()
+                }
+            })
+            .collect();
+
+        CpuTexture {
+            width: PLACEHOLDER_SIZE,
+            height: PLACEHOLDER_SIZE,
+            pixels,
+        }
+    }
+
+    /// Reconstruye una imagen de raylib para subirla a la GPU desde el hilo principal
+    fn to_image(&self) -> Image {
+        let mut image = Image::gen_image_color(self.width, self.height, Color::BLACK);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let p = self.pixels[(y * self.width + x) as usize];
+                let color = Color::new(
+                    (p.x * 255.0).round() as u8,
+                    (p.y * 255.0).round() as u8,
+                    (p.z * 255.0).round() as u8,
+                    255,
+                );
+                image.draw_pixel(x, y, color);
+            }
+        }
+        image
+    }
+}
+
+// Muestras por eje del filtro de textura guiado por diferenciales de rayo
+const MAX_FILTER_TAPS: u32 = 8;
+
+// Casillas por lado del tablero que reemplaza a las texturas faltantes
+const PLACEHOLDER_SIZE: i32 = 8;
+
+/// Copia en CPU de una textura. Una vez descartada por el presupuesto de memoria, el primer
+/// hilo que la vuelve a muestrear la recarga del disco mientras los demás esperan.
+#[derive(Default)]
+struct CpuSlot {
+    texture: OnceLock<Option<CpuTexture>>,
+    evicted: bool,
+    /// Último frame en el que se muestreó, para descartar primero la menos usada
+    last_used: AtomicU64,
 }
 
 pub struct TextureManager {
-    cpu_textures: HashMap<String, CpuTexture>,
-    textures: HashMap<String, Texture2D>, // Store GPU textures for rendering
+    cpu_textures: Vec<CpuSlot>,              // Indexado por TextureId
+    textures: HashMap<TextureId, Texture2D>, // Store GPU textures for rendering
+    placeholder: Option<CpuTexture>,
+    /// Bytes de texturas en CPU que se mantienen entre frames; `None` no pone límite
+    memory_budget: Option<usize>,
+    frame: u64,
 }
 
 impl TextureManager {
@@ -46,9 +161,10 @@ impl TextureManager {
         rl: &mut RaylibHandle,
         thread: &RaylibThread,
         path: &str,
-    ) {
-        if self.textures.contains_key(path) {
-            return;
+    ) -> TextureId {
+        let id = TextureId::from_path(path);
+        if self.textures.contains_key(&id) {
+            return id;
         }
 
         let image = Image::load_image(path)
@@ -60,17 +176,226 @@ impl TextureManager {
 
         let cpu_texture = CpuTexture::from_image(&image);
 
-        self.cpu_textures.insert(path.to_string(), cpu_texture);
-        self.textures.insert(path.to_string(), texture);
+        self.insert_cpu_texture(id, cpu_texture);
+        self.textures.insert(id, texture);
+        id
+    }
+
+    /// Si se activa, las rutas sin textura cargada muestran un tablero magenta en lugar
+    /// de caer en silencio al color difuso del material
+    pub fn set_missing_placeholder(&mut self, enabled: bool) {
+        self.placeholder = if enabled {
+            Some(CpuTexture::checkerboard())
+        } else {
+            None
+        };
+    }
+
+    /// Limita la memoria de las texturas en CPU; al terminar cada frame se descartan las
+    /// menos usadas hasta caber, y se recargan del disco si se vuelven a necesitar
+    pub fn set_memory_budget(&mut self, bytes: Option<usize>) {
+        self.memory_budget = bytes;
+    }
+
+    fn insert_cpu_texture(&mut self, id: TextureId, cpu_texture: CpuTexture) {
+        if self.cpu_textures.len() <= id.index() {
+            self.cpu_textures
+                .resize_with(id.index() + 1, CpuSlot::default);
+        }
+        let slot = &mut self.cpu_textures[id.index()];
+        slot.texture = OnceLock::from(Some(cpu_texture));
+        slot.evicted = false;
+        *slot.last_used.get_mut() = self.frame;
+    }
+
+    fn loaded(&self, id: TextureId) -> Option<&CpuTexture> {
+        let slot = self.cpu_textures.get(id.index())?;
+        let texture = if slot.evicted {
+            slot.texture.get_or_init(|| {
+                Image::load_image(&id.path())
+                    .map(|image| CpuTexture::from_image(&image))
+                    .ok()
+            })
+        } else {
+            slot.texture.get()?
+        };
+
+        // Solo se escribe al cambiar de frame, para no competir por la línea de caché en cada texel
+        if slot.last_used.load(Ordering::Relaxed) != self.frame {
+            slot.last_used.store(self.frame, Ordering::Relaxed);
+        }
+        texture.as_ref()
+    }
+
+    /// Memoria que ocupan ahora las texturas en CPU
+    pub fn resident_bytes(&self) -> usize {
+        self.cpu_textures
+            .iter()
+            .filter_map(|slot| slot.texture.get().and_then(Option::as_ref))
+            .map(CpuTexture::bytes)
+            .sum()
+    }
+
+    /// Cierra el frame: con presupuesto, descarta las texturas menos usadas hasta caber en
+    /// él. Las muestreadas en este frame nunca se descartan, y como hace falta `&mut self`
+    /// ningún render puede estar leyéndolas mientras tanto.
+    pub fn end_frame(&mut self) {
+        if let Some(budget) = self.memory_budget {
+            let mut resident = self.resident_bytes();
+            while resident > budget {
+                let frame = self.frame;
+                let Some(victim) = self
+                    .cpu_textures
+                    .iter_mut()
+                    .filter(|slot| matches!(slot.texture.get(), Some(Some(_))))
+                    .filter(|slot| slot.last_used.load(Ordering::Relaxed) < frame)
+                    .min_by_key(|slot| slot.last_used.load(Ordering::Relaxed))
+                else {
+                    break;
+                };
+
+                if let Some(Some(texture)) = victim.texture.take() {
+                    resident -= texture.bytes();
+                }
+                victim.evicted = true;
+            }
+        }
+        self.frame += 1;
+    }
+
+    fn cpu_texture(&self, id: TextureId) -> Option<&CpuTexture> {
+        self.loaded(id).or(self.placeholder.as_ref())
+    }
+
+    /// Carga varias texturas decodificando las imágenes en paralelo. La subida a la GPU
+    /// se hace después en el hilo principal, como exige raylib, y solo si hay ventana.
+    /// Una textura que falla no aborta la carga: su error se devuelve y la ruta queda sin
+    /// textura (o con el tablero de `set_missing_placeholder`).
+    pub fn load_textures(
+        &mut self,
+        mut gpu: Option<(&mut RaylibHandle, &RaylibThread)>,
+        ids: &[TextureId],
+    ) -> Vec<Result<(), String>> {
+        let mut pending: Vec<(TextureId, String)> = Vec::new();
+        for &id in ids {
+            if self.loaded(id).is_none() && !pending.iter().any(|(p, _)| *p == id) {
+                pending.push((id, id.path()));
+            }
+        }
+
+        let decoded: Vec<Result<CpuTexture, String>> = thread::scope(|s| {
+            let handles: Vec<_> = pending
+                .iter()
+                .map(|(_, path)| {
+                    s.spawn(move || {
+                        Image::load_image(path)
+                            .map(|image| CpuTexture::from_image(&image))
+                            .map_err(|e| format!("Failed to load image {}: {}", path, e))
+                    })
+                })
+                .collect();
+
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+
+        let mut results = Vec::with_capacity(pending.len());
+        for ((id, path), cpu_texture) in pending.into_iter().zip(decoded) {
+            let cpu_texture = match cpu_texture {
+                Ok(cpu_texture) => cpu_texture,
+                Err(e) => {
+                    results.push(Err(e));
+                    continue;
+                }
+            };
+
+            if let Some((rl, thread)) = gpu.as_mut() {
+                match rl.load_texture_from_image(thread, &cpu_texture.to_image()) {
+                    Ok(texture) => {
+                        self.textures.insert(id, texture);
+                    }
+                    Err(e) => {
+                        self.insert_cpu_texture(id, cpu_texture);
+                        results.push(Err(format!("Failed to load texture {}: {}", path, e)));
+                        continue;
+                    }
+                }
+            }
+
+            self.insert_cpu_texture(id, cpu_texture);
+            results.push(Ok(()));
+        }
+
+        results
+    }
+
+    /// Carga solo la copia en CPU de la textura, sin necesitar una ventana de raylib
+    pub fn load_cpu_texture(&mut self, path: &str) -> Result<TextureId, String> {
+        let id = TextureId::from_path(path);
+        if self.loaded(id).is_some() {
+            return Ok(id);
+        }
+
+        let image =
+            Image::load_image(path).map_err(|e| format!("Failed to load image {}: {}", path, e))?;
+
+        self.insert_cpu_texture(id, CpuTexture::from_image(&image));
+        Ok(id)
+    }
+
+    /// Dimensiones de la textura en CPU, disponibles aunque no exista la copia en GPU
+    pub fn texture_size(&self, id: TextureId) -> Option<(u32, u32)> {
+        self.cpu_texture(id)
+            .map(|t| (t.width as u32, t.height as u32))
+    }
+
+    /// Color del texel en las coordenadas (u, v) en [0, 1], usando el ancho y alto propios
+    /// de la textura para que las no cuadradas no se estiren. Los bordes se recortan.
+    pub fn sample(&self, id: TextureId, u: f32, v: f32) -> Option<Vector3> {
+        let (width, height) = self.texture_size(id)?;
+        let tx = ((u.clamp(0.0, 1.0) * width as f32) as u32).min(width - 1);
+        let ty = ((v.clamp(0.0, 1.0) * height as f32) as u32).min(height - 1);
+        Some(self.get_pixel_color(id, tx, ty))
+    }
+
+    /// Como `sample`, pero promediando los texels que cubre una huella de `width` x `height`
+    /// en (u, v) centrada en el punto. Con huellas de un texel o menos equivale a `sample`.
+    pub fn sample_filtered(
+        &self,
+        id: TextureId,
+        u: f32,
+        v: f32,
+        width: f32,
+        height: f32,
+    ) -> Option<Vector3> {
+        let (tex_width, tex_height) = self.texture_size(id)?;
+        let taps = |extent: f32, size: u32| {
+            ((extent * size as f32).ceil() as u32).clamp(1, MAX_FILTER_TAPS)
+        };
+        let (taps_u, taps_v) = (taps(width, tex_width), taps(height, tex_height));
+        if taps_u == 1 && taps_v == 1 {
+            return self.sample(id, u, v);
+        }
+
+        // Una muestra en el centro de cada celda de la huella; las huellas enormes (superficies
+        // rasantes) se recortan a MAX_FILTER_TAPS por lado para acotar el coste
+        let mut sum = Vector3::zero();
+        for j in 0..taps_v {
+            for i in 0..taps_u {
+                let su = u + width * ((i as f32 + 0.5) / taps_u as f32 - 0.5);
+                let sv = v + height * ((j as f32 + 0.5) / taps_v as f32 - 0.5);
+                sum += self.sample(id, su, sv)?;
+            }
+        }
+        Some(sum / (taps_u * taps_v) as f32)
     }
 
     pub fn get_pixel_color(
         &self,
-        path: &str,
+        id: TextureId,
         tx: u32,
         ty: u32,
     ) -> Vector3 {
-        if let Some(cpu_texture) = self.cpu_textures.get(path) {
+        if let Some(cpu_texture) = self.cpu_texture(id) {
             let x = tx.min(cpu_texture.width as u32 - 1) as i32;
             let y = ty.min(cpu_texture.height as u32 - 1) as i32;
 
@@ -91,18 +416,18 @@ impl TextureManager {
 
     pub fn get_texture(
         &self,
-        path: &str,
+        id: TextureId,
     ) -> Option<&Texture2D> {
-        self.textures.get(path)
+        self.textures.get(&id)
     }
 
     pub fn get_normal_from_map(
         &self,
-        path: &str,
+        id: TextureId,
         tx: u32,
         ty: u32,
     ) -> Option<Vector3> {
-        if let Some(cpu_texture) = self.cpu_textures.get(path) {
+        if let Some(cpu_texture) = self.loaded(id) {
             let x = tx.min(cpu_texture.width as u32 - 1) as i32;
             let y = ty.min(cpu_texture.height as u32 - 1) as i32;
 
@@ -131,8 +456,161 @@ impl TextureManager {
 impl Default for TextureManager {
     fn default() -> Self {
         TextureManager {
-            cpu_textures: HashMap::new(),
+            cpu_textures: Vec::new(),
             textures: HashMap::new(),
+            placeholder: None,
+            memory_budget: None,
+            frame: 0,
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cube::Cube;
+    use crate::material::Material;
+    use crate::ray_intersect::RayIntersect;
+
+    /// Textura de 4x2 donde cada texel codifica su columna en rojo y su fila en verde
+    fn wide_texture() -> (TextureManager, TextureId) {
+        let (width, height) = (4, 2);
+        let pixels = (0..width * height)
+            .map(|i| Vector3::new((i % width) as f32, (i / width) as f32, 0.0))
+            .collect();
+
+        let mut manager = TextureManager::new();
+        let id = TextureId::from_path("wide");
+        manager.insert_cpu_texture(
+            id,
+            CpuTexture {
+                width,
+                height,
+                pixels,
+            },
+        );
+        (manager, id)
+    }
+
+    #[test]
+    fn filtered_sample_averages_the_footprint() {
+        let (manager, wide) = wide_texture();
+
+        // Menos de un texel: el mismo texel que sin filtro
+        assert_eq!(
+            manager.sample_filtered(wide, 0.6, 0.25, 0.1, 0.2),
+            manager.sample(wide, 0.6, 0.25)
+        );
+
+        // Media textura de ancho y toda la altura: columnas 1 y 2, filas 0 y 1
+        let average = manager.sample_filtered(wide, 0.5, 0.5, 0.5, 1.0).unwrap();
+        assert!((average.x - 1.5).abs() < 1e-6 && (average.y - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn non_square_texture_uses_its_own_dimensions() {
+        let (manager, wide) = wide_texture();
+        assert_eq!(manager.texture_size(wide), Some((4, 2)));
+
+        // Centros de texel: u avanza en cuartos y v en mitades
+        assert_eq!(
+            manager.sample(wide, 0.125, 0.25),
+            Some(Vector3::new(0.0, 0.0, 0.0))
+        );
+        assert_eq!(
+            manager.sample(wide, 0.875, 0.25),
+            Some(Vector3::new(3.0, 0.0, 0.0))
+        );
+        assert_eq!(
+            manager.sample(wide, 0.375, 0.75),
+            Some(Vector3::new(1.0, 1.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn uv_edges_stay_inside_the_texture() {
+        let (manager, wide) = wide_texture();
+        assert_eq!(
+            manager.sample(wide, 1.0, 1.0),
+            Some(Vector3::new(3.0, 1.0, 0.0))
+        );
+        assert_eq!(
+            manager.sample(wide, -0.5, 0.0),
+            Some(Vector3::new(0.0, 0.0, 0.0))
+        );
+        assert_eq!(
+            manager.sample(TextureId::from_path("missing"), 0.5, 0.5),
+            None
+        );
+    }
+
+    #[test]
+    fn cube_face_maps_every_column_of_a_wide_texture() {
+        let (manager, wide) = wide_texture();
+        let cube = Cube::new(Vector3::zero(), 2.0, Material::black());
+        let dir = Vector3::new(0.0, 0.0, -1.0);
+
+        // Cara +Z: u recorre x de -1 a 1, así que cada cuarto de la cara es una columna
+        for column in 0..4 {
+            let x = -1.0 + 0.5 * column as f32 + 0.25;
+            let hit = cube.ray_intersect(&Vector3::new(x, 0.5, 5.0), &dir);
+            let texel = manager.sample(wide, hit.u, hit.v).unwrap();
+            assert_eq!(texel.x, column as f32);
+            // y = 0.5 queda en la mitad superior de la imagen (v = 0.25)
+            assert_eq!(texel.y, 0.0);
+        }
+    }
+
+    #[test]
+    fn ids_are_stable_per_path() {
+        let portal = TextureId::from_path("assets/portal.png");
+        assert_eq!(TextureId::from_path("assets/portal.png"), portal);
+        assert_ne!(TextureId::from_path("assets/obsidian.png"), portal);
+        assert_eq!(portal.path(), "assets/portal.png");
+
+        // Un id sin textura cargada no tiene tamaño ni color
+        assert_eq!(TextureManager::new().texture_size(portal), None);
+    }
+
+    #[test]
+    fn budget_evicts_the_least_recently_used_texture() {
+        let (mut manager, wide) = wide_texture();
+        let other = TextureId::from_path("budget/other");
+        let recent = TextureId::from_path("budget/recent");
+        for id in [other, recent] {
+            let pixels = vec![Vector3::one(); 8];
+            manager.insert_cpu_texture(
+                id,
+                CpuTexture {
+                    width: 4,
+                    height: 2,
+                    pixels,
+                },
+            );
+        }
+        let each = 8 * std::mem::size_of::<Vector3>();
+        assert_eq!(manager.resident_bytes(), 3 * each);
+
+        // Sin presupuesto no se descarta nada
+        manager.end_frame();
+        assert_eq!(manager.resident_bytes(), 3 * each);
+
+        // `other` es la única que no se ha muestreado desde que se cargó
+        manager.set_memory_budget(Some(2 * each));
+        manager.sample(recent, 0.5, 0.5);
+        manager.sample(wide, 0.5, 0.5);
+        manager.end_frame();
+        assert_eq!(manager.resident_bytes(), 2 * each);
+        assert!(manager.cpu_textures[other.index()].evicted);
+
+        // Aunque nada cabe, lo muestreado en el frame en curso se conserva
+        manager.set_memory_budget(Some(0));
+        manager.sample(wide, 0.5, 0.5);
+        manager.end_frame();
+        assert_eq!(manager.resident_bytes(), each);
+        assert_eq!(
+            manager.sample(wide, 0.875, 0.25),
+            Some(Vector3::new(3.0, 0.0, 0.0))
+        );
+    }
+}